@@ -4,6 +4,11 @@ use tempfile;
 fn main() {
     println!("cargo::rustc-check-cfg=cfg(rrdsys_use_pregen)");
 
+    // Nothing to link, and the pregenerated bindings are always used
+    if env::var("CARGO_FEATURE_DYNAMIC_LOADING").is_ok() {
+        return;
+    }
+
     if env::var("DOCS_RS").is_ok() {
         println!("cargo::rustc-cfg=rrdsys_use_pregen");
         return;
@@ -76,6 +81,12 @@ fn create_bindings(location: HeaderLocation) {
         .use_core()
         .opaque_type("_IO_FILE")     // Treat as opaque - we only use FILE*, never sizeof(FILE)
         .parse_callbacks(Box::new(bindgen::CargoCallbacks::new()));
+    if env::var("CARGO_FEATURE_GRAPH").is_err() {
+        // Not present in librrd built without graph support
+        builder = builder
+            .blocklist_function("rrd_graph.*")
+            .blocklist_function("rrd_xport.*");
+    }
     if let HeaderLocation::NonStandardLocation(location) = location {
         builder = builder.clang_arg(format!("-I{}", location.to_string_lossy()));
     } else {
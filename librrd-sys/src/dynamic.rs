@@ -0,0 +1,178 @@
+//! Runtime loading of `librrd`, used instead of linking with the `dynamic-loading` feature.
+
+use libloading::Library;
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fmt,
+    sync::{Mutex, OnceLock},
+};
+
+/// If set, the path of the library to load instead of searching the default names.
+pub const LIBRARY_PATH_ENV: &str = "RRD_SYS_LIBRARY";
+
+#[cfg(target_os = "macos")]
+const DEFAULT_NAMES: &[&str] = &["librrd.8.dylib", "librrd.dylib"];
+#[cfg(windows)]
+const DEFAULT_NAMES: &[&str] = &["librrd-8.dll", "librrd.dll"];
+#[cfg(not(any(target_os = "macos", windows)))]
+const DEFAULT_NAMES: &[&str] = &["librrd.so.8", "librrd.so"];
+
+static LIBRARY: OnceLock<Library> = OnceLock::new();
+/// Held while loading, so that only one library is ever loaded
+static LOADING: Mutex<()> = Mutex::new(());
+
+/// Load `librrd` from `path`.
+///
+/// This is only needed to use a library other than the default, and must be called before any
+/// `rrd_*` function is used. Otherwise, the library at [`LIBRARY_PATH_ENV`] or a default name for
+/// the platform (e.g. `librrd.so.8`) is loaded when first needed.
+pub fn load_library(path: impl AsRef<OsStr>) -> Result<(), LoadError> {
+    let _guard = LOADING.lock().unwrap_or_else(|e| e.into_inner());
+    if LIBRARY.get().is_some() {
+        return Err(LoadError::AlreadyLoaded);
+    }
+    let library = open(path.as_ref())?;
+    let _ = LIBRARY.set(library);
+    Ok(())
+}
+
+/// Returns the loaded library, loading the default if no library has been loaded yet.
+///
+/// A failed load is not remembered, so it is retried the next time.
+pub fn library() -> Result<&'static Library, LoadError> {
+    if let Some(library) = LIBRARY.get() {
+        return Ok(library);
+    }
+
+    let _guard = LOADING.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(library) = LIBRARY.get() {
+        return Ok(library);
+    }
+    let library = match env::var_os(LIBRARY_PATH_ENV) {
+        Some(path) => open(&path)?,
+        None => {
+            let mut last_error = None;
+            let mut found = None;
+            for name in DEFAULT_NAMES {
+                match open(OsStr::new(name)) {
+                    Ok(library) => {
+                        found = Some(library);
+                        break;
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            match found {
+                Some(library) => library,
+                None => return Err(last_error.expect("At least one default name")),
+            }
+        }
+    };
+    Ok(LIBRARY.get_or_init(|| library))
+}
+
+/// Returns `Ok` if `name` can be resolved in the loaded library (loading the default if needed).
+///
+/// Use this to find out whether calling the `rrd_*` function of the same name would succeed.
+pub fn check_symbol(name: &str) -> Result<(), LoadError> {
+    symbol::<unsafe extern "C" fn()>(name).map(|_| ())
+}
+
+/// Resolve `name` to a value of type `T`, which must be the correct function pointer type.
+pub(crate) fn symbol<T: Copy>(name: &str) -> Result<T, LoadError> {
+    let library = library()?;
+    let mut name_bytes = name.as_bytes().to_vec();
+    name_bytes.push(0);
+    unsafe { library.get::<T>(&name_bytes) }
+        .map(|symbol| *symbol)
+        .map_err(|e| LoadError::Symbol {
+            name: name.to_string(),
+            message: e.to_string(),
+        })
+}
+
+fn open(path: &OsStr) -> Result<Library, LoadError> {
+    unsafe { Library::new(path) }.map_err(|e| LoadError::Library {
+        path: path.to_owned(),
+        message: e.to_string(),
+    })
+}
+
+/// `librrd` or one of its symbols could not be loaded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The library could not be loaded
+    Library {
+        /// The path or name that was tried
+        path: OsString,
+        /// Why it couldn't be loaded
+        message: String,
+    },
+    /// The library was loaded, but does not have a symbol, e.g. because it's an older version
+    Symbol {
+        /// The missing symbol
+        name: String,
+        /// Why it couldn't be loaded
+        message: String,
+    },
+    /// [`load_library`] was called after a library was already loaded
+    AlreadyLoaded,
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Library { path, message } => {
+                write!(f, "Could not load {}: {message}", path.to_string_lossy())
+            }
+            LoadError::Symbol { name, message } => {
+                write!(f, "Could not find {name} in librrd: {message}")
+            }
+            LoadError::AlreadyLoaded => f.write_str("librrd is already loaded"),
+        }
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+/// Define a function for each `librrd` function, which calls the function resolved at runtime.
+macro_rules! dynamic_fns {
+    ($(pub fn $name:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;)*) => {
+        $(
+            /// Calls the function of the same name in the dynamically loaded `librrd`.
+            ///
+            /// # Panics
+            ///
+            /// If `librrd` can't be loaded or lacks this function. Use
+            #[doc = concat!("[`check_symbol(\"", stringify!($name), "\")`](check_symbol)")]
+            /// first to avoid that.
+            pub unsafe fn $name($($arg: $ty),*) $(-> $ret)? {
+                type F = unsafe extern "C" fn($($ty),*) $(-> $ret)?;
+                static FN: std::sync::OnceLock<F> = std::sync::OnceLock::new();
+                let f = FN.get_or_init(|| {
+                    $crate::dynamic::symbol::<F>(stringify!($name)).unwrap_or_else(|e| panic!("{e}"))
+                });
+                f($($arg),*)
+            }
+        )*
+    };
+}
+pub(crate) use dynamic_fns;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nonexistent_library() {
+        let path = "/nonexistent/librrd.so";
+        match open(OsStr::new(path)) {
+            Err(LoadError::Library { path: p, message }) => {
+                assert_eq!(OsStr::new(path), p);
+                assert!(!message.is_empty());
+            }
+            other => panic!("Unexpected {other:?}"),
+        }
+    }
+}
@@ -1,6 +1,10 @@
 //! This exposes `bindgen` bindings to [`librrd`](https://oss.oetiker.ch/rrdtool/index.en.html).
 //!
 //! For a high level library built on top of this, see [`rrd`](https://crates.io/crates/rrd).
+//!
+//! The graphing functions (`rrd_graph`, `rrd_graph_v`, `rrd_xport`) are only declared with the
+//! `graph` feature, which is enabled by default. Without it, `librrd` builds configured with
+//! `--disable-rrd_graph` can be linked.
 
 #![allow(non_upper_case_globals)]
 #![allow(non_camel_case_types)]
@@ -12,7 +16,91 @@ pub use core::ffi::c_int as rrd_int;
 pub use core::ffi::c_ulong as rrd_ulong;
 pub use core::ffi::c_void as rrd_void;
 
-#[cfg(rrdsys_use_pregen)]
+#[cfg(all(rrdsys_use_pregen, not(feature = "dynamic-loading")))]
 include!("pregen/bindings.rs");
-#[cfg(not(rrdsys_use_pregen))]
+#[cfg(all(rrdsys_use_pregen, feature = "graph", not(feature = "dynamic-loading")))]
+include!("pregen/graph.rs");
+#[cfg(all(not(rrdsys_use_pregen), not(feature = "dynamic-loading")))]
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+// With `dynamic-loading`, the types come from the pregenerated bindings, and the functions below
+// shadow the `extern` declarations of the same name. Other functions are still declared, but
+// can't be linked.
+#[cfg(feature = "dynamic-loading")]
+#[allow(dead_code)]
+mod bindings {
+    include!("pregen/bindings.rs");
+    #[cfg(feature = "graph")]
+    include!("pregen/graph.rs");
+}
+#[cfg(feature = "dynamic-loading")]
+pub use bindings::*;
+
+#[cfg(feature = "dynamic-loading")]
+mod dynamic;
+#[cfg(feature = "dynamic-loading")]
+pub use dynamic::{check_symbol, library, load_library, LoadError, LIBRARY_PATH_ENV};
+
+#[cfg(feature = "dynamic-loading")]
+dynamic::dynamic_fns! {
+    pub fn rrd_create_r2(
+        filename: *const rrd_char,
+        pdp_step: rrd_ulong,
+        last_up: time_t,
+        no_overwrite: rrd_int,
+        sources: *mut *const rrd_char,
+        _template: *const rrd_char,
+        argc: rrd_int,
+        argv: *mut *const rrd_char,
+    ) -> rrd_int;
+    pub fn rrd_updatex_r(
+        filename: *const rrd_char,
+        _template: *const rrd_char,
+        extra_flags: rrd_int,
+        argc: rrd_int,
+        argv: *mut *const rrd_char,
+    ) -> rrd_int;
+    pub fn rrd_fetch_r(
+        filename: *const rrd_char,
+        cf: *const rrd_char,
+        start: *mut time_t,
+        end: *mut time_t,
+        step: *mut rrd_ulong,
+        ds_cnt: *mut rrd_ulong,
+        ds_namv: *mut *mut *mut rrd_char,
+        data: *mut *mut rrd_value_t,
+    ) -> rrd_int;
+    pub fn rrd_info_r(filename: *const rrd_char) -> *mut rrd_info_t;
+    pub fn rrd_info_free(arg1: *mut rrd_info_t);
+    pub fn rrd_lastupdate_r(
+        filename: *const rrd_char,
+        ret_last_update: *mut time_t,
+        ret_ds_count: *mut rrd_ulong,
+        ret_ds_names: *mut *mut *mut rrd_char,
+        ret_last_ds: *mut *mut *mut rrd_char,
+    ) -> rrd_int;
+    pub fn rrd_first_r(filename: *const rrd_char, rraindex: rrd_int) -> time_t;
+    pub fn rrd_last_r(filename: *const rrd_char) -> time_t;
+    pub fn rrd_freemem(mem: *mut rrd_void);
+    pub fn rrd_get_context() -> *mut rrd_context_t;
+    pub fn rrd_clear_error();
+    pub fn rrd_get_error() -> *mut rrd_char;
+    pub fn rrd_thread_init();
+    pub fn rrd_strversion() -> *mut rrd_char;
+}
+
+#[cfg(all(feature = "dynamic-loading", feature = "graph"))]
+dynamic::dynamic_fns! {
+    pub fn rrd_graph_v(arg1: rrd_int, arg2: *mut *mut rrd_char) -> *mut rrd_info_t;
+    pub fn rrd_xport(
+        arg1: rrd_int,
+        arg2: *mut *mut rrd_char,
+        arg3: *mut rrd_int,
+        arg4: *mut time_t,
+        arg5: *mut time_t,
+        arg6: *mut rrd_ulong,
+        arg7: *mut rrd_ulong,
+        arg8: *mut *mut *mut rrd_char,
+        arg9: *mut *mut rrd_value_t,
+    ) -> rrd_int;
+}
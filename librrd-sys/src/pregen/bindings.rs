@@ -511,24 +511,6 @@ extern "C" {
         arg2: *mut *mut ::core::ffi::c_char,
     ) -> *mut rrd_info_t;
 }
-extern "C" {
-    pub fn rrd_graph(
-        argc: ::core::ffi::c_int,
-        argv: *mut *mut ::core::ffi::c_char,
-        prdata: *mut *mut *mut ::core::ffi::c_char,
-        xsize: *mut ::core::ffi::c_int,
-        ysize: *mut ::core::ffi::c_int,
-        stream: *mut FILE,
-        ymin: *mut f64,
-        ymax: *mut f64
-    ) -> ::core::ffi::c_int;
-}
-extern "C" {
-    pub fn rrd_graph_v(
-        arg1: ::core::ffi::c_int,
-        arg2: *mut *mut ::core::ffi::c_char,
-    ) -> *mut rrd_info_t;
-}
 extern "C" {
     pub fn rrd_fetch(
         arg1: ::core::ffi::c_int,
@@ -583,19 +565,6 @@ extern "C" {
 extern "C" {
     pub fn rrd_version() -> f64;
 }
-extern "C" {
-    pub fn rrd_xport(
-        arg1: ::core::ffi::c_int,
-        arg2: *mut *mut ::core::ffi::c_char,
-        arg3: *mut ::core::ffi::c_int,
-        arg4: *mut time_t,
-        arg5: *mut time_t,
-        arg6: *mut ::core::ffi::c_ulong,
-        arg7: *mut ::core::ffi::c_ulong,
-        arg8: *mut *mut *mut ::core::ffi::c_char,
-        arg9: *mut *mut rrd_value_t,
-    ) -> ::core::ffi::c_int;
-}
 extern "C" {
     pub fn rrd_flushcached(
         argc: ::core::ffi::c_int,
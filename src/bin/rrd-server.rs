@@ -0,0 +1,51 @@
+//! Serve the RRDs in a directory over HTTP. See `rrd::server` for the endpoints.
+//!
+//! Usage: `rrd-server ROOT [ADDR] [MAX_CONCURRENT]`
+//!
+//! `ADDR` defaults to `127.0.0.1:8080`.
+
+use rrd::server::{Server, ServerConfig};
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (root, addr, max_concurrent) = match args.as_slice() {
+        [root] => (root, "127.0.0.1:8080", None),
+        [root, addr] => (root, addr.as_str(), None),
+        [root, addr, max_concurrent] => (root, addr.as_str(), Some(max_concurrent)),
+        _ => {
+            eprintln!("Usage: rrd-server ROOT [ADDR] [MAX_CONCURRENT]");
+            return ExitCode::FAILURE;
+        }
+    };
+
+    let mut config = match ServerConfig::new(root) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(max_concurrent) = max_concurrent {
+        match max_concurrent.parse() {
+            Ok(max_concurrent) => config.max_concurrent = max_concurrent,
+            Err(_) => {
+                eprintln!("Invalid MAX_CONCURRENT {max_concurrent:?}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let server = match Server::bind(addr, config) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("{e}");
+            return ExitCode::FAILURE;
+        }
+    };
+    if let Some(addr) = server.local_addr() {
+        eprintln!("Serving {root} on http://{addr}");
+    }
+    server.run();
+    ExitCode::SUCCESS
+}
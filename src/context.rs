@@ -0,0 +1,68 @@
+//! Management of `librrd`'s per-thread state.
+//!
+//! `librrd` keeps the last error in a context that is specific to the calling thread, so as long as
+//! an error is read on the same thread as the call that produced it, concurrent calls can't see
+//! each other's errors. The reentrant (`_r`) entry points use nothing else that is shared between
+//! threads, but others (e.g. `rrd_graph_v`, `rrd_xport`) use global state, and must be
+//! serialized with [`non_reentrant`].
+
+use crate::error::RrdResult;
+#[cfg(feature = "graph")]
+use std::sync::Mutex;
+use std::sync::Once;
+
+/// Prepare the calling thread's context for a call to the `librrd` function `entry_point`.
+///
+/// Any error left over from an earlier call on this thread is cleared, so it can't be attributed to
+/// the upcoming call.
+pub(crate) fn prepare(entry_point: &'static str) -> RrdResult<()> {
+    ensure_available(entry_point)?;
+
+    static THREAD_INIT: Once = Once::new();
+    THREAD_INIT.call_once(|| unsafe { rrd_sys::rrd_thread_init() });
+
+    unsafe { rrd_sys::rrd_clear_error() }
+    Ok(())
+}
+
+/// Run `call`, which uses the non-reentrant `librrd` function `entry_point`, while no other such
+/// call is running.
+///
+/// The call's error should be read inside `call` as well.
+#[cfg(feature = "graph")]
+pub(crate) fn non_reentrant<T>(
+    entry_point: &'static str,
+    call: impl FnOnce() -> RrdResult<T>,
+) -> RrdResult<T> {
+    static LOCK: Mutex<()> = Mutex::new(());
+    // A panic while holding the lock doesn't leave any Rust state inconsistent
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    prepare(entry_point)?;
+    call()
+}
+
+/// Returns [`RrdError::LibraryUnavailable`] if `librrd` can't be loaded, or lacks `entry_point` or
+/// the functions used to manage the context and free results.
+///
+/// Always succeeds unless the `dynamic-loading` feature is enabled, since `librrd` is linked.
+///
+/// [`RrdError::LibraryUnavailable`]: crate::error::RrdError::LibraryUnavailable
+#[cfg(feature = "dynamic-loading")]
+pub(crate) fn ensure_available(entry_point: &'static str) -> RrdResult<()> {
+    const SUPPORT: &[&str] = &[
+        "rrd_thread_init",
+        "rrd_get_context",
+        "rrd_clear_error",
+        "rrd_freemem",
+        "rrd_info_free",
+    ];
+    std::iter::once(entry_point)
+        .chain(SUPPORT.iter().copied())
+        .try_for_each(rrd_sys::check_symbol)
+        .map_err(|e| crate::error::RrdError::LibraryUnavailable(e.to_string()))
+}
+
+#[cfg(not(feature = "dynamic-loading"))]
+pub(crate) fn ensure_available(_entry_point: &'static str) -> RrdResult<()> {
+    Ok(())
+}
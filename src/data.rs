@@ -1,12 +1,58 @@
 //! Support for navigating fetched data sets.
 
-use crate::Timestamp;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "serde")]
+mod serialize;
+mod stats;
+
+#[cfg(feature = "serde")]
+pub use serialize::RowOriented;
+
+use crate::{
+    error::{RrdError, RrdResult},
+    ops::update::{BatchTime, Datum},
+    Timestamp,
+};
 use rrd_sys::rrd_double;
-use std::{fmt, ops::Deref, time::Duration};
+use std::{
+    fmt,
+    ops::{Bound, Deref, RangeBounds},
+    time::Duration,
+};
 
 /// Provides a safe abstraction for traversing the dataset produced by `fetch()`.
 ///
 /// Contains both the data and the metadata (e.g. start, end, step and data sources).
+///
+/// Unknown values are represented as `NaN`, as they are in `librrd`. Accessors like
+/// [`Self::value_at`] and [`Row::value`] map those to `None` instead.
+///
+/// # Examples
+///
+/// Data doesn't have to come from `fetch()`; owned data can be built from plain `Vec`s.
+///
+/// ```
+/// use rrd::{data::Data, Timestamp};
+/// use std::time::Duration;
+///
+/// let start = Timestamp::from_timestamp(1_000, 0).unwrap();
+/// let data = Data::from_columns(
+///     start,
+///     Duration::from_secs(10),
+///     vec![
+///         ("in".to_string(), vec![1.0, 2.0, f64::NAN]),
+///         ("out".to_string(), vec![4.0, 5.0, 6.0]),
+///     ],
+/// )
+/// .unwrap();
+///
+/// assert_eq!(3, data.row_count());
+/// assert_eq!(vec![1.0, 2.0], data.column("in").unwrap().iter().take(2).collect::<Vec<_>>());
+/// assert_eq!(Some(5.0), data.value_at(start + Duration::from_secs(10), "out"));
+/// // unknown
+/// assert_eq!(None, data.value_at(start + Duration::from_secs(20), "in"));
+/// ```
 pub struct Data<T> {
     start: Timestamp,
     end: Timestamp,
@@ -39,6 +85,32 @@ where
         }
     }
 
+    /// Like [`Self::new`], but rejects inconsistent input rather than panicking.
+    fn try_new(
+        start: Timestamp,
+        end: Timestamp,
+        step: Duration,
+        names: Vec<String>,
+        data: T,
+    ) -> RrdResult<Self> {
+        if names.is_empty() {
+            return Err(RrdError::InvalidArgument(
+                "Must have at least one data source".to_string(),
+            ));
+        }
+        if step.is_zero() {
+            return Err(RrdError::InvalidArgument(
+                "Step must be positive".to_string(),
+            ));
+        }
+        if !data.len().is_multiple_of(names.len()) {
+            return Err(RrdError::InvalidArgument(
+                "Number of values is not a multiple of the number of data sources".to_string(),
+            ));
+        }
+        Ok(Self::new(start, end, step, names, data))
+    }
+
     /// Timestamp for the first row of data.
     pub fn start(&self) -> Timestamp {
         self.start
@@ -66,10 +138,310 @@ where
         &self.names
     }
 
+    /// Returns the index of the data source named `name`, if there is one.
+    pub fn ds_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n == name)
+    }
+
+    /// All values in the dataset, row by row.
+    pub fn values(&self) -> &[rrd_double] {
+        &self.data
+    }
+
     /// The rows of data in the dataset.
     pub fn rows(&self) -> Rows<'_, T> {
         Rows { data: self }
     }
+
+    /// Returns the row at `index`, if there is one.
+    pub fn row(&self, index: usize) -> Option<Row<'_, T>> {
+        (index < self.row_count).then(|| Row::new(self, index))
+    }
+
+    /// The timestamps of each row.
+    pub fn timestamps(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Timestamp> + ExactSizeIterator + '_ {
+        (0..self.row_count).map(|i| self.row_timestamp(i))
+    }
+
+    /// The values for the data source named `name`, if there is one.
+    pub fn column(&self, name: &str) -> Option<Column<'_, T>> {
+        self.ds_index(name).map(|ds_index| Column {
+            data: self,
+            ds_index,
+        })
+    }
+
+    /// Returns the rows with timestamps in `range`.
+    ///
+    /// The result borrows the values from `self` rather than copying them. If no rows are in the
+    /// range, the result is empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use rrd::{data::Data, Timestamp};
+    /// use std::time::Duration;
+    ///
+    /// let start = Timestamp::from_timestamp(1_000, 0).unwrap();
+    /// let step = Duration::from_secs(10);
+    /// let data = Data::from_rows(start, step, vec!["ds".to_string()], vec![1.0, 2.0, 3.0, 4.0])
+    ///     .unwrap();
+    ///
+    /// let sliced = data.slice(start + step..start + step * 3);
+    /// assert_eq!(start + step, sliced.start());
+    /// assert_eq!(&[2.0, 3.0], sliced.values());
+    /// ```
+    pub fn slice(&self, range: impl RangeBounds<Timestamp>) -> Data<&[rrd_double]> {
+        let first = match range.start_bound() {
+            Bound::Included(ts) => self.partition_point(|t| t < *ts),
+            Bound::Excluded(ts) => self.partition_point(|t| t <= *ts),
+            Bound::Unbounded => 0,
+        };
+        let last = match range.end_bound() {
+            Bound::Included(ts) => self.partition_point(|t| t <= *ts),
+            Bound::Excluded(ts) => self.partition_point(|t| t < *ts),
+            Bound::Unbounded => self.row_count,
+        }
+        .max(first);
+
+        let start = self.row_timestamp(first);
+        let end = if last > first {
+            self.row_timestamp(last - 1)
+        } else {
+            start
+        };
+        let width = self.names.len();
+        Data::new(
+            start,
+            end,
+            self.step,
+            self.names.clone(),
+            &self.data[first * width..last * width],
+        )
+    }
+
+    /// Returns the value for data source `ds_name` at time `ts`, or `None` if `ts` is outside the
+    /// dataset, there is no such data source, or the value is unknown.
+    ///
+    /// As in `rrdfetch` output, the value in a row covers the `step` leading up to and including
+    /// the row's timestamp, so `ts` need not be exactly a row timestamp.
+    pub fn value_at(&self, ts: Timestamp, ds_name: &str) -> Option<f64> {
+        let ds_index = self.ds_index(ds_name)?;
+        let row_index = self.partition_point(|t| t < ts);
+        if row_index >= self.row_count || self.row_timestamp(row_index) - self.step >= ts {
+            return None;
+        }
+        known(self.data[row_index * self.names.len() + ds_index])
+    }
+
+    /// Returns each row as a batch suitable for [`crate::ops::update::update_all`], with unknown
+    /// values as [`Datum::Unspecified`].
+    pub fn update_batches(&self) -> impl ExactSizeIterator<Item = (BatchTime, Vec<Datum>)> + '_ {
+        self.rows().into_iter().map(|row| {
+            (
+                row.timestamp().into(),
+                row.iter_known()
+                    .map(|v| v.map_or(Datum::Unspecified, Datum::Float))
+                    .collect(),
+            )
+        })
+    }
+
+    /// Copies the values so that they no longer borrow from (or are owned by) `librrd`.
+    pub fn to_owned(&self) -> Data<Vec<rrd_double>> {
+        Data {
+            start: self.start,
+            end: self.end,
+            step: self.step,
+            names: self.names.clone(),
+            data: self.data.to_vec(),
+            row_count: self.row_count,
+        }
+    }
+
+    fn row_timestamp(&self, index: usize) -> Timestamp {
+        // The last row's timestamp is checked when building from user input, and `librrd`'s are
+        // in range
+        nth_row_timestamp(self.start, self.step, index).expect("Row timestamp out of range")
+    }
+
+    /// Binary search for the first row whose timestamp doesn't satisfy `pred`.
+    ///
+    /// `pred` must be true for a (possibly empty) prefix of rows, and false for the remainder.
+    fn partition_point(&self, pred: impl Fn(Timestamp) -> bool) -> usize {
+        let mut low = 0;
+        let mut high = self.row_count;
+        while low < high {
+            let mid = low + (high - low) / 2;
+            if pred(self.row_timestamp(mid)) {
+                low = mid + 1;
+            } else {
+                high = mid;
+            }
+        }
+        low
+    }
+}
+
+impl Data<Vec<rrd_double>> {
+    /// Build an owned dataset from `values` laid out row by row, with one value per name in
+    /// `names` in each row.
+    ///
+    /// Use `NaN` for unknown values.
+    ///
+    /// Returns an error if `names` is empty, `step` is zero, or the number of values is not a
+    /// multiple of the number of names.
+    pub fn from_rows(
+        start: Timestamp,
+        step: Duration,
+        names: Vec<String>,
+        values: Vec<rrd_double>,
+    ) -> RrdResult<Self> {
+        let end = match values.len().checked_div(names.len()) {
+            Some(rows) if rows > 1 => {
+                nth_row_timestamp(start, step, rows - 1).ok_or_else(|| {
+                    RrdError::InvalidArgument("Row timestamps are out of range".to_string())
+                })?
+            }
+            _ => start,
+        };
+        Self::try_new(start, end, step, names, values)
+    }
+
+    /// Build an owned dataset from `(name, values)` columns.
+    ///
+    /// Use `NaN` for unknown values.
+    ///
+    /// Returns an error if `columns` is empty, `step` is zero, or the columns are not all the
+    /// same length.
+    pub fn from_columns(
+        start: Timestamp,
+        step: Duration,
+        columns: Vec<(String, Vec<rrd_double>)>,
+    ) -> RrdResult<Self> {
+        let row_count = columns.first().map(|(_, c)| c.len()).unwrap_or_default();
+        if columns.iter().any(|(_, c)| c.len() != row_count) {
+            return Err(RrdError::InvalidArgument(
+                "Column lengths don't match".to_string(),
+            ));
+        }
+
+        let mut values = Vec::with_capacity(row_count * columns.len());
+        for row_index in 0..row_count {
+            values.extend(columns.iter().map(|(_, c)| c[row_index]));
+        }
+        let names = columns.into_iter().map(|(name, _)| name).collect();
+
+        Self::from_rows(start, step, names, values)
+    }
+}
+
+/// The timestamp of row `index` of data starting at `start`, or `None` if it's out of range.
+pub(crate) fn nth_row_timestamp(
+    start: Timestamp,
+    step: Duration,
+    index: usize,
+) -> Option<Timestamp> {
+    let offset = step.checked_mul(u32::try_from(index).ok()?)?;
+    start.checked_add_signed(chrono::TimeDelta::from_std(offset).ok()?)
+}
+
+/// The values for a single data source in [`Data`].
+///
+/// See [`Data::column`].
+pub struct Column<'data, T> {
+    data: &'data Data<T>,
+    ds_index: usize,
+}
+
+impl<'data, T> Column<'data, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    /// The data source name for this column.
+    pub fn name(&self) -> &'data str {
+        &self.data.names[self.ds_index]
+    }
+
+    /// The number of values.
+    pub fn len(&self) -> usize {
+        self.data.row_count()
+    }
+
+    /// True _iff_ there are 0 values.
+    pub fn is_empty(&self) -> bool {
+        self.data.row_count() == 0
+    }
+
+    /// Iterate over the values, with unknown values as `NaN`.
+    pub fn iter(&self) -> ColumnIter<'data> {
+        ColumnIter {
+            values: self.data.values()[self.ds_index..]
+                .iter()
+                .step_by(self.data.names.len()),
+        }
+    }
+
+    /// Iterate over the values, with unknown values as `None`.
+    pub fn iter_known(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = Option<f64>> + ExactSizeIterator + 'data {
+        self.iter().map(known)
+    }
+
+    /// Iterate over `(timestamp, value)` pairs, with unknown values as `NaN`.
+    pub fn iter_with_timestamps(
+        &self,
+    ) -> impl DoubleEndedIterator<Item = (Timestamp, f64)> + ExactSizeIterator + 'data {
+        self.data.timestamps().zip(self.iter())
+    }
+}
+
+impl<'data, T> IntoIterator for Column<'data, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    type Item = f64;
+
+    type IntoIter = ColumnIter<'data>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterate over the values in a [`Column`].
+///
+/// See [`Column::iter`].
+pub struct ColumnIter<'data> {
+    values: std::iter::StepBy<std::slice::Iter<'data, rrd_double>>,
+}
+
+impl Iterator for ColumnIter<'_> {
+    type Item = f64;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.values.next().copied()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for ColumnIter<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.values.next_back().copied()
+    }
+}
+
+impl ExactSizeIterator for ColumnIter<'_> {}
+
+/// Map `NaN`, which `librrd` uses for unknown values, to `None`.
+fn known(value: rrd_double) -> Option<f64> {
+    (!value.is_nan()).then_some(value)
 }
 
 /// An iterator over the [`Row`]s in [`Data`].
@@ -179,8 +551,7 @@ where
         Self {
             data,
             data_offset: data.names.len() * row_index,
-            timestamp: data.start()
-                + data.step() * row_index.try_into().expect("Row index exceeds u32"),
+            timestamp: data.row_timestamp(row_index),
         }
     }
 
@@ -207,6 +578,19 @@ where
                 value: *value,
             })
     }
+
+    /// Returns the value for data source `ds_name`, or `None` if there is no such data source or
+    /// the value is unknown.
+    pub fn value(&self, ds_name: &str) -> Option<f64> {
+        self.data
+            .ds_index(ds_name)
+            .and_then(|i| known(self.as_slice()[i]))
+    }
+
+    /// Iterate over this row's values, with unknown values as `None`.
+    pub fn iter_known(&self) -> impl Iterator<Item = Option<f64>> + '_ {
+        self.as_slice().iter().copied().map(known)
+    }
 }
 
 impl<T> Deref for Row<'_, T>
@@ -255,3 +639,156 @@ pub struct Cell<'data> {
     /// A value in a [`Row`]
     pub value: f64,
 }
+
+impl Cell<'_> {
+    /// Returns the value, or `None` if it is unknown.
+    pub fn known_value(&self) -> Option<f64> {
+        known(self.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const STEP: Duration = Duration::from_secs(10);
+
+    fn ts(secs: i64) -> Timestamp {
+        Timestamp::from_timestamp(secs, 0).unwrap()
+    }
+
+    fn sample() -> Data<Vec<f64>> {
+        Data::from_columns(
+            ts(100),
+            STEP,
+            vec![
+                ("a".to_string(), vec![1.0, 2.0, f64::NAN, 4.0]),
+                ("b".to_string(), vec![5.0, 6.0, 7.0, 8.0]),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn from_columns_is_row_major() {
+        let data = sample();
+        assert_eq!(ts(100), data.start());
+        assert_eq!(ts(130), data.end());
+        assert_eq!(4, data.row_count());
+        assert_eq!(&[1.0, 5.0, 2.0, 6.0], &data.values()[..4], "first two rows");
+    }
+
+    #[test]
+    fn from_columns_rejects_mismatched_lengths() {
+        assert!(Data::from_columns(
+            ts(100),
+            STEP,
+            vec![("a".to_string(), vec![1.0]), ("b".to_string(), vec![])]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn from_rows_rejects_invalid() {
+        assert!(Data::from_rows(ts(100), STEP, vec![], vec![]).is_err());
+        assert!(Data::from_rows(ts(100), Duration::ZERO, vec!["a".into()], vec![1.0]).is_err());
+        assert!(Data::from_rows(ts(100), STEP, vec!["a".into(), "b".into()], vec![1.0]).is_err());
+        assert!(Data::from_rows(
+            ts(100),
+            Duration::from_secs(u64::MAX),
+            vec!["a".into()],
+            vec![1.0, 2.0]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn column() {
+        let data = sample();
+        let column = data.column("b").unwrap();
+        assert_eq!("b", column.name());
+        assert_eq!(4, column.len());
+        assert_eq!(vec![5.0, 6.0, 7.0, 8.0], column.iter().collect_vec());
+        assert_eq!(
+            vec![Some(1.0), Some(2.0), None, Some(4.0)],
+            data.column("a").unwrap().iter_known().collect_vec()
+        );
+        assert_eq!(
+            vec![(ts(100), 5.0), (ts(110), 6.0)],
+            column.iter_with_timestamps().take(2).collect_vec()
+        );
+        assert!(data.column("c").is_none());
+    }
+
+    #[test]
+    fn slice_bounds() {
+        let data = sample();
+
+        let sliced = data.slice(ts(110)..ts(130));
+        assert_eq!(ts(110), sliced.start());
+        assert_eq!(ts(120), sliced.end());
+        assert_eq!(2, sliced.row_count());
+
+        let sliced = data.slice(ts(105)..=ts(130));
+        assert_eq!(
+            vec![ts(110), ts(120), ts(130)],
+            sliced.timestamps().collect_vec()
+        );
+
+        let sliced = data.slice(..);
+        assert_eq!(data.values().as_ptr_range(), sliced.values().as_ptr_range());
+
+        let sliced = data.slice(ts(200)..);
+        assert_eq!(0, sliced.row_count());
+        assert!(sliced.rows().is_empty());
+
+        // backwards
+        let sliced = data.slice(ts(130)..ts(100));
+        assert_eq!(0, sliced.row_count());
+    }
+
+    #[test]
+    fn value_at_covers_preceding_step() {
+        let data = sample();
+        assert_eq!(Some(5.0), data.value_at(ts(100), "b"));
+        assert_eq!(Some(6.0), data.value_at(ts(101), "b"));
+        assert_eq!(Some(6.0), data.value_at(ts(110), "b"));
+        assert_eq!(Some(5.0), data.value_at(ts(91), "b"));
+        assert_eq!(None, data.value_at(ts(90), "b"));
+        assert_eq!(None, data.value_at(ts(131), "b"));
+        assert_eq!(None, data.value_at(ts(120), "a"));
+        assert_eq!(None, data.value_at(ts(120), "c"));
+    }
+
+    #[test]
+    fn row_value() {
+        let data = sample();
+        let row = data.row(2).unwrap();
+        assert_eq!(None, row.value("a"));
+        assert_eq!(Some(7.0), row.value("b"));
+        assert_eq!(vec![None, Some(7.0)], row.iter_known().collect_vec());
+        assert!(data.row(4).is_none());
+    }
+
+    #[test]
+    fn update_batches() {
+        let data = sample();
+        let batches = data.update_batches().collect_vec();
+        assert_eq!(4, batches.len());
+        assert!(matches!(batches[2].0, BatchTime::Timestamp(t) if t == ts(120)));
+        assert_eq!(vec![Datum::Unspecified, Datum::Float(7.0)], batches[2].1);
+    }
+
+    #[test]
+    fn to_owned_detaches() {
+        let data = sample();
+        let owned = data.slice(ts(110)..).to_owned();
+        assert_eq!(ts(110), owned.start());
+        assert_eq!(data.ds_names(), owned.ds_names());
+        assert_eq!(
+            data.values()[2..].iter().map(|v| v.to_bits()).collect_vec(),
+            owned.values().iter().map(|v| v.to_bits()).collect_vec()
+        );
+    }
+}
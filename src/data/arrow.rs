@@ -0,0 +1,358 @@
+//! Conversion between [`Data`] and Apache Arrow [`RecordBatch`]es.
+//!
+//! A record batch has a non-null [`TIMESTAMP_COLUMN`] with second precision in UTC, followed by
+//! one nullable `Float64` column per data source. Unknown (`NaN`) values become nulls, and vice
+//! versa.
+//!
+//! # Examples
+//!
+//! ```
+//! use rrd::{data::{arrow, Data}, Timestamp};
+//! use std::time::Duration;
+//!
+//! let data = Data::from_columns(
+//!     Timestamp::from_timestamp(1_000, 0).unwrap(),
+//!     Duration::from_secs(60),
+//!     vec![("speed".to_string(), vec![1.0, f64::NAN, 3.0])],
+//! )
+//! .unwrap();
+//!
+//! let batch = arrow::to_record_batch(&data).unwrap();
+//! assert_eq!(2, batch.num_columns());
+//! assert_eq!(1, batch.column(1).null_count());
+//!
+//! let round_trip = arrow::from_record_batch(&batch, None).unwrap();
+//! assert_eq!(data.step(), round_trip.step());
+//! assert_eq!(None, round_trip.value_at(data.start() + Duration::from_secs(60), "speed"));
+//! ```
+
+use crate::{
+    data::Data,
+    error::{RrdError, RrdResult},
+    Timestamp,
+};
+use arrow_array::{
+    cast::AsArray,
+    types::{
+        Float64Type, TimestampMicrosecondType, TimestampMillisecondType, TimestampNanosecondType,
+        TimestampSecondType,
+    },
+    Array as _, ArrayRef, Float64Array, RecordBatch, TimestampSecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use rrd_sys::rrd_double;
+use std::{ops::Deref, sync::Arc, time::Duration};
+
+/// Name of the timestamp column.
+pub const TIMESTAMP_COLUMN: &str = "timestamp";
+
+/// Convert `data` into a record batch.
+///
+/// Returns an error if a data source is named [`TIMESTAMP_COLUMN`].
+pub fn to_record_batch<T>(data: &Data<T>) -> RrdResult<RecordBatch>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    if data.ds_index(TIMESTAMP_COLUMN).is_some() {
+        return Err(RrdError::InvalidArgument(format!(
+            "Data source name {TIMESTAMP_COLUMN:?} clashes with the timestamp column"
+        )));
+    }
+
+    let mut fields = Vec::with_capacity(data.ds_names().len() + 1);
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(data.ds_names().len() + 1);
+
+    fields.push(Field::new(
+        TIMESTAMP_COLUMN,
+        DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+        false,
+    ));
+    columns.push(Arc::new(
+        TimestampSecondArray::from_iter_values(data.timestamps().map(|ts| ts.timestamp()))
+            .with_timezone("UTC"),
+    ));
+
+    for name in data.ds_names() {
+        let column = data.column(name).expect("Names come from the data");
+        fields.push(Field::new(name, DataType::Float64, true));
+        columns.push(Arc::new(Float64Array::from_iter(column.iter_known())));
+    }
+
+    RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+        .map_err(|e| RrdError::Internal(format!("Could not build record batch: {e}")))
+}
+
+/// Convert a record batch into owned [`Data`].
+///
+/// The batch must have a [`TIMESTAMP_COLUMN`] of any timestamp type with no nulls, and all other
+/// columns must be `Float64`. Nulls become `NaN`. Timestamps are truncated to whole seconds.
+///
+/// If `step` is `None`, it is inferred from the first two timestamps, so the batch must have at
+/// least two rows. Either way, the timestamps must be evenly spaced by `step`.
+pub fn from_record_batch(
+    batch: &RecordBatch,
+    step: Option<Duration>,
+) -> RrdResult<Data<Vec<rrd_double>>> {
+    let schema = batch.schema();
+    let (ts_index, _) = schema
+        .column_with_name(TIMESTAMP_COLUMN)
+        .ok_or_else(|| RrdError::InvalidArgument(format!("No {TIMESTAMP_COLUMN:?} column")))?;
+    let timestamps = timestamp_seconds(batch.column(ts_index))?;
+
+    let step = match step {
+        Some(s) => s,
+        None => match timestamps.as_slice() {
+            [first, second, ..] => match second.checked_sub(*first) {
+                Some(diff) if diff > 0 => Duration::from_secs(diff as u64),
+                Some(_) => {
+                    return Err(RrdError::InvalidArgument(
+                        "Timestamps must be increasing".to_string(),
+                    ))
+                }
+                None => {
+                    return Err(RrdError::InvalidArgument(
+                        "Timestamps out of range".to_string(),
+                    ))
+                }
+            },
+            _ => {
+                return Err(RrdError::InvalidArgument(
+                    "Need at least two rows to infer step".to_string(),
+                ))
+            }
+        },
+    };
+    let step_secs = i64::try_from(step.as_secs())
+        .map_err(|_| RrdError::InvalidArgument("Step too large".to_string()))?;
+    if timestamps
+        .iter()
+        .zip(timestamps.iter().skip(1))
+        // Differences that overflow can't be `step_secs`
+        .any(|(a, b)| b.checked_sub(*a) != Some(step_secs))
+    {
+        return Err(RrdError::InvalidArgument(
+            "Timestamps must be evenly spaced by step".to_string(),
+        ));
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .enumerate()
+        .filter(|(i, _)| *i != ts_index)
+        .map(|(i, field)| {
+            let values = batch
+                .column(i)
+                .as_primitive_opt::<Float64Type>()
+                .ok_or_else(|| {
+                    RrdError::InvalidArgument(format!(
+                        "Column {:?} must be Float64, not {}",
+                        field.name(),
+                        field.data_type()
+                    ))
+                })?
+                .iter()
+                .map(|v| v.unwrap_or(f64::NAN))
+                .collect();
+            Ok((field.name().clone(), values))
+        })
+        .collect::<RrdResult<Vec<_>>>()?;
+
+    let start = match timestamps.first() {
+        Some(secs) => Timestamp::from_timestamp(*secs, 0)
+            .ok_or_else(|| RrdError::InvalidArgument("Timestamp out of range".to_string()))?,
+        None => Timestamp::UNIX_EPOCH,
+    };
+
+    Data::from_columns(start, step, columns)
+}
+
+/// Returns the values of a timestamp column as seconds since epoch.
+fn timestamp_seconds(column: &ArrayRef) -> RrdResult<Vec<i64>> {
+    if column.null_count() > 0 {
+        return Err(RrdError::InvalidArgument(format!(
+            "{TIMESTAMP_COLUMN:?} column must not have nulls"
+        )));
+    }
+
+    let seconds = match column.data_type() {
+        DataType::Timestamp(TimeUnit::Second, _) => column
+            .as_primitive::<TimestampSecondType>()
+            .values()
+            .to_vec(),
+        DataType::Timestamp(TimeUnit::Millisecond, _) => column
+            .as_primitive::<TimestampMillisecondType>()
+            .values()
+            .iter()
+            .map(|v| v.div_euclid(1_000))
+            .collect(),
+        DataType::Timestamp(TimeUnit::Microsecond, _) => column
+            .as_primitive::<TimestampMicrosecondType>()
+            .values()
+            .iter()
+            .map(|v| v.div_euclid(1_000_000))
+            .collect(),
+        DataType::Timestamp(TimeUnit::Nanosecond, _) => column
+            .as_primitive::<TimestampNanosecondType>()
+            .values()
+            .iter()
+            .map(|v| v.div_euclid(1_000_000_000))
+            .collect(),
+        other => {
+            return Err(RrdError::InvalidArgument(format!(
+                "{TIMESTAMP_COLUMN:?} column must be a timestamp, not {other}"
+            )))
+        }
+    };
+    Ok(seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::TimestampMillisecondArray;
+    use itertools::Itertools;
+
+    fn sample() -> Data<Vec<f64>> {
+        Data::from_columns(
+            Timestamp::from_timestamp(1_000, 0).unwrap(),
+            Duration::from_secs(10),
+            vec![
+                ("a".to_string(), vec![1.0, f64::NAN, 3.0]),
+                ("b".to_string(), vec![4.0, 5.0, 6.0]),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn schema() {
+        let batch = to_record_batch(&sample()).unwrap();
+        let schema = batch.schema();
+        assert_eq!(
+            vec![TIMESTAMP_COLUMN, "a", "b"],
+            schema.fields().iter().map(|f| f.name()).collect_vec()
+        );
+        assert_eq!(
+            &DataType::Timestamp(TimeUnit::Second, Some("UTC".into())),
+            schema.field(0).data_type()
+        );
+        assert_eq!(
+            vec![1_000, 1_010, 1_020],
+            batch
+                .column(0)
+                .as_primitive::<TimestampSecondType>()
+                .values()
+                .to_vec()
+        );
+        assert_eq!(
+            vec![Some(1.0), None, Some(3.0)],
+            batch
+                .column(1)
+                .as_primitive::<Float64Type>()
+                .iter()
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = sample();
+        let converted = from_record_batch(&to_record_batch(&data).unwrap(), None).unwrap();
+        assert_eq!(data.start(), converted.start());
+        assert_eq!(data.end(), converted.end());
+        assert_eq!(data.step(), converted.step());
+        assert_eq!(data.ds_names(), converted.ds_names());
+        assert_eq!(
+            data.values().iter().map(|v| v.to_bits()).collect_vec(),
+            converted.values().iter().map(|v| v.to_bits()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn millisecond_timestamps() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                TIMESTAMP_COLUMN,
+                Arc::new(TimestampMillisecondArray::from(vec![60_000, 120_000])) as ArrayRef,
+            ),
+            (
+                "a",
+                Arc::new(Float64Array::from(vec![1.0, 2.0])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        let data = from_record_batch(&batch, None).unwrap();
+        assert_eq!(Timestamp::from_timestamp(60, 0).unwrap(), data.start());
+        assert_eq!(Duration::from_secs(60), data.step());
+    }
+
+    #[test]
+    fn single_row_needs_step() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                TIMESTAMP_COLUMN,
+                Arc::new(TimestampSecondArray::from(vec![60])) as ArrayRef,
+            ),
+            ("a", Arc::new(Float64Array::from(vec![1.0])) as ArrayRef),
+        ])
+        .unwrap();
+        assert!(from_record_batch(&batch, None).is_err());
+        let data = from_record_batch(&batch, Some(Duration::from_secs(5))).unwrap();
+        assert_eq!(1, data.row_count());
+    }
+
+    #[test]
+    fn rejects_irregular_timestamps() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                TIMESTAMP_COLUMN,
+                Arc::new(TimestampSecondArray::from(vec![0, 10, 30])) as ArrayRef,
+            ),
+            (
+                "a",
+                Arc::new(Float64Array::from(vec![1.0, 2.0, 3.0])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        assert!(from_record_batch(&batch, None).is_err());
+    }
+
+    #[test]
+    fn rejects_extreme_timestamps() {
+        for (timestamps, step) in [
+            (vec![i64::MIN, i64::MAX], None),
+            (vec![i64::MIN, 0, i64::MAX], Some(Duration::from_secs(10))),
+        ] {
+            let values = vec![1.0; timestamps.len()];
+            let batch = RecordBatch::try_from_iter([
+                (
+                    TIMESTAMP_COLUMN,
+                    Arc::new(TimestampSecondArray::from(timestamps)) as ArrayRef,
+                ),
+                ("a", Arc::new(Float64Array::from(values)) as ArrayRef),
+            ])
+            .unwrap();
+            assert!(matches!(
+                from_record_batch(&batch, step),
+                Err(RrdError::InvalidArgument(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_non_float_columns() {
+        let batch = RecordBatch::try_from_iter([
+            (
+                TIMESTAMP_COLUMN,
+                Arc::new(TimestampSecondArray::from(vec![0, 10])) as ArrayRef,
+            ),
+            (
+                "a",
+                Arc::new(arrow_array::Int64Array::from(vec![1, 2])) as ArrayRef,
+            ),
+        ])
+        .unwrap();
+        assert!(from_record_batch(&batch, None).is_err());
+    }
+}
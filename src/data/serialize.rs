@@ -0,0 +1,376 @@
+//! `serde` support for [`Data`].
+//!
+//! By default, data is serialized column by column:
+//!
+//! ```json
+//! {
+//!   "start": "2025-01-01T00:00:00Z",
+//!   "end": "2025-01-01T00:10:00Z",
+//!   "step": 300,
+//!   "ds_names": ["in", "out"],
+//!   "columns": [[1.0, 2.0, null], [3.0, 4.0, 5.0]]
+//! }
+//! ```
+//!
+//! [`Data::as_row_oriented`] instead serializes row by row, with a timestamp for each row:
+//!
+//! ```json
+//! {
+//!   "start": "2025-01-01T00:00:00Z",
+//!   "end": "2025-01-01T00:10:00Z",
+//!   "step": 300,
+//!   "ds_names": ["in", "out"],
+//!   "rows": [
+//!     { "timestamp": "2025-01-01T00:00:00Z", "values": [1.0, 3.0] },
+//!     { "timestamp": "2025-01-01T00:05:00Z", "values": [2.0, 4.0] },
+//!     { "timestamp": "2025-01-01T00:10:00Z", "values": [null, 5.0] }
+//!   ]
+//! }
+//! ```
+//!
+//! `step` is in seconds, and unknown values are `null`. Either form can be deserialized into a
+//! `Data<Vec<f64>>`.
+
+use crate::{
+    data::{self, Column, Data, Row},
+    Timestamp,
+};
+use rrd_sys::rrd_double;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{ops::Deref, time::Duration};
+
+impl<T> Data<T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    /// Returns a wrapper that serializes the data row by row rather than column by column.
+    ///
+    /// Requires the `serde` feature.
+    pub fn as_row_oriented(&self) -> RowOriented<'_, T> {
+        RowOriented { data: self }
+    }
+}
+
+/// Serializes [`Data`] column by column.
+impl<T> Serialize for Data<T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedData {
+            start: self.start,
+            end: self.end,
+            step: self.step.as_secs(),
+            ds_names: &self.names,
+            columns: Some(Columns { data: self }),
+            rows: None,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Serializes [`Data`] row by row.
+///
+/// See [`Data::as_row_oriented`].
+pub struct RowOriented<'data, T> {
+    data: &'data Data<T>,
+}
+
+impl<T> Serialize for RowOriented<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SerializedData {
+            start: self.data.start,
+            end: self.data.end,
+            step: self.data.step.as_secs(),
+            ds_names: &self.data.names,
+            columns: None,
+            rows: Some(Rows { data: self.data }),
+        }
+        .serialize(serializer)
+    }
+}
+
+/// Deserializes either the column or row oriented form.
+impl<'de> Deserialize<'de> for Data<Vec<rrd_double>> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let serialized = DeserializedData::deserialize(deserializer)?;
+        let step = Duration::from_secs(serialized.step);
+
+        match (serialized.columns, serialized.rows) {
+            (Some(columns), None) => {
+                if columns.len() != serialized.ds_names.len() {
+                    return Err(de::Error::custom(
+                        "Number of columns doesn't match number of DS names",
+                    ));
+                }
+                Data::from_columns(
+                    serialized.start,
+                    step,
+                    serialized
+                        .ds_names
+                        .into_iter()
+                        .zip(columns)
+                        .map(|(name, values)| {
+                            (name, values.into_iter().map(unknown_to_nan).collect())
+                        })
+                        .collect(),
+                )
+            }
+            (None, Some(rows)) => {
+                let mut values = Vec::with_capacity(rows.len() * serialized.ds_names.len());
+                for (index, row) in rows.into_iter().enumerate() {
+                    let expected_timestamp = data::nth_row_timestamp(serialized.start, step, index)
+                        .ok_or_else(|| de::Error::custom("Row timestamps are out of range"))?;
+                    if row.timestamp != expected_timestamp {
+                        return Err(de::Error::custom(format!(
+                            "Expected row timestamp {expected_timestamp}, got {}",
+                            row.timestamp
+                        )));
+                    }
+                    if row.values.len() != serialized.ds_names.len() {
+                        return Err(de::Error::custom(
+                            "Number of row values doesn't match number of DS names",
+                        ));
+                    }
+                    values.extend(row.values.into_iter().map(unknown_to_nan));
+                }
+                Data::from_rows(serialized.start, step, serialized.ds_names, values)
+            }
+            _ => {
+                return Err(de::Error::custom(
+                    "Exactly one of `columns` or `rows` must be present",
+                ))
+            }
+        }
+        .map_err(de::Error::custom)
+    }
+}
+
+#[derive(Serialize)]
+#[serde(bound(serialize = "T: Deref<Target = [rrd_double]>"))]
+struct SerializedData<'data, T> {
+    start: Timestamp,
+    end: Timestamp,
+    step: u64,
+    ds_names: &'data [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    columns: Option<Columns<'data, T>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    rows: Option<Rows<'data, T>>,
+}
+
+#[derive(Deserialize)]
+struct DeserializedData {
+    start: Timestamp,
+    step: u64,
+    ds_names: Vec<String>,
+    columns: Option<Vec<Vec<Option<f64>>>>,
+    rows: Option<Vec<DeserializedRow>>,
+}
+
+#[derive(Deserialize)]
+struct DeserializedRow {
+    timestamp: Timestamp,
+    values: Vec<Option<f64>>,
+}
+
+struct Columns<'data, T> {
+    data: &'data Data<T>,
+}
+
+impl<T> Serialize for Columns<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq((0..self.data.names.len()).map(|ds_index| ColumnValues {
+            column: Column {
+                data: self.data,
+                ds_index,
+            },
+        }))
+    }
+}
+
+struct ColumnValues<'data, T> {
+    column: Column<'data, T>,
+}
+
+impl<T> Serialize for ColumnValues<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.column.iter_known())
+    }
+}
+
+struct Rows<'data, T> {
+    data: &'data Data<T>,
+}
+
+impl<T> Serialize for Rows<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(
+            self.data
+                .rows()
+                .into_iter()
+                .map(|row| SerializedRow { row }),
+        )
+    }
+}
+
+struct SerializedRow<'data, T> {
+    row: Row<'data, T>,
+}
+
+impl<T> Serialize for SerializedRow<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut s = serializer.serialize_struct("Row", 2)?;
+        s.serialize_field("timestamp", &self.row.timestamp())?;
+        s.serialize_field("values", &RowValues { row: &self.row })?;
+        s.end()
+    }
+}
+
+struct RowValues<'row, 'data, T> {
+    row: &'row Row<'data, T>,
+}
+
+impl<T> Serialize for RowValues<'_, '_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.row.iter_known())
+    }
+}
+
+fn unknown_to_nan(value: Option<f64>) -> f64 {
+    value.unwrap_or(f64::NAN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    fn sample() -> Data<Vec<f64>> {
+        Data::from_columns(
+            Timestamp::from_timestamp(1_000, 0).unwrap(),
+            Duration::from_secs(10),
+            vec![
+                ("a".to_string(), vec![1.0, f64::NAN]),
+                ("b".to_string(), vec![3.0, 4.0]),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn assert_same(expected: &Data<Vec<f64>>, actual: &Data<Vec<f64>>) {
+        assert_eq!(expected.start(), actual.start());
+        assert_eq!(expected.end(), actual.end());
+        assert_eq!(expected.step(), actual.step());
+        assert_eq!(expected.ds_names(), actual.ds_names());
+        assert_eq!(
+            expected.values().iter().map(|v| v.to_bits()).collect_vec(),
+            actual.values().iter().map(|v| v.to_bits()).collect_vec()
+        );
+    }
+
+    #[test]
+    fn columns() {
+        let data = sample();
+        let json = serde_json::to_value(&data).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "start": "1970-01-01T00:16:40Z",
+                "end": "1970-01-01T00:16:50Z",
+                "step": 10,
+                "ds_names": ["a", "b"],
+                "columns": [[1.0, null], [3.0, 4.0]],
+            }),
+            json
+        );
+        assert_same(&data, &serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn rows() {
+        let data = sample();
+        let json = serde_json::to_value(data.as_row_oriented()).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "start": "1970-01-01T00:16:40Z",
+                "end": "1970-01-01T00:16:50Z",
+                "step": 10,
+                "ds_names": ["a", "b"],
+                "rows": [
+                    { "timestamp": "1970-01-01T00:16:40Z", "values": [1.0, 3.0] },
+                    { "timestamp": "1970-01-01T00:16:50Z", "values": [null, 4.0] },
+                ],
+            }),
+            json
+        );
+        assert_same(&data, &serde_json::from_value(json).unwrap());
+    }
+
+    #[test]
+    fn rejects_misplaced_row() {
+        let json = serde_json::json!({
+            "start": "1970-01-01T00:16:40Z",
+            "step": 10,
+            "ds_names": ["a"],
+            "rows": [
+                { "timestamp": "1970-01-01T00:16:40Z", "values": [1.0] },
+                { "timestamp": "1970-01-01T00:17:40Z", "values": [2.0] },
+            ],
+        });
+        assert!(serde_json::from_value::<Data<Vec<f64>>>(json).is_err());
+    }
+
+    #[test]
+    fn rejects_column_count_mismatch() {
+        let json = serde_json::json!({
+            "start": "1970-01-01T00:16:40Z",
+            "step": 10,
+            "ds_names": ["a", "b"],
+            "columns": [[1.0]],
+        });
+        assert!(serde_json::from_value::<Data<Vec<f64>>>(json).is_err());
+    }
+
+    #[test]
+    fn rejects_out_of_range_timestamps() {
+        for step in [u64::MAX, 1 << 43] {
+            let json = serde_json::json!({
+                "start": "2025-01-01T00:00:00Z",
+                "step": step,
+                "ds_names": ["a"],
+                "columns": [[1, 2]],
+            });
+            assert!(serde_json::from_value::<Data<Vec<f64>>>(json).is_err());
+            let json = serde_json::json!({
+                "start": "2025-01-01T00:00:00Z",
+                "step": step,
+                "ds_names": ["a"],
+                "rows": [
+                    { "timestamp": "2025-01-01T00:00:00Z", "values": [1] },
+                    { "timestamp": "2025-01-01T00:00:00Z", "values": [2] },
+                ],
+            });
+            assert!(serde_json::from_value::<Data<Vec<f64>>>(json).is_err());
+        }
+    }
+}
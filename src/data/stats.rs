@@ -0,0 +1,273 @@
+//! Statistics over a [`Column`], matching the `VDEF` functions in `rrdgraph`.
+//!
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_rpn.en.html>.
+
+use crate::{
+    data::{known, Column},
+    error::InvalidArgument,
+    Timestamp,
+};
+use rrd_sys::rrd_double;
+use std::{cmp::Ordering, ops::Deref};
+
+impl<T> Column<'_, T>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    /// The largest value and its timestamp, ignoring unknown values, like `MAXIMUM`.
+    ///
+    /// Returns `None` if all values are unknown.
+    pub fn maximum(&self) -> Option<(Timestamp, f64)> {
+        self.extreme(|candidate, current| candidate > current)
+    }
+
+    /// The smallest value and its timestamp, ignoring unknown values, like `MINIMUM`.
+    ///
+    /// Returns `None` if all values are unknown.
+    pub fn minimum(&self) -> Option<(Timestamp, f64)> {
+        self.extreme(|candidate, current| candidate < current)
+    }
+
+    /// The first known value and its timestamp, like `FIRST`.
+    pub fn first(&self) -> Option<(Timestamp, f64)> {
+        self.iter_with_timestamps().find(|(_, v)| !v.is_nan())
+    }
+
+    /// The last known value and its timestamp, like `LAST`.
+    pub fn last(&self) -> Option<(Timestamp, f64)> {
+        self.iter_with_timestamps().rev().find(|(_, v)| !v.is_nan())
+    }
+
+    /// The mean of the finite values, like `AVERAGE`.
+    ///
+    /// Returns `None` if there are no finite values.
+    pub fn average(&self) -> Option<f64> {
+        let (count, sum) = self.finite_count_and_sum();
+        (count > 0).then(|| sum / count as f64)
+    }
+
+    /// The sum of the finite values multiplied by the step in seconds, like `TOTAL`.
+    ///
+    /// For a rate (e.g. from a `COUNTER`), this is the total amount over the time range.
+    ///
+    /// Returns `None` if there are no finite values.
+    pub fn total(&self) -> Option<f64> {
+        let (count, sum) = self.finite_count_and_sum();
+        (count > 0).then(|| sum * self.data.step().as_secs_f64())
+    }
+
+    /// The population standard deviation of the finite values, like `STDEV`.
+    ///
+    /// Returns `None` if there are no finite values.
+    pub fn standard_deviation(&self) -> Option<f64> {
+        let average = self.average()?;
+        let (count, sum_of_squares) = self
+            .iter()
+            .filter(|v| v.is_finite())
+            .fold((0_usize, 0.0), |(count, sum), v| {
+                (count + 1, sum + (v - average).powi(2))
+            });
+        Some((sum_of_squares / count as f64).sqrt())
+    }
+
+    /// The value that `percentile` percent of the values are less than or equal to, like
+    /// `PERCENT`.
+    ///
+    /// Unknown values are sorted below all other values (including negative infinity), so a
+    /// `None` result means a large share of the values are unknown. See
+    /// [`Self::percentile_nan`] to ignore unknown values instead.
+    ///
+    /// `percentile` must be in `[0, 100]`.
+    pub fn percentile(&self, percentile: f64) -> Result<Option<f64>, InvalidArgument> {
+        percentile_of(self.iter().collect(), percentile)
+    }
+
+    /// Like [`Self::percentile`], but ignoring unknown values, like `PERCENTNAN`.
+    ///
+    /// Returns `Ok(None)` if all values are unknown.
+    pub fn percentile_nan(&self, percentile: f64) -> Result<Option<f64>, InvalidArgument> {
+        percentile_of(self.iter().filter(|v| !v.is_nan()).collect(), percentile)
+    }
+
+    /// The slope of the least squares line through the finite values, like `LSLSLOPE`.
+    ///
+    /// As in `rrdgraph`, x is the row index rather than the timestamp, so the slope is the change
+    /// per step.
+    pub fn slope(&self) -> Option<f64> {
+        self.least_squares().map(|lsl| lsl.slope)
+    }
+
+    /// The y intercept of the least squares line through the finite values, like `LSLINT`.
+    ///
+    /// This is the value of the line at the first row.
+    pub fn intercept(&self) -> Option<f64> {
+        self.least_squares().map(|lsl| lsl.intercept)
+    }
+
+    /// The correlation coefficient of the least squares line through the finite values, like
+    /// `LSLCORREL`.
+    pub fn correlation(&self) -> Option<f64> {
+        self.least_squares().map(|lsl| lsl.correlation)
+    }
+
+    /// Like `rrdgraph`, the first known value is the starting point, but only finite values can
+    /// replace it.
+    fn extreme(&self, is_better: impl Fn(f64, f64) -> bool) -> Option<(Timestamp, f64)> {
+        let mut values = self.iter_with_timestamps().skip_while(|(_, v)| v.is_nan());
+        let first = values.next()?;
+        Some(values.fold(first, |current, candidate| {
+            if candidate.1.is_finite() && is_better(candidate.1, current.1) {
+                candidate
+            } else {
+                current
+            }
+        }))
+    }
+
+    fn finite_count_and_sum(&self) -> (usize, f64) {
+        self.iter()
+            .filter(|v| v.is_finite())
+            .fold((0, 0.0), |(count, sum), v| (count + 1, sum + v))
+    }
+
+    fn least_squares(&self) -> Option<LeastSquaresLine> {
+        let mut count = 0.0;
+        let (mut sum_x, mut sum_y, mut sum_xy, mut sum_xx, mut sum_yy) = (0.0, 0.0, 0.0, 0.0, 0.0);
+        for (x, y) in self.iter().enumerate().filter(|(_, y)| y.is_finite()) {
+            let x = x as f64;
+            count += 1.0;
+            sum_x += x;
+            sum_y += y;
+            sum_xy += x * y;
+            sum_xx += x * x;
+            sum_yy += y * y;
+        }
+        if count == 0.0 {
+            return None;
+        }
+
+        let slope = (sum_x * sum_y - count * sum_xy) / (sum_x * sum_x - count * sum_xx);
+        let intercept = (sum_y - slope * sum_x) / count;
+        let correlation = (sum_xy - (sum_x * sum_y) / count)
+            / ((sum_xx - (sum_x * sum_x) / count) * (sum_yy - (sum_y * sum_y) / count)).sqrt();
+
+        Some(LeastSquaresLine {
+            slope,
+            intercept,
+            correlation,
+        })
+    }
+}
+
+struct LeastSquaresLine {
+    slope: f64,
+    intercept: f64,
+    correlation: f64,
+}
+
+fn percentile_of(mut values: Vec<f64>, percentile: f64) -> Result<Option<f64>, InvalidArgument> {
+    if !(0.0..=100.0).contains(&percentile) {
+        return Err(InvalidArgument("percentile must be in [0, 100]"));
+    }
+    if values.is_empty() {
+        return Ok(None);
+    }
+
+    // NaN < -inf < finite < inf
+    values.sort_by(|a, b| match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        (false, true) => Ordering::Greater,
+        (false, false) => a.partial_cmp(b).expect("Not NaN"),
+    });
+    let index = (percentile * (values.len() - 1) as f64 / 100.0).round() as usize;
+    Ok(known(values[index]))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::Data;
+    use crate::Timestamp;
+    use std::time::Duration;
+
+    const NAN: f64 = f64::NAN;
+
+    fn ts(secs: i64) -> Timestamp {
+        Timestamp::from_timestamp(secs, 0).unwrap()
+    }
+
+    fn data(values: Vec<f64>) -> Data<Vec<f64>> {
+        Data::from_columns(ts(0), Duration::from_secs(10), vec![("ds".into(), values)]).unwrap()
+    }
+
+    fn assert_close(expected: f64, actual: Option<f64>) {
+        let actual = actual.unwrap();
+        assert!((expected - actual).abs() < 1e-9, "{expected} != {actual}");
+    }
+
+    #[test]
+    fn extremes() {
+        let data = data(vec![NAN, 3.0, 1.0, NAN, 5.0, 2.0, NAN]);
+        let column = data.column("ds").unwrap();
+        assert_eq!(Some((ts(40), 5.0)), column.maximum());
+        assert_eq!(Some((ts(20), 1.0)), column.minimum());
+        assert_eq!(Some((ts(10), 3.0)), column.first());
+        assert_eq!(Some((ts(50), 2.0)), column.last());
+    }
+
+    #[test]
+    fn all_unknown() {
+        let data = data(vec![NAN, NAN]);
+        let column = data.column("ds").unwrap();
+        assert_eq!(None, column.maximum());
+        assert_eq!(None, column.minimum());
+        assert_eq!(None, column.first());
+        assert_eq!(None, column.last());
+        assert_eq!(None, column.average());
+        assert_eq!(None, column.total());
+        assert_eq!(None, column.standard_deviation());
+        assert_eq!(None, column.slope());
+        assert_eq!(Ok(None), column.percentile(50.0));
+        assert_eq!(Ok(None), column.percentile_nan(50.0));
+    }
+
+    #[test]
+    fn average_total_stdev_ignore_unknown() {
+        let data = data(vec![2.0, NAN, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        let column = data.column("ds").unwrap();
+        assert_close(5.0, column.average());
+        assert_close(400.0, column.total());
+        assert_close(2.0, column.standard_deviation());
+    }
+
+    #[test]
+    fn percentile_sorts_unknown_lowest() {
+        let data = data(vec![NAN, NAN, NAN, 1.0, 2.0]);
+        let column = data.column("ds").unwrap();
+        assert_eq!(Ok(None), column.percentile(50.0));
+        assert_eq!(Ok(Some(2.0)), column.percentile(100.0));
+        assert_eq!(Ok(Some(1.0)), column.percentile_nan(0.0));
+        assert_eq!(Ok(Some(2.0)), column.percentile_nan(95.0));
+        assert!(column.percentile(101.0).is_err());
+    }
+
+    #[test]
+    fn percentile_rounds_index() {
+        let data = data((1..=10).map(f64::from).collect());
+        let column = data.column("ds").unwrap();
+        // index = round(0.95 * 9) = 9
+        assert_eq!(Ok(Some(10.0)), column.percentile(95.0));
+        // index = round(0.5 * 9) = 5 (4.5 rounds away from zero)
+        assert_eq!(Ok(Some(6.0)), column.percentile(50.0));
+    }
+
+    #[test]
+    fn least_squares_uses_row_index() {
+        // y = 2x + 1, with a gap
+        let data = data(vec![1.0, 3.0, NAN, 7.0, 9.0]);
+        let column = data.column("ds").unwrap();
+        assert_close(2.0, column.slope());
+        assert_close(1.0, column.intercept());
+        assert_close(1.0, column.correlation());
+    }
+}
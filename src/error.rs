@@ -1,11 +1,16 @@
 //! RRD-related errors.
 
-use std::ffi::{self, CStr, NulError};
+use std::{
+    ffi::{self, CStr, NulError},
+    fmt,
+    path::{Path, PathBuf},
+};
 
+use crate::ops::version::LibrrdVersion;
 use thiserror::Error;
 
 /// Top-level RRD error used for all `librrd` operations.
-#[derive(Error, Debug, PartialEq, Eq)]
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
 pub enum RrdError {
     /// A string contained `\0`, and thus could not be converted to a C string
     #[error(transparent)]
@@ -16,8 +21,8 @@ pub enum RrdError {
     PathEncodingError,
 
     /// An error from the underlying C librrd library
-    #[error("librrd: \"{0}\"")]
-    LibRrdError(String),
+    #[error(transparent)]
+    LibRrdError(LibRrdError),
 
     /// A miscellaneous error in this library
     #[error("Internal error: {0}")]
@@ -26,6 +31,47 @@ pub enum RrdError {
     /// An [`InvalidArgument`] error
     #[error("Invalid argument: {0}")]
     InvalidArgument(String),
+
+    /// The linked `librrd` is too old for a requested option
+    ///
+    /// See [`crate::ops::version::Capabilities`].
+    #[error("{feature} requires librrd {required} or newer, but {linked} is linked")]
+    Unsupported {
+        /// The requested option
+        feature: &'static str,
+        /// The oldest `librrd` version that supports `feature`
+        required: LibrrdVersion,
+        /// The linked `librrd` version
+        linked: LibrrdVersion,
+    },
+
+    /// `librrd` could not be loaded at runtime, or lacks a needed function
+    ///
+    /// Only returned with the `dynamic-loading` feature. See [`crate::ops::version::is_available`].
+    #[error("librrd is unavailable: {0}")]
+    LibraryUnavailable(String),
+
+    /// A graph definition was rejected by a `GraphPolicy`
+    #[error("Graph policy violation: {0}")]
+    PolicyViolation(String),
+}
+
+impl RrdError {
+    /// The kind of `librrd` error, if this is a [`RrdError::LibRrdError`].
+    ///
+    /// ```
+    /// use rrd::error::{LibRrdErrorKind, RrdError};
+    ///
+    /// fn should_retry(error: &RrdError) -> bool {
+    ///     error.librrd_kind() == Some(LibRrdErrorKind::LockFailed)
+    /// }
+    /// ```
+    pub fn librrd_kind(&self) -> Option<LibRrdErrorKind> {
+        match self {
+            RrdError::LibRrdError(e) => Some(e.kind()),
+            _ => None,
+        }
+    }
 }
 
 /// A `Result<T, RrdError>`, a combo used throughout this library
@@ -42,26 +88,300 @@ impl From<InvalidArgument> for RrdError {
     }
 }
 
+/// An error reported by `librrd`, with the operation and file it occurred in.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub struct LibRrdError {
+    kind: LibRrdErrorKind,
+    message: String,
+    operation: Operation,
+    path: Option<PathBuf>,
+}
+
+impl LibRrdError {
+    pub(crate) fn new(message: String, operation: Operation, path: Option<&Path>) -> Self {
+        Self {
+            kind: LibRrdErrorKind::classify(&message),
+            message,
+            operation,
+            path: path.map(Path::to_path_buf),
+        }
+    }
+
+    /// The kind of error, determined from the message.
+    pub fn kind(&self) -> LibRrdErrorKind {
+        self.kind
+    }
+
+    /// The message exactly as reported by `librrd`.
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// The operation that failed.
+    pub fn operation(&self) -> Operation {
+        self.operation
+    }
+
+    /// The RRD file the operation was using, if it uses a single file.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+impl fmt::Display for LibRrdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.operation)?;
+        if let Some(path) = &self.path {
+            write!(f, " {}", path.display())?;
+        }
+        write!(f, ": librrd: \"{}\"", self.message)
+    }
+}
+
+/// Families of `librrd` error messages.
+///
+/// `librrd` only reports errors as strings, so this is determined by matching known messages, and
+/// is [`Self::Other`] for anything unrecognized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum LibRrdErrorKind {
+    /// The RRD file (or a file it references) doesn't exist
+    FileNotFound,
+    /// The file couldn't be accessed due to permissions
+    PermissionDenied,
+    /// The file isn't an RRD, or was created on an incompatible architecture
+    InvalidFile,
+    /// An update used a time that isn't after the last update
+    IllegalUpdateTime,
+    /// The RRD is locked by another process
+    LockFailed,
+    /// A DS name doesn't exist in the RRD
+    UnknownDataSource,
+    /// No RRA matches the requested consolidation function
+    NoMatchingArchive,
+    /// An argument was rejected by `librrd`
+    InvalidArgument,
+    /// `librrd` couldn't allocate memory
+    OutOfMemory,
+    /// Any other error
+    Other,
+}
+
+impl LibRrdErrorKind {
+    fn classify(message: &str) -> Self {
+        let message = message.to_ascii_lowercase();
+        // Checked in order, so more specific messages come first. E.g. `opening '...': No such
+        // file or directory` is reported the same way for any file librrd opens.
+        const PATTERNS: &[(&[&str], LibRrdErrorKind)] = &[
+            (
+                &["illegal attempt to update using time"],
+                LibRrdErrorKind::IllegalUpdateTime,
+            ),
+            (&["could not lock"], LibRrdErrorKind::LockFailed),
+            (
+                &["no such file or directory"],
+                LibRrdErrorKind::FileNotFound,
+            ),
+            (&["permission denied"], LibRrdErrorKind::PermissionDenied),
+            (
+                &[
+                    "is not an rrd file",
+                    "created on another architecture",
+                    "is too small",
+                    "unexpected end of file",
+                    "short read",
+                ],
+                LibRrdErrorKind::InvalidFile,
+            ),
+            (
+                &["unknown ds name", "no ds called", "invalid ds name"],
+                LibRrdErrorKind::UnknownDataSource,
+            ),
+            (
+                &["does not contain an rra matching"],
+                LibRrdErrorKind::NoMatchingArchive,
+            ),
+            (
+                &[
+                    "out of memory",
+                    "cannot allocate memory",
+                    "malloc",
+                    "realloc",
+                    "allocating",
+                ],
+                LibRrdErrorKind::OutOfMemory,
+            ),
+            (
+                &[
+                    "usage:",
+                    "unknown option",
+                    "invalid option",
+                    "invalid argument",
+                    "requires an argument",
+                    "data source readings",
+                    "can't parse",
+                    "cannot parse",
+                    "conversion of",
+                ],
+                LibRrdErrorKind::InvalidArgument,
+            ),
+        ];
+
+        PATTERNS
+            .iter()
+            .find(|(needles, _)| needles.iter().any(|n| contains_phrase(&message, n)))
+            .map_or(LibRrdErrorKind::Other, |(_, kind)| *kind)
+    }
+}
+
+/// Whether `phrase` is in `message` as whole words, e.g. `expected` isn't in `unexpected`.
+fn contains_phrase(message: &str, phrase: &str) -> bool {
+    message.match_indices(phrase).any(|(i, _)| {
+        let before = message[..i].chars().next_back();
+        let after = message[i + phrase.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// The `librrd` operation that produced a [`LibRrdError`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[allow(missing_docs)]
+pub enum Operation {
+    Create,
+    Update,
+    Fetch,
+    Info,
+    Graph,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Operation::Create => "create",
+            Operation::Update => "update",
+            Operation::Fetch => "fetch",
+            Operation::Info => "info",
+            Operation::Graph => "graph",
+        })
+    }
+}
+
 /// Map `0` to `Ok`, anything else to `Err`
-pub(crate) fn return_code_to_result(rc: ffi::c_int) -> RrdResult<()> {
+pub(crate) fn return_code_to_result(
+    rc: ffi::c_int,
+    operation: Operation,
+    path: Option<&Path>,
+) -> RrdResult<()> {
     match rc {
         0 => Ok(()),
-        _ => Err(get_rrd_error().unwrap_or_else(|| {
-            RrdError::Internal("Unknown error - no librrd error info".to_string())
+        _ => Err(get_rrd_error(operation, path).unwrap_or_else(|| {
+            RrdError::Internal(format!("Unknown {operation} error - no librrd error info"))
         })),
     }
 }
 
-/// Returns `None` if `rrd_get_error()` return null, otherwise an `RrdError` with the error string..
-pub(crate) fn get_rrd_error() -> Option<RrdError> {
+/// Returns `None` if the calling thread's `librrd` context has no error, otherwise an `RrdError`
+/// with the error string, attributed to `operation` on `path`.
+///
+/// The error is cleared, so it won't be returned again.
+pub(crate) fn get_rrd_error(operation: Operation, path: Option<&Path>) -> Option<RrdError> {
     unsafe {
-        let p = rrd_sys::rrd_get_error();
-        if p.is_null() {
+        let context = rrd_sys::rrd_get_context();
+        if context.is_null() {
+            return None;
+        }
+        let error = CStr::from_ptr((*context).rrd_error.as_ptr());
+        if error.is_empty() {
             None
         } else {
-            let string = CStr::from_ptr(p).to_string_lossy().into_owned();
+            let string = error.to_string_lossy().into_owned();
             rrd_sys::rrd_clear_error();
-            Some(RrdError::LibRrdError(string))
+            Some(RrdError::LibRrdError(LibRrdError::new(
+                string, operation, path,
+            )))
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_known_messages() {
+        for (message, kind) in [
+            (
+                "opening '/tmp/missing.rrd': No such file or directory",
+                LibRrdErrorKind::FileNotFound,
+            ),
+            (
+                "opening '/root/x.rrd': Permission denied",
+                LibRrdErrorKind::PermissionDenied,
+            ),
+            (
+                "'/tmp/x.txt' is not an RRD file",
+                LibRrdErrorKind::InvalidFile,
+            ),
+            (
+                "/tmp/x.rrd: illegal attempt to update using time 920804400 when last update \
+                 time is 920804700 (minimum one second step)",
+                LibRrdErrorKind::IllegalUpdateTime,
+            ),
+            ("could not lock RRD", LibRrdErrorKind::LockFailed),
+            (
+                "No DS called 'speed' in '/tmp/x.rrd'",
+                LibRrdErrorKind::UnknownDataSource,
+            ),
+            (
+                "unknown DS name 'speed'",
+                LibRrdErrorKind::UnknownDataSource,
+            ),
+            (
+                "the RRD does not contain an RRA matching the chosen CF",
+                LibRrdErrorKind::NoMatchingArchive,
+            ),
+            (
+                "expected 2 data source readings (got 1) from N:1",
+                LibRrdErrorKind::InvalidArgument,
+            ),
+            (
+                "allocating data source buffer",
+                LibRrdErrorKind::OutOfMemory,
+            ),
+            (
+                "'/tmp/x.rrd' is too small (should be 1496 bytes)",
+                LibRrdErrorKind::InvalidFile,
+            ),
+            (
+                "reading '/tmp/x.rrd': unexpected end of file",
+                LibRrdErrorKind::InvalidFile,
+            ),
+            (
+                "Usage: rrdtool fetch filename CF [options]",
+                LibRrdErrorKind::InvalidArgument,
+            ),
+            ("something new", LibRrdErrorKind::Other),
+            // only whole words match
+            ("unexpected value", LibRrdErrorKind::Other),
+            ("dsmalloc_usage", LibRrdErrorKind::Other),
+        ] {
+            assert_eq!(kind, LibRrdErrorKind::classify(message), "{message}");
+        }
+    }
+
+    #[test]
+    fn display_includes_context() {
+        let error = RrdError::LibRrdError(LibRrdError::new(
+            "could not lock RRD".to_string(),
+            Operation::Update,
+            Some(Path::new("/tmp/x.rrd")),
+        ));
+        assert_eq!(
+            "update /tmp/x.rrd: librrd: \"could not lock RRD\"",
+            error.to_string()
+        );
+        assert_eq!(Some(LibRrdErrorKind::LockFailed), error.librrd_kind());
+        assert_eq!(None, RrdError::PathEncodingError.librrd_kind());
+    }
+}
@@ -13,13 +13,25 @@
 //! their input into `librrd` input, the [`log`](https://crates.io/crates/log) crate is used at
 //! `debug` level, so log output can be enabled with `RUST_LOG=rrd=debug` (if using `env_logger`)
 //! or other means of configuring `log`.
+//!
+//! # Thread safety
+//!
+//! All operations may be called concurrently from multiple threads. Errors are read from the
+//! calling thread's `librrd` context, so an error is always reported to the call that caused it.
+//! Operations backed by reentrant `librrd` functions (create, update, fetch, info) run fully in
+//! parallel, while the others (e.g. `ops::graph::graph`) are serialized behind an internal lock.
+//!
+//! Concurrent updates to the same file from different threads are not coordinated by `librrd`.
+//! Avoid them, or use `ops::tokio` (with the `async` feature), which serializes calls per file.
 
 #![deny(missing_docs)]
 
-// TODO get confirmation from upstream about librrd thread safety
+mod context;
 pub mod data;
 pub mod error;
 pub mod ops;
+#[cfg(feature = "server")]
+pub mod server;
 pub mod util;
 
 // `chrono::DateTime` and `chrono::Utc` are used for timestamps, so this is provided to allow
@@ -43,9 +55,10 @@ impl TimestampExt for Timestamp {
 
 /// How to aggregate primary data points in a RRA.
 ///
-/// See [`ops::create::Archive`] and [`ops::graph::elements::Def`].
+/// See [`ops::create::Archive`] and `ops::graph::elements::Def`.
 #[allow(missing_docs)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConsolidationFn {
     Avg,
     Min,
@@ -13,10 +13,21 @@
 //! [`rrdcreate`](https://oss.oetiker.ch/rrdtool/doc/rrdcreate.en.html), which would correspond with
 //! [`create::create`]. The Rust types that generate the C arg strings have been named to match
 //! those docs.
+//!
+//! To reproduce an op with the CLI, e.g. while debugging, the inputs can also be rendered as an
+//! `rrdtool` command line with the `*Command` types, such as [`fetch::FetchCommand`].
 
+pub mod batch;
 pub mod create;
+pub mod dump;
 pub mod fetch;
+#[cfg(feature = "graph")]
 pub mod graph;
 pub mod info;
+#[cfg(feature = "async")]
+pub mod tokio;
+pub mod tune;
 pub mod update;
 pub mod version;
+#[cfg(feature = "graph")]
+pub mod xport;
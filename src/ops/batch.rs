@@ -0,0 +1,355 @@
+//! Run many ops, e.g. on thousands of RRD files, on a bounded pool of threads.
+//!
+//! ```no_run
+//! use rrd::{ops::batch::{Executor, Job, JobOutput}, ConsolidationFn};
+//! use std::{path::PathBuf, time::Duration};
+//!
+//! let end = rrd::chrono::Utc::now();
+//! let jobs = (0..1000)
+//!     .map(|i| Job::Fetch {
+//!         filename: PathBuf::from(format!("host-{i}.rrd")),
+//!         cf: ConsolidationFn::Avg,
+//!         start: end - Duration::from_secs(3600),
+//!         end,
+//!         resolution: Duration::from_secs(300),
+//!         options: Default::default(),
+//!     })
+//!     .collect();
+//!
+//! let report = Executor::default().run(jobs);
+//! println!("{} failed in {:?}", report.failure_count(), report.elapsed);
+//! for result in report.results {
+//!     if let Ok(JobOutput::Fetch(data)) = result.output {
+//!         println!("{} rows", data.row_count());
+//!     }
+//! }
+//! ```
+//!
+//! Jobs for different files run in parallel, since the reentrant `librrd` functions used by
+//! fetch, update and info can safely run concurrently. Graph jobs are also spread across the
+//! pool, but the rendering itself is serialized internally (see the crate-level thread safety
+//! docs). Jobs for the same file run one at a time, in the order they were submitted, so e.g. a
+//! batch of updates to one file is applied in order. Files are compared by canonical path, so
+//! `a.rrd`, `./a.rrd` and symlinks to it count as the same file.
+
+#[cfg(feature = "graph")]
+use crate::ops::graph::{
+    self,
+    elements::GraphElement,
+    props::{GraphProps, ImageFormat},
+    GraphMetadata,
+};
+use crate::{
+    data::Data,
+    error::RrdResult,
+    ops::{
+        fetch::{self, Array, FetchOptions},
+        info::{self, InfoValue},
+        update::{self, BatchTime, Datum},
+    },
+    util, ConsolidationFn, Timestamp,
+};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::PathBuf,
+    sync::{mpsc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// An op to run as part of a batch.
+///
+/// See the corresponding functions in [`crate::ops`] for details.
+#[allow(missing_docs)]
+pub enum Job {
+    /// See [`fetch::fetch_with_options`].
+    Fetch {
+        filename: PathBuf,
+        cf: ConsolidationFn,
+        start: Timestamp,
+        end: Timestamp,
+        resolution: Duration,
+        options: FetchOptions,
+    },
+    /// See [`update::update_all`].
+    Update {
+        filename: PathBuf,
+        options: update::Options,
+        data: Vec<(BatchTime, Vec<Datum>)>,
+    },
+    /// See [`info::info`].
+    Info { filename: PathBuf },
+    /// See [`graph::graph`].
+    ///
+    /// `props` is boxed so that other jobs aren't as large as a graph job.
+    #[cfg(feature = "graph")]
+    Graph {
+        image_format: ImageFormat,
+        props: Box<GraphProps>,
+        elements: Vec<GraphElement>,
+    },
+}
+
+impl Job {
+    /// The file the job operates on, if it's limited to one file.
+    fn filename(&self) -> Option<&PathBuf> {
+        match self {
+            Job::Fetch { filename, .. } | Job::Update { filename, .. } | Job::Info { filename } => {
+                Some(filename)
+            }
+            #[cfg(feature = "graph")]
+            Job::Graph { .. } => None,
+        }
+    }
+
+    fn run(self) -> RrdResult<JobOutput> {
+        match self {
+            Job::Fetch {
+                filename,
+                cf,
+                start,
+                end,
+                resolution,
+                options,
+            } => fetch::fetch_with_options(&filename, cf, start, end, resolution, options)
+                .map(JobOutput::Fetch),
+            Job::Update {
+                filename,
+                options,
+                data,
+            } => update::update_all(&filename, options, &data).map(|_| JobOutput::Update),
+            Job::Info { filename } => info::info(&filename).map(JobOutput::Info),
+            #[cfg(feature = "graph")]
+            Job::Graph {
+                image_format,
+                props,
+                elements,
+            } => graph::graph(image_format, *props, &elements)
+                .map(|(image, metadata)| JobOutput::Graph(image, metadata)),
+        }
+    }
+}
+
+/// The output of a successful [`Job`], of the corresponding variant.
+pub enum JobOutput {
+    /// The fetched data
+    Fetch(Data<Array>),
+    /// The update succeeded
+    Update,
+    /// The info map
+    Info(HashMap<String, InfoValue>),
+    /// The image data and graph metadata
+    #[cfg(feature = "graph")]
+    Graph(Vec<u8>, GraphMetadata),
+}
+
+/// The outcome of one [`Job`].
+pub struct JobResult {
+    /// The job's output, or the error it failed with
+    pub output: RrdResult<JobOutput>,
+    /// How long the job took to run, excluding time spent waiting for a thread
+    pub elapsed: Duration,
+}
+
+/// The outcome of a batch of jobs.
+pub struct BatchReport {
+    /// One result per job, in the same order as the jobs
+    pub results: Vec<JobResult>,
+    /// Wall clock time for the whole batch
+    pub elapsed: Duration,
+}
+
+impl BatchReport {
+    /// The sum of the time taken by each job.
+    ///
+    /// Dividing this by [`Self::elapsed`] gives the average number of jobs running at once.
+    pub fn total_job_time(&self) -> Duration {
+        self.results.iter().map(|r| r.elapsed).sum()
+    }
+
+    /// The longest time taken by a single job, or zero if there were no jobs.
+    pub fn max_job_time(&self) -> Duration {
+        self.results
+            .iter()
+            .map(|r| r.elapsed)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// The number of jobs that failed.
+    pub fn failure_count(&self) -> usize {
+        self.results.iter().filter(|r| r.output.is_err()).count()
+    }
+}
+
+/// Runs batches of [`Job`]s on a bounded number of threads.
+///
+/// Threads are started for each batch and stopped when it finishes.
+#[derive(Debug, Clone)]
+pub struct Executor {
+    threads: NonZeroUsize,
+}
+
+impl Executor {
+    /// An executor that runs at most `threads` jobs at once.
+    pub fn new(threads: NonZeroUsize) -> Self {
+        Self { threads }
+    }
+
+    /// Run all of `jobs`, returning once they have all finished.
+    pub fn run(&self, jobs: Vec<Job>) -> BatchReport {
+        let start = Instant::now();
+        let keyed = jobs
+            .into_iter()
+            // So that e.g. `a.rrd` and `./a.rrd` are run one after the other
+            .map(|job| (job.filename().map(|f| util::file_key(f)), job))
+            .collect();
+        let results = run_grouped(keyed, self.threads, |job| {
+            let start = Instant::now();
+            let output = job.run();
+            JobResult {
+                output,
+                elapsed: start.elapsed(),
+            }
+        });
+
+        BatchReport {
+            results,
+            elapsed: start.elapsed(),
+        }
+    }
+}
+
+/// Uses as many threads as there are CPUs.
+impl Default for Executor {
+    fn default() -> Self {
+        Self::new(thread::available_parallelism().unwrap_or(NonZeroUsize::MIN))
+    }
+}
+
+/// Run `f` on every item, on up to `threads` threads, returning the results in the same order as
+/// the items.
+///
+/// Items with the same key run sequentially, in order, on one thread. Items with no key can run
+/// alongside anything.
+fn run_grouped<K, T, R, F>(items: Vec<(Option<K>, T)>, threads: NonZeroUsize, f: F) -> Vec<R>
+where
+    K: Eq + std::hash::Hash,
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    let count = items.len();
+
+    // Each group is a list of (index, item), in submission order
+    let mut groups: Vec<Vec<(usize, T)>> = Vec::new();
+    let mut group_by_key = HashMap::new();
+    for (index, (key, item)) in items.into_iter().enumerate() {
+        match key {
+            Some(key) => {
+                let group = *group_by_key.entry(key).or_insert_with(|| {
+                    groups.push(Vec::new());
+                    groups.len() - 1
+                });
+                groups[group].push((index, item));
+            }
+            None => groups.push(vec![(index, item)]),
+        }
+    }
+
+    let threads = threads.get().min(groups.len());
+    let queue = Mutex::new(groups.into_iter());
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender = sender.clone();
+            let (queue, f) = (&queue, &f);
+            scope.spawn(move || loop {
+                // Don't hold the lock while running the group
+                let group = queue.lock().unwrap_or_else(|e| e.into_inner()).next();
+                let Some(group) = group else {
+                    break;
+                };
+                for (index, item) in group {
+                    sender
+                        .send((index, f(item)))
+                        .expect("Receiver outlives workers");
+                }
+            });
+        }
+    });
+    drop(sender);
+
+    let mut results = (0..count).map(|_| None).collect::<Vec<_>>();
+    for (index, result) in receiver {
+        results[index] = Some(result);
+    }
+    results
+        .into_iter()
+        .map(|r| r.expect("Every item produces a result"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn threads(n: usize) -> NonZeroUsize {
+        NonZeroUsize::new(n).unwrap()
+    }
+
+    #[test]
+    fn results_in_submission_order() {
+        let items = (0..100_u64).map(|i| (None::<u64>, i)).collect::<Vec<_>>();
+        let results = run_grouped(items, threads(8), |i| {
+            // later items finish first
+            thread::sleep(Duration::from_micros(100 - i));
+            i * 2
+        });
+        assert_eq!((0..100).map(|i| i * 2).collect::<Vec<_>>(), results);
+    }
+
+    #[test]
+    fn same_key_runs_sequentially_in_order() {
+        let running = (0..4).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+        let next = (0..4).map(|_| AtomicUsize::new(0)).collect::<Vec<_>>();
+        let items = (0..200_usize)
+            .map(|i| (Some(i % 4), (i % 4, i / 4)))
+            .collect::<Vec<_>>();
+
+        run_grouped(items, threads(8), |(key, sequence)| {
+            assert_eq!(0, running[key].fetch_add(1, Ordering::SeqCst));
+            assert_eq!(sequence, next[key].fetch_add(1, Ordering::SeqCst));
+            thread::sleep(Duration::from_micros(50));
+            running[key].fetch_sub(1, Ordering::SeqCst);
+        });
+
+        assert!(next.iter().all(|n| n.load(Ordering::SeqCst) == 50));
+    }
+
+    #[test]
+    fn thread_count_is_bounded() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let items = (0..50).map(|i| (Some(i), ())).collect::<Vec<_>>();
+
+        run_grouped(items, threads(3), |_| {
+            let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+            max_running.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(1));
+            running.fetch_sub(1, Ordering::SeqCst);
+        });
+
+        assert!(max_running.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[test]
+    fn empty_batch() {
+        let report = Executor::default().run(vec![]);
+        assert!(report.results.is_empty());
+        assert_eq!(Duration::ZERO, report.total_job_time());
+        assert_eq!(0, report.failure_count());
+    }
+}
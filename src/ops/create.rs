@@ -2,12 +2,19 @@
 
 use crate::error::InvalidArgument;
 use crate::{
-    error::{return_code_to_result, RrdResult},
-    util::{path_to_str, ArrayOfStrings, NullTerminatedArrayOfStrings},
+    context,
+    error::{return_code_to_result, Operation, RrdError, RrdResult},
+    ops::version::{self, Capabilities},
+    util::{self, path_to_str, ArrayOfStrings, NullTerminatedArrayOfStrings},
     ConsolidationFn, Timestamp, TimestampExt,
 };
 use log::debug;
-use std::{ffi::CString, path::Path, ptr::null, time::Duration};
+use std::{
+    ffi::CString,
+    path::{Path, PathBuf},
+    ptr::null,
+    time::Duration,
+};
 
 /// Create a new RRD.
 ///
@@ -27,15 +34,26 @@ pub fn create<'a>(
         .iter()
         .map(|p| path_to_str(p).and_then(|s| CString::new(s).map_err(|e| e.into())))
         .collect::<Result<NullTerminatedArrayOfStrings, _>>()?;
-    let filename = CString::new(path_to_str(filename)?)?;
+    let c_filename = CString::new(path_to_str(filename)?)?;
     let template = match template {
         None => None,
         Some(p) => Some(CString::new(path_to_str(p)?)?),
     };
 
+    version::require("Create", Capabilities::CREATE_R2)?;
+    let data_sources = data_sources.into_iter().collect::<Vec<_>>();
+    if data_sources.iter().any(|ds| ds.needs_dcounter_dderive()) {
+        version::require(
+            "DCOUNTER and DDERIVE data sources",
+            Capabilities::DCOUNTER_DDERIVE,
+        )?;
+    }
+
     let args = data_sources
         .into_iter()
         .map(DataSource::as_arg_string)
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
         .chain(round_robin_archives.into_iter().map(Archive::as_arg_string))
         .map(CString::new)
         .collect::<Result<ArrayOfStrings, _>>()?;
@@ -46,45 +64,121 @@ pub fn create<'a>(
         step.as_secs()
     );
 
+    // windows c_ulong is u32
+    #[allow(clippy::useless_conversion)]
+    let step = step
+        .as_secs()
+        .try_into()
+        .map_err(|_| RrdError::InvalidArgument("Step too big for c_ulong".to_string()))?;
+    let argc = args
+        .len()
+        .try_into()
+        .map_err(|_| RrdError::InvalidArgument("Too many args to fit in rrd_int".to_string()))?;
+
+    context::prepare("rrd_create_r2")?;
     let rc = unsafe {
         rrd_sys::rrd_create_r2(
-            filename.as_ptr(),
-            #[allow(clippy::useless_conversion)]
-            // windows c_ulong is u32
-            step.as_secs().try_into().expect("step too big for c_ulong"),
+            c_filename.as_ptr(),
+            step,
             start.as_time_t(),
             no_overwrite.into(),
             sources.as_ptr(),
             template.map_or_else(null, |s| s.as_ptr()),
-            args.len()
-                .try_into()
-                .expect("Too many args to fit in rrd_int"),
+            argc,
             args.as_ptr(),
         )
     };
-    return_code_to_result(rc)
+    return_code_to_result(rc, Operation::Create, Some(filename))
+}
+
+/// The inputs to [`create`], for building the equivalent `rrdtool create` command line.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::{ops::create::{Archive, CreateCommand, DataSource, DataSourceName}, ConsolidationFn};
+/// use std::time::Duration;
+///
+/// let command = CreateCommand {
+///     filename: "my data.rrd".into(),
+///     start: rrd::Timestamp::from_timestamp(920804400, 0).unwrap(),
+///     step: Duration::from_secs(300),
+///     no_overwrite: true,
+///     template: None,
+///     sources: vec![],
+///     data_sources: vec![DataSource::gauge(DataSourceName::new("speed"), 600, None, None)],
+///     round_robin_archives: vec![Archive::new(ConsolidationFn::Avg, 0.5, 1, 24).unwrap()],
+/// };
+/// assert_eq!(
+///     "rrdtool create 'my data.rrd' --start 920804400 --step 300 --no-overwrite \
+///      DS:speed:GAUGE:600:U:U RRA:AVERAGE:0.5:1:24",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub struct CreateCommand {
+    pub filename: PathBuf,
+    pub start: Timestamp,
+    pub step: Duration,
+    pub no_overwrite: bool,
+    pub template: Option<PathBuf>,
+    pub sources: Vec<PathBuf>,
+    pub data_sources: Vec<DataSource>,
+    pub round_robin_archives: Vec<Archive>,
+}
+
+impl CreateCommand {
+    /// Returns the `rrdtool` arguments, starting with `create`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec![
+            "create".to_string(),
+            path_to_str(&self.filename)?.to_string(),
+            "--start".to_string(),
+            self.start.timestamp().to_string(),
+            "--step".to_string(),
+            self.step.as_secs().to_string(),
+        ];
+        if self.no_overwrite {
+            args.push("--no-overwrite".to_string());
+        }
+        if let Some(template) = &self.template {
+            args.push("--template".to_string());
+            args.push(path_to_str(template)?.to_string());
+        }
+        for source in &self.sources {
+            args.push("--source".to_string());
+            args.push(path_to_str(source)?.to_string());
+        }
+        for data_source in &self.data_sources {
+            args.push(data_source.as_arg_string()?);
+        }
+        args.extend(self.round_robin_archives.iter().map(Archive::as_arg_string));
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool create` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
 }
 
 /// Definition of a data source in an RRD.
 ///
 /// Corresponds to the `DS` arg to `rrdcreate`.
+///
+/// Names are checked when the RRD is created, and must be 1 to 19 ASCII letters, digits or `_`.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DataSource {
-    arg: String,
+    name: DataSourceName,
+    /// The fields after the name, starting with the type, e.g. `["GAUGE", "600", "U", "U"]`
+    fields: Vec<String>,
 }
 
 impl DataSource {
     /// Define a 'GAUGE' data source.
     pub fn gauge(name: DataSourceName, heartbeat: u32, min: Option<f64>, max: Option<f64>) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:GAUGE:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "GAUGE", heartbeat, min, max)
     }
 
     /// Define a 'COUNTER` data source.
@@ -94,16 +188,7 @@ impl DataSource {
         min: Option<u64>,
         max: Option<u64>,
     ) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:COUNTER:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "COUNTER", heartbeat, min, max)
     }
 
     /// Define a 'DCOUNTER` data source.
@@ -113,16 +198,7 @@ impl DataSource {
         min: Option<f64>,
         max: Option<f64>,
     ) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:DCOUNTER:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "DCOUNTER", heartbeat, min, max)
     }
 
     /// Define a 'DERIVE` data source.
@@ -132,16 +208,7 @@ impl DataSource {
         min: Option<u64>,
         max: Option<u64>,
     ) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:DERIVE:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "DERIVE", heartbeat, min, max)
     }
 
     /// Define a 'DDERIVE` data source.
@@ -151,16 +218,7 @@ impl DataSource {
         min: Option<f64>,
         max: Option<f64>,
     ) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:DDERIVE:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "DDERIVE", heartbeat, min, max)
     }
 
     /// Define an 'ABSOLUTE` data source.
@@ -170,55 +228,100 @@ impl DataSource {
         min: Option<u64>,
         max: Option<u64>,
     ) -> Self {
-        Self {
-            arg: format!(
-                "DS:{}:ABSOLUTE:{heartbeat}:{}:{}",
-                name.name,
-                min.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string()),
-                max.map(|m| m.to_string())
-                    .unwrap_or_else(|| "U".to_string())
-            ),
-        }
+        Self::with_limits(name, "ABSOLUTE", heartbeat, min, max)
     }
 
     /// Define a 'COMPUTE` data source.
     pub fn compute(name: DataSourceName, rpn: &str) -> Self {
         Self {
-            arg: format!("DS:{}:COMPUTE:{rpn}", name.name),
+            name,
+            fields: vec!["COMPUTE".to_string(), rpn.to_string()],
+        }
+    }
+
+    fn with_limits<T: ToString>(
+        name: DataSourceName,
+        ds_type: &str,
+        heartbeat: u32,
+        min: Option<T>,
+        max: Option<T>,
+    ) -> Self {
+        let limit = |l: Option<T>| l.map_or_else(|| "U".to_string(), |l| l.to_string());
+        Self {
+            name,
+            fields: vec![
+                ds_type.to_string(),
+                heartbeat.to_string(),
+                limit(min),
+                limit(max),
+            ],
         }
     }
 
     /// Returns the `DS:...` arg
-    fn as_arg_string(&self) -> String {
-        self.arg.clone()
+    fn as_arg_string(&self) -> Result<String, InvalidArgument> {
+        // A `:` (e.g. in a COMPUTE RPN expression) would be read as a field separator
+        if self.fields.iter().any(|f| f.contains(':')) {
+            return Err(InvalidArgument("Data source fields can't contain ':'"));
+        }
+        Ok(format!(
+            "DS:{}:{}",
+            self.name.as_arg_string()?,
+            self.fields.join(":")
+        ))
+    }
+
+    fn needs_dcounter_dderive(&self) -> bool {
+        matches!(
+            self.fields.first().map(String::as_str),
+            Some("DCOUNTER" | "DDERIVE")
+        )
     }
 }
 
 /// A plain data source name, or a mapping referencing a `source` DS.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DataSourceName {
-    /// The `name` string to use in a DS arg for `create`.
     name: String,
+    /// The source DS name and index, if mapped
+    source: Option<(String, Option<u32>)>,
 }
 
 impl DataSourceName {
     /// A data source name that does not reference a source RRD DS.
     pub fn new(name: impl Into<String>) -> Self {
-        Self { name: name.into() }
+        Self {
+            name: name.into(),
+            source: None,
+        }
     }
 
     /// A data source name that will be pre-filled from `src_ds_name`, optionally at source `index`.
     pub fn mapped(name: &str, src_ds_name: &str, index: Option<u32>) -> Self {
         Self {
-            name: match index {
-                None => format!("{name}={src_ds_name}"),
-                Some(i) => format!("{name}={src_ds_name}[{i}]"),
-            },
+            name: name.to_string(),
+            source: Some((src_ds_name.to_string(), index)),
         }
     }
+
+    /// The `name` string to use in a DS arg for `create`.
+    fn as_arg_string(&self) -> Result<String, InvalidArgument> {
+        util::check_ds_name(&self.name)?;
+        Ok(match &self.source {
+            None => self.name.clone(),
+            Some((src_ds_name, index)) => {
+                util::check_ds_name(src_ds_name)?;
+                match index {
+                    None => format!("{}={src_ds_name}", self.name),
+                    Some(i) => format!("{}={src_ds_name}[{i}]", self.name),
+                }
+            }
+        })
+    }
 }
 
 /// Definition of an RRA to include in a new RRD.
+#[derive(Debug, Clone, PartialEq)]
 pub struct Archive {
     consolidation_fn: ConsolidationFn,
     /// In `[0, 1]`
@@ -263,3 +366,54 @@ impl Archive {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn data_source_args() {
+        assert_eq!(
+            "DS:speed:GAUGE:600:U:1.5",
+            DataSource::gauge(DataSourceName::new("speed"), 600, None, Some(1.5))
+                .as_arg_string()
+                .unwrap()
+        );
+        assert_eq!(
+            "DS:speed=old_speed[2]:COUNTER:600:0:U",
+            DataSource::counter(
+                DataSourceName::mapped("speed", "old_speed", Some(2)),
+                600,
+                Some(0),
+                None
+            )
+            .as_arg_string()
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn data_source_rejects_injection() {
+        for name in ["", "speed:GAUGE", "a b", "twenty_chars_is_long", r"spe\:ed"] {
+            assert!(
+                DataSource::gauge(DataSourceName::new(name), 600, None, None)
+                    .as_arg_string()
+                    .is_err(),
+                "{name}"
+            );
+        }
+        assert!(DataSource::gauge(
+            DataSourceName::mapped("speed", "old:GAUGE", None),
+            600,
+            None,
+            None
+        )
+        .as_arg_string()
+        .is_err());
+        assert!(
+            DataSource::compute(DataSourceName::new("total"), "a,b,+:GAUGE")
+                .as_arg_string()
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,77 @@
+//! Dump the contents of an RRD as XML.
+//!
+//! So far this only builds `rrdtool dump` command lines; see [`DumpCommand`].
+//!
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrddump.en.html>.
+
+use crate::{
+    error::RrdResult,
+    util::{self, path_to_str},
+};
+use std::path::PathBuf;
+
+/// The inputs to `rrdtool dump`.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::dump::{DumpCommand, DumpHeader};
+///
+/// let command = DumpCommand {
+///     filename: "data.rrd".into(),
+///     output: Some("data.xml".into()),
+///     header: Some(DumpHeader::None),
+/// };
+/// assert_eq!(
+///     "rrdtool dump --header none data.rrd data.xml",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DumpCommand {
+    /// The RRD to dump
+    pub filename: PathBuf,
+    /// The file to write the XML to, or `None` for stdout
+    pub output: Option<PathBuf>,
+    /// The XML header to write, if not the default ([`DumpHeader::Dtd`])
+    pub header: Option<DumpHeader>,
+}
+
+impl DumpCommand {
+    /// Returns the `rrdtool` arguments, starting with `dump`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec!["dump".to_string()];
+        if let Some(header) = self.header {
+            args.push("--header".to_string());
+            args.push(
+                match header {
+                    DumpHeader::None => "none",
+                    DumpHeader::Xsd => "xsd",
+                    DumpHeader::Dtd => "dtd",
+                }
+                .to_string(),
+            );
+        }
+        args.push(path_to_str(&self.filename)?.to_string());
+        if let Some(output) = &self.output {
+            args.push(path_to_str(output)?.to_string());
+        }
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool dump` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
+}
+
+/// The header at the start of the XML.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpHeader {
+    /// No header
+    None,
+    /// Reference the XML schema
+    Xsd,
+    /// Reference the DTD
+    Dtd,
+}
@@ -1,17 +1,18 @@
 //! Fetch data from an RRD.
 
 use crate::{
+    context,
     data::Data,
-    error::{return_code_to_result, RrdResult},
-    util::path_to_str,
+    error::{return_code_to_result, Operation, RrdError, RrdResult},
+    util::{self, path_to_str},
     ConsolidationFn, Timestamp, TimestampExt,
 };
-use rrd_sys::{rrd_double, rrd_void};
+use rrd_sys::{rrd_char, rrd_double, rrd_ulong, rrd_void};
 use std::{
     ffi::{CStr, CString},
     fmt,
     ops::Deref,
-    path::Path,
+    path::{Path, PathBuf},
     ptr::null_mut,
     slice,
     time::Duration,
@@ -26,29 +27,125 @@ pub fn fetch(
     start: Timestamp,
     end: Timestamp,
     resolution: Duration,
+) -> RrdResult<Data<Array>> {
+    fetch_with_options(
+        filename,
+        cf,
+        start,
+        end,
+        resolution,
+        FetchOptions::default(),
+    )
+}
+
+/// Options to alter fetch behavior.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct FetchOptions {
+    /// Adjust `start` down to a multiple of the resolution, and `end` by the same amount.
+    ///
+    /// Corresponds to `rrdtool fetch --align-start`.
+    pub align_start: bool,
+}
+
+/// The inputs to [`fetch_with_options`], for building the equivalent `rrdtool fetch` command line.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::{ops::fetch::FetchCommand, ConsolidationFn, Timestamp};
+/// use std::time::Duration;
+///
+/// let command = FetchCommand {
+///     filename: "data.rrd".into(),
+///     cf: ConsolidationFn::Max,
+///     start: Timestamp::from_timestamp(920804400, 0).unwrap(),
+///     end: Timestamp::from_timestamp(920808000, 0).unwrap(),
+///     resolution: Duration::from_secs(300),
+///     options: Default::default(),
+/// };
+/// assert_eq!(
+///     "rrdtool fetch data.rrd MAX --resolution 300 --start 920804400 --end 920808000",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct FetchCommand {
+    pub filename: PathBuf,
+    pub cf: ConsolidationFn,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    pub resolution: Duration,
+    pub options: FetchOptions,
+}
+
+impl FetchCommand {
+    /// Returns the `rrdtool` arguments, starting with `fetch`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec![
+            "fetch".to_string(),
+            path_to_str(&self.filename)?.to_string(),
+            self.cf.as_arg_str().to_string(),
+            "--resolution".to_string(),
+            self.resolution.as_secs().to_string(),
+            "--start".to_string(),
+            self.start.timestamp().to_string(),
+            "--end".to_string(),
+            self.end.timestamp().to_string(),
+        ];
+        if self.options.align_start {
+            args.push("--align-start".to_string());
+        }
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool fetch` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
+}
+
+/// Like [`fetch`], with additional [`FetchOptions`].
+pub fn fetch_with_options(
+    filename: &Path,
+    cf: ConsolidationFn,
+    start: Timestamp,
+    end: Timestamp,
+    resolution: Duration,
+    options: FetchOptions,
 ) -> RrdResult<Data<Array>> {
     // in
-    let filename = CString::new(path_to_str(filename)?)?;
+    let c_filename = CString::new(path_to_str(filename)?)?;
     let cf = CString::new(cf.as_arg_str())?;
 
     // in/out - clobber var names to avoid accidentally using original input values
     let mut start = start.as_time_t();
     let mut end = end.as_time_t();
+    if options.align_start {
+        let resolution_secs = i64::try_from(resolution.as_secs())
+            .map_err(|_| RrdError::InvalidArgument("Implausibly long resolution".to_string()))?;
+        if resolution_secs > 0 {
+            let delta = start.rem_euclid(resolution_secs);
+            start -= delta;
+            end -= delta;
+        }
+    }
     // windows c_ulong is u32
     #[allow(clippy::useless_conversion)]
     let mut resolution = resolution
         .as_secs()
         .try_into()
-        .expect("Implausibly long resolution");
+        .map_err(|_| RrdError::InvalidArgument("Implausibly long resolution".to_string()))?;
 
     // out
     let mut ds_count = 0;
     let mut ds_names = null_mut();
     let mut data = null_mut();
 
+    context::prepare("rrd_fetch_r")?;
     let rc = unsafe {
         rrd_sys::rrd_fetch_r(
-            filename.as_ptr(),
+            c_filename.as_ptr(),
             cf.as_ptr(),
             &mut start,
             &mut end,
@@ -58,50 +155,47 @@ pub fn fetch(
             &mut data,
         )
     };
-    return_code_to_result(rc)?;
-
-    assert!(!ds_names.is_null());
-    assert!(!data.is_null());
-    assert!(resolution > 0);
-
-    // Move forward one step -- first timestamp's data is included in the time that ends one step ahead
-    let start = Timestamp::from_timestamp(
-        start
-            .checked_add(i64::try_from(resolution).expect("Resolution i64 overflow"))
-            .expect("Start overflow"),
-        0,
-    )
-    .expect("Impossible start");
-    let end = Timestamp::from_timestamp(end, 0).expect("Impossible end");
-
-    let ds_count_usize = ds_count.try_into().expect("Count overflow");
+    return_code_to_result(rc, Operation::Fetch, Some(filename))?;
 
-    let names = unsafe {
-        let names: Vec<_> = slice::from_raw_parts(ds_names, ds_count_usize)
-            .iter()
-            .map(|p| {
-                let s = CStr::from_ptr(*p).to_string_lossy().into_owned();
-                rrd_sys::rrd_freemem(*p as *mut rrd_void);
-                s
-            })
-            .collect();
-        rrd_sys::rrd_freemem(ds_names as *mut rrd_void);
-        names
+    // Take ownership right away so that the librrd allocations are freed on every return path.
+    // The length is filled in once it has been validated.
+    let mut data = Array { ptr: data, len: 0 };
+    let ds_names = FetchedNames {
+        ptr: ds_names,
+        count: ds_count,
     };
 
-    let rows = (usize::try_from(
-        end.timestamp()
-            .checked_sub(start.timestamp())
-            .expect("Negative time range"),
-    )
-    .expect("Time range overflow")
-        / usize::try_from(resolution).expect("Resolution usize overflow"))
-    .checked_add(1)
-    .expect("Num rows overflow");
-    let data = Array {
-        ptr: data,
-        len: rows.checked_mul(ds_count_usize).expect("Data len overflow"),
-    };
+    if ds_names.ptr.is_null() || data.ptr.is_null() {
+        return Err(RrdError::Internal(
+            "Fetch output missing, but no librrd error".to_string(),
+        ));
+    }
+    let names = ds_names.to_vec()?;
+    if names.is_empty() {
+        return Err(RrdError::Internal("Fetch output has no DS".to_string()));
+    }
+
+    let resolution_secs = i64::try_from(resolution)
+        .ok()
+        .filter(|r| *r > 0)
+        .ok_or_else(|| RrdError::Internal(format!("Unexpected fetch resolution {resolution}")))?;
+    // Each row covers the step that ends at its timestamp, so the first row is one step ahead
+    let rows = end
+        .checked_sub(start)
+        .filter(|range| *range >= 0)
+        .and_then(|range| usize::try_from(range / resolution_secs).ok())
+        .ok_or_else(|| {
+            RrdError::Internal(format!("Unexpected fetch time range {start} to {end}"))
+        })?;
+    let start = start
+        .checked_add(resolution_secs)
+        .and_then(|start| Timestamp::from_timestamp(start, 0))
+        .ok_or_else(|| RrdError::Internal(format!("Unexpected fetch start {start}")))?;
+    let end = Timestamp::from_timestamp(end, 0)
+        .ok_or_else(|| RrdError::Internal(format!("Unexpected fetch end {end}")))?;
+    data.len = rows
+        .checked_mul(names.len())
+        .ok_or_else(|| RrdError::Internal("Fetch data length overflow".to_string()))?;
 
     // we need u64, but windows c_ulong is u32
     #[allow(clippy::useless_conversion)]
@@ -114,6 +208,175 @@ pub fn fetch(
     ))
 }
 
+/// Fetch data from `filename` once per consolidation function in `cfs`, and combine the results
+/// on a common time axis.
+///
+/// Each DS appears once per consolidation function, with the consolidation function appended to
+/// its name, e.g. `speed_AVERAGE`, `speed_MIN`, `speed_MAX`. Columns are grouped by DS, in the
+/// order of `cfs`.
+///
+/// Separate fetches can select different RRAs and return different time ranges, so the result
+/// only covers the rows present in all of them. An error is returned if the RRAs chosen have
+/// different resolutions.
+pub fn fetch_multi(
+    filename: &Path,
+    cfs: &[ConsolidationFn],
+    start: Timestamp,
+    end: Timestamp,
+    resolution: Duration,
+    options: FetchOptions,
+) -> RrdResult<Data<Vec<rrd_double>>> {
+    let (first_cf, other_cfs) = cfs.split_first().ok_or_else(|| {
+        RrdError::InvalidArgument("At least one consolidation function is required".to_string())
+    })?;
+    if cfs.iter().enumerate().any(|(i, cf)| cfs[..i].contains(cf)) {
+        return Err(RrdError::InvalidArgument(
+            "Duplicate consolidation function".to_string(),
+        ));
+    }
+
+    let first = fetch_with_options(filename, *first_cf, start, end, resolution, options)?;
+    // Request the same range and resolution that the first fetch actually returned, so that
+    // librrd selects matching RRAs for the rest where it can
+    let start = first.start() - first.step();
+    let end = first.end();
+    let resolution = first.step();
+
+    let mut fetched = vec![(*first_cf, first)];
+    for cf in other_cfs {
+        let data = fetch_with_options(
+            filename,
+            *cf,
+            start,
+            end,
+            resolution,
+            FetchOptions::default(),
+        )?;
+        fetched.push((*cf, data));
+    }
+
+    merge_consolidated(&fetched)
+}
+
+/// Combine the output of fetches with different consolidation functions into one [`Data`],
+/// keeping only the rows present in all of them.
+fn merge_consolidated<T>(fetched: &[(ConsolidationFn, Data<T>)]) -> RrdResult<Data<Vec<rrd_double>>>
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    let (_, first) = fetched
+        .first()
+        .ok_or_else(|| RrdError::Internal("Nothing to merge".to_string()))?;
+    let step = first.step();
+    let names = first.ds_names();
+    for (cf, data) in fetched {
+        if data.step() != step {
+            return Err(RrdError::Internal(format!(
+                "{} resolution {}s doesn't match {}s",
+                cf.as_arg_str(),
+                data.step().as_secs(),
+                step.as_secs()
+            )));
+        }
+        if data.ds_names() != names {
+            return Err(RrdError::Internal(format!(
+                "{} DS names don't match",
+                cf.as_arg_str()
+            )));
+        }
+    }
+
+    let start = fetched
+        .iter()
+        .map(|(_, data)| data.start())
+        .max()
+        .expect("Not empty");
+    let end = fetched
+        .iter()
+        .map(|(_, data)| data.end())
+        .min()
+        .expect("Not empty");
+    if start > end {
+        return Err(RrdError::Internal(
+            "Fetched time ranges don't overlap".to_string(),
+        ));
+    }
+    let sliced = fetched
+        .iter()
+        .map(|(cf, data)| (cf, data.slice(start..=end)))
+        .collect::<Vec<_>>();
+    let rows = sliced[0].1.row_count();
+    if sliced
+        .iter()
+        .any(|(_, data)| data.row_count() != rows || data.start() != start)
+    {
+        return Err(RrdError::Internal(
+            "Fetched rows aren't aligned".to_string(),
+        ));
+    }
+
+    let merged_names = names
+        .iter()
+        .flat_map(|name| {
+            sliced
+                .iter()
+                .map(move |(cf, _)| format!("{name}_{}", cf.as_arg_str()))
+        })
+        .collect::<Vec<_>>();
+    let mut values = Vec::with_capacity(rows * merged_names.len());
+    for row in 0..rows {
+        for ds_index in 0..names.len() {
+            values.extend(
+                sliced
+                    .iter()
+                    .map(|(_, data)| data.values()[row * names.len() + ds_index]),
+            );
+        }
+    }
+
+    Data::from_rows(start, step, merged_names, values)
+}
+
+/// The DS names output by `rrd_fetch_r`, which are freed on drop.
+struct FetchedNames {
+    ptr: *mut *mut rrd_char,
+    count: rrd_ulong,
+}
+
+impl FetchedNames {
+    fn to_vec(&self) -> RrdResult<Vec<String>> {
+        let count = usize::try_from(self.count)
+            .map_err(|_| RrdError::Internal("DS count overflow".to_string()))?;
+        unsafe { slice::from_raw_parts(self.ptr, count) }
+            .iter()
+            .map(|p| {
+                if p.is_null() {
+                    Err(RrdError::Internal("Null DS name".to_string()))
+                } else {
+                    Ok(unsafe { CStr::from_ptr(*p) }.to_string_lossy().into_owned())
+                }
+            })
+            .collect()
+    }
+}
+
+impl Drop for FetchedNames {
+    fn drop(&mut self) {
+        if self.ptr.is_null() {
+            return;
+        }
+        unsafe {
+            // A count that doesn't fit can't describe a real allocation
+            if let Ok(count) = usize::try_from(self.count) {
+                for p in slice::from_raw_parts(self.ptr, count) {
+                    rrd_sys::rrd_freemem(*p as *mut rrd_void);
+                }
+            }
+            rrd_sys::rrd_freemem(self.ptr as *mut rrd_void);
+        }
+    }
+}
+
 /// Contiguous data for the output of [`fetch`].
 ///
 /// This is not intended to be used directly, but rather is the underlying storage accessed via
@@ -133,6 +396,9 @@ impl Drop for Array {
 
 unsafe impl Send for Array {}
 
+// Only ever read through shared references
+unsafe impl Sync for Array {}
+
 impl Deref for Array {
     type Target = [rrd_double];
 
@@ -146,3 +412,69 @@ impl fmt::Debug for Array {
         f.debug_list().entries(self.deref().iter()).finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ts(secs: i64) -> Timestamp {
+        Timestamp::from_timestamp(secs, 0).unwrap()
+    }
+
+    fn data(start: i64, step: u64, values: Vec<f64>) -> Data<Vec<f64>> {
+        Data::from_rows(
+            ts(start),
+            Duration::from_secs(step),
+            vec!["a".to_string(), "b".to_string()],
+            values,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn merge_intersects_time_ranges() {
+        let merged = merge_consolidated(&[
+            (
+                ConsolidationFn::Avg,
+                data(100, 10, vec![1.0, 2.0, 3.0, 4.0]),
+            ),
+            (
+                ConsolidationFn::Max,
+                data(110, 10, vec![5.0, 6.0, 7.0, 8.0]),
+            ),
+        ])
+        .unwrap();
+
+        assert_eq!(ts(110), merged.start());
+        assert_eq!(ts(110), merged.end());
+        assert_eq!(
+            &["a_AVERAGE", "a_MAX", "b_AVERAGE", "b_MAX"],
+            merged.ds_names()
+        );
+        assert_eq!(&[3.0, 5.0, 4.0, 6.0], merged.values());
+    }
+
+    #[test]
+    fn merge_rejects_mismatched_step() {
+        assert!(merge_consolidated(&[
+            (ConsolidationFn::Avg, data(100, 10, vec![1.0, 2.0])),
+            (ConsolidationFn::Min, data(100, 20, vec![1.0, 2.0])),
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn merge_rejects_misaligned_rows() {
+        assert!(merge_consolidated(&[
+            (
+                ConsolidationFn::Avg,
+                data(100, 10, vec![1.0, 2.0, 3.0, 4.0])
+            ),
+            (
+                ConsolidationFn::Min,
+                data(105, 10, vec![1.0, 2.0, 3.0, 4.0])
+            ),
+        ])
+        .is_err());
+    }
+}
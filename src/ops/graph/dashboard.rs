@@ -0,0 +1,789 @@
+//! Compose several graphs into one image.
+//!
+//! A [`Dashboard`] renders each of its graphs over a shared time range, and lays them out in a
+//! grid under a shared title. Each row is as tall as its tallest graph, and each column as wide as
+//! its widest graph.
+//!
+//! SVG dashboards nest each graph's `<svg>` in the dashboard's. PNG dashboards decode each graph
+//! and copy it into the dashboard's image, and require the `png` feature. There's no text rendering
+//! for PNGs, so `librrd` draws a PNG dashboard's title: one more graph is rendered, with the first
+//! graph's props and the first graph's first `DEF`, and cropped to its title.
+
+use crate::{
+    error::{RrdError, RrdResult},
+    ops::graph::{
+        self,
+        elements::{Def, GraphElement, Line},
+        props::{
+            ColorTag, GraphProps, ImageFormat, Labels, Legend, Size, TimeRange, XAxis, XAxisGrid,
+            YAxis, YAxisGrid,
+        },
+        Color, GraphMetadata,
+    },
+};
+use std::{fmt::Write as _, sync};
+
+/// Height in pixels of the title in SVG dashboards.
+const SVG_TITLE_HEIGHT: u32 = 30;
+
+/// A grid of graphs rendered as one image.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rrd::ops::graph::{self, dashboard::Dashboard, props};
+/// use std::path::Path;
+///
+/// let mut dashboard = Dashboard::new(3);
+/// dashboard.title = Some("Daily report".to_string());
+/// for rrd in ["a.rrd", "b.rrd", "c.rrd"] {
+///     let (props, elements) = graph::quick_graph(Path::new(rrd), props::TimeRange::default())?;
+///     dashboard.add(props, elements);
+/// }
+/// let (image, layout) = dashboard.render(props::ImageFormat::Svg)?;
+/// # Ok::<(), rrd::error::RrdError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Dashboard {
+    /// Title centered above the graphs. For PNG, it costs an extra graph render (see the
+    /// [module docs](self)).
+    pub title: Option<String>,
+    /// Time range for every graph, replacing the range in each graph's props
+    pub time_range: TimeRange,
+    /// Number of graphs per row
+    pub columns: usize,
+    /// Space in pixels around and between graphs
+    pub spacing: u32,
+    /// Color behind and between graphs
+    pub background: Color,
+    /// Graphs, in rows from the top left
+    pub graphs: Vec<DashboardGraph>,
+}
+
+/// A graph in a [`Dashboard`].
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub struct DashboardGraph {
+    pub props: GraphProps,
+    pub elements: Vec<GraphElement>,
+}
+
+/// Where each graph was placed in a rendered [`Dashboard`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashboardLayout {
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+    /// A cell for each graph, in the same order as [`Dashboard::graphs`]
+    pub cells: Vec<DashboardCell>,
+}
+
+/// A graph's position in a [`DashboardLayout`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct DashboardCell {
+    /// Offset in pixels of the graph's image from the left edge of the dashboard
+    pub x: u32,
+    /// Offset in pixels of the graph's image from the top edge of the dashboard
+    pub y: u32,
+    /// The graph's metadata, with positions relative to the graph's image. Like `librrd`'s, they
+    /// don't include the graph's `--zoom`.
+    pub metadata: GraphMetadata,
+}
+
+impl Dashboard {
+    /// An empty dashboard with `columns` graphs per row, on a white background.
+    pub fn new(columns: usize) -> Self {
+        Self {
+            title: None,
+            time_range: TimeRange::default(),
+            columns,
+            spacing: 10,
+            background: Color {
+                red: 0xFF,
+                green: 0xFF,
+                blue: 0xFF,
+                alpha: None,
+            },
+            graphs: vec![],
+        }
+    }
+
+    /// Add a graph after the existing graphs.
+    pub fn add(&mut self, props: GraphProps, elements: Vec<GraphElement>) {
+        self.graphs.push(DashboardGraph { props, elements });
+    }
+
+    /// Render every graph, and compose them into one image.
+    ///
+    /// Only [`ImageFormat::Svg`], and [`ImageFormat::Png`] with the `png` feature, are supported.
+    pub fn render(&self, image_format: ImageFormat) -> RrdResult<(Vec<u8>, DashboardLayout)> {
+        match image_format {
+            ImageFormat::Svg => {}
+            #[cfg(feature = "png")]
+            ImageFormat::Png => {}
+            _ => {
+                return Err(RrdError::InvalidArgument(format!(
+                    "Dashboards can't be rendered as {image_format:?}"
+                )))
+            }
+        }
+        if self.columns == 0 {
+            return Err(RrdError::InvalidArgument(
+                "Dashboard must have at least one column".to_string(),
+            ));
+        }
+        if self.graphs.is_empty() {
+            return Err(RrdError::InvalidArgument(
+                "Dashboard must have at least one graph".to_string(),
+            ));
+        }
+        // Checked before rendering anything, and relied on for PNG titles
+        if let Some(index) = self.graphs.iter().position(|g| first_def(g).is_none()) {
+            return Err(RrdError::InvalidArgument(format!(
+                "Dashboard graph {index} must have at least one Def"
+            )));
+        }
+
+        let rendered = self
+            .graphs
+            .iter()
+            .map(|g| {
+                let props = GraphProps {
+                    time_range: self.time_range.clone(),
+                    ..g.props.clone()
+                };
+                graph::graph(image_format, props, &g.elements)
+            })
+            .collect::<RrdResult<Vec<_>>>()?;
+
+        match image_format {
+            #[cfg(feature = "png")]
+            ImageFormat::Png => self.render_png(rendered),
+            _ => self.render_svg(rendered),
+        }
+    }
+
+    fn render_svg(
+        &self,
+        rendered: Vec<(Vec<u8>, GraphMetadata)>,
+    ) -> RrdResult<(Vec<u8>, DashboardLayout)> {
+        let sizes = rendered
+            .iter()
+            .zip(&self.graphs)
+            .map(|((_, metadata), g)| svg_size(metadata, zoom(&g.props)))
+            .collect::<RrdResult<Vec<_>>>()?;
+        let title_height = if self.title.is_some() {
+            SVG_TITLE_HEIGHT
+        } else {
+            0
+        };
+        let grid = Grid::new(&sizes, self.columns, self.spacing, title_height);
+        let images = rendered
+            .iter()
+            .map(|(image, _)| image.as_slice())
+            .collect::<Vec<_>>();
+        let svg = compose_svg(
+            self.title.as_deref(),
+            self.title_color(),
+            self.background,
+            &grid,
+            &sizes,
+            &images,
+        )?;
+        Ok((svg, grid.into_layout(rendered)))
+    }
+
+    #[cfg(feature = "png")]
+    fn render_png(
+        &self,
+        rendered: Vec<(Vec<u8>, GraphMetadata)>,
+    ) -> RrdResult<(Vec<u8>, DashboardLayout)> {
+        // The metadata's sizes are before `--zoom`, so use the images'
+        let images = rendered
+            .iter()
+            .map(|(image, _)| png_image::Rgba::decode(image))
+            .collect::<RrdResult<Vec<_>>>()?;
+        let sizes = images
+            .iter()
+            .map(|image| (image.width, image.height))
+            .collect::<Vec<_>>();
+
+        // There's no text rendering here, so librrd draws the title
+        let title = match &self.title {
+            Some(title) => {
+                let grid = Grid::new(&sizes, self.columns, self.spacing, 0);
+                Some(self.render_png_title(title, grid.width)?)
+            }
+            None => None,
+        };
+        let title_height = title.as_ref().map_or(0, |t| t.height);
+        let grid = Grid::new(&sizes, self.columns, self.spacing, title_height);
+
+        let mut canvas = png_image::Rgba::filled(grid.width, grid.height, self.background);
+        if let Some(title) = &title {
+            let x = (grid.width.saturating_sub(title.width)) / 2;
+            canvas.draw(title, x, 0);
+        }
+        for (image, (x, y)) in images.iter().zip(&grid.positions) {
+            canvas.draw(image, *x, *y);
+        }
+        Ok((canvas.encode()?, grid.into_layout(rendered)))
+    }
+
+    /// Renders just the title, `width` pixels wide, with the first graph's colors, fonts and zoom.
+    ///
+    /// `librrd` can't draw a graph without data, so the first graph's first `Def` is read again.
+    #[cfg(feature = "png")]
+    fn render_png_title(&self, title: &str, width: u32) -> RrdResult<png_image::Rgba> {
+        let first = &self.graphs[0];
+        let def = first_def(first).ok_or_else(|| {
+            RrdError::InvalidArgument("Dashboard graph 0 must have at least one Def".to_string())
+        })?;
+        // librrd's sizes and positions are scaled by the zoom when drawn
+        let zoom = zoom(&first.props);
+        let (props, elements) = title_graph(
+            &first.props,
+            self.time_range.clone(),
+            title,
+            (f64::from(width) / zoom) as u32,
+            def,
+        );
+        let (image, metadata) = graph::graph(ImageFormat::Png, props, &elements)?;
+
+        // The title is all that's drawn above the graph area
+        let mut image = png_image::Rgba::decode(&image)?;
+        image.crop_height((metadata.graph_top as f64 * zoom) as u32);
+        Ok(image)
+    }
+
+    /// The first graph's font color, or black.
+    fn title_color(&self) -> Color {
+        self.graphs
+            .first()
+            .and_then(|g| g.props.misc.colors.get(&ColorTag::Font).copied())
+            .unwrap_or(Color {
+                red: 0,
+                green: 0,
+                blue: 0,
+                alpha: None,
+            })
+    }
+}
+
+fn first_def(graph: &DashboardGraph) -> Option<&Def> {
+    graph.elements.iter().find_map(|e| match e {
+        GraphElement::Def(def) => Some(def),
+        _ => None,
+    })
+}
+
+/// A graph that draws nothing but `title`, in the style of `props`.
+#[cfg_attr(not(feature = "png"), allow(dead_code))]
+fn title_graph(
+    props: &GraphProps,
+    time_range: TimeRange,
+    title: &str,
+    width: u32,
+    def: &Def,
+) -> (GraphProps, Vec<GraphElement>) {
+    let transparent = Color {
+        red: 0,
+        green: 0,
+        blue: 0,
+        alpha: Some(0),
+    };
+    let mut misc = props.misc.clone();
+    for tag in [
+        ColorTag::Back,
+        ColorTag::Canvas,
+        ColorTag::ShadeA,
+        ColorTag::ShadeB,
+        ColorTag::Grid,
+        ColorTag::MGrid,
+        ColorTag::Axis,
+        ColorTag::Frame,
+        ColorTag::Arrow,
+    ] {
+        misc.colors.insert(tag, transparent);
+    }
+    misc.watermark = None;
+
+    let props = GraphProps {
+        time_range,
+        labels: Labels {
+            title: Some(title.to_string()),
+            vertical_label: None,
+        },
+        size: Size {
+            width: Some(width.saturating_sub(100).max(100)),
+            height: Some(10),
+            ..Default::default()
+        },
+        x_axis: XAxis {
+            grid: Some(XAxisGrid::None),
+            week_format: None,
+        },
+        y_axis: YAxis {
+            grid: Some(YAxisGrid::None),
+            ..Default::default()
+        },
+        legend: Legend {
+            no_legend: true,
+            ..Default::default()
+        },
+        misc,
+        ..Default::default()
+    };
+    let elements = vec![
+        def.clone().into(),
+        // A line without a color isn't drawn
+        Line {
+            width: 1.0,
+            value: def.var_name.clone(),
+            color: None,
+            stack: false,
+            skip_scale: false,
+            dashes: None,
+        }
+        .into(),
+    ];
+    (props, elements)
+}
+
+/// Positions of graphs in a dashboard.
+#[derive(Debug, PartialEq)]
+struct Grid {
+    width: u32,
+    height: u32,
+    /// Top left of each graph
+    positions: Vec<(u32, u32)>,
+}
+
+impl Grid {
+    /// Lay out graphs of `sizes` in rows of `columns`, below `title_height` pixels of title.
+    fn new(sizes: &[(u32, u32)], columns: usize, spacing: u32, title_height: u32) -> Self {
+        let mut column_widths = vec![0_u32; columns.min(sizes.len())];
+        let mut row_heights = vec![0_u32; sizes.len().div_ceil(columns)];
+        for (i, (width, height)) in sizes.iter().enumerate() {
+            let column = &mut column_widths[i % columns];
+            *column = (*column).max(*width);
+            let row = &mut row_heights[i / columns];
+            *row = (*row).max(*height);
+        }
+
+        // Offsets of each column and row, and the total width and height, with spacing around
+        let offsets = |lengths: &[u32], start: u32| {
+            lengths.iter().fold(vec![start + spacing], |mut acc, l| {
+                acc.push(acc.last().unwrap() + l + spacing);
+                acc
+            })
+        };
+        let xs = offsets(&column_widths, 0);
+        let ys = offsets(&row_heights, title_height);
+        Self {
+            width: *xs.last().unwrap(),
+            height: *ys.last().unwrap(),
+            positions: (0..sizes.len())
+                .map(|i| (xs[i % columns], ys[i / columns]))
+                .collect(),
+        }
+    }
+
+    fn into_layout(self, rendered: Vec<(Vec<u8>, GraphMetadata)>) -> DashboardLayout {
+        DashboardLayout {
+            width: self.width,
+            height: self.height,
+            cells: self
+                .positions
+                .into_iter()
+                .zip(rendered)
+                .map(|((x, y), (_, metadata))| DashboardCell { x, y, metadata })
+                .collect(),
+        }
+    }
+}
+
+/// `props`'s `--zoom`, or 1.
+fn zoom(props: &GraphProps) -> f64 {
+    props.misc.zoom.map_or(1.0, f64::from)
+}
+
+/// The size of an SVG graph, which is its metadata's size (from before `--zoom`) scaled by `zoom`.
+fn svg_size(metadata: &GraphMetadata, zoom: f64) -> RrdResult<(u32, u32)> {
+    let convert = |n: u64| {
+        let zoomed = (n as f64 * zoom).ceil();
+        if zoomed <= f64::from(u32::MAX) {
+            Ok(zoomed as u32)
+        } else {
+            Err(RrdError::Internal(format!(
+                "Graph image size {zoomed} is too large"
+            )))
+        }
+    };
+    Ok((
+        convert(metadata.image_width)?,
+        convert(metadata.image_height)?,
+    ))
+}
+
+/// Nest the SVG `images` in a new SVG document, at the positions in `grid`.
+fn compose_svg(
+    title: Option<&str>,
+    title_color: Color,
+    background: Color,
+    grid: &Grid,
+    sizes: &[(u32, u32)],
+    images: &[&[u8]],
+) -> RrdResult<Vec<u8>> {
+    let (width, height) = (grid.width, grid.height);
+    let mut svg = String::new();
+    writeln!(svg, r#"<?xml version="1.0" encoding="UTF-8"?>"#).unwrap();
+    writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="{width}" height="{height}" viewBox="0 0 {width} {height}" version="1.1">"#
+    )
+    .unwrap();
+    writeln!(
+        svg,
+        r#"<rect width="100%" height="100%" {}/>"#,
+        svg_fill(background)
+    )
+    .unwrap();
+    if let Some(title) = title {
+        writeln!(
+            svg,
+            r#"<text x="{}" y="20" text-anchor="middle" font-family="sans-serif" font-size="16" {}>{}</text>"#,
+            width / 2,
+            svg_fill(title_color),
+            escape_xml(title)
+        )
+        .unwrap();
+    }
+    for (i, ((image, (x, y)), (w, h))) in images.iter().zip(&grid.positions).zip(sizes).enumerate()
+    {
+        svg.push_str(&nest_svg(image, &format!("g{i}-"), *x, *y, *w, *h)?);
+        svg.push('\n');
+    }
+    svg.push_str("</svg>\n");
+    Ok(svg.into_bytes())
+}
+
+/// Returns `image`'s root `<svg>` element, positioned at `x`,`y` with size `width`x`height` in
+/// pixels, and with its ids prefixed with `id_prefix` so they're unique in the dashboard.
+fn nest_svg(
+    image: &[u8],
+    id_prefix: &str,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+) -> RrdResult<String> {
+    static SIZE_ATTR: sync::LazyLock<regex::Regex> =
+        sync::LazyLock::new(|| regex::Regex::new(r#"\s(width|height|x|y)="[^"]*""#).unwrap());
+    // ids are referenced with `url(#id)` and `href="#id"`, e.g. for glyphs and clip paths
+    static ID_REF: sync::LazyLock<regex::Regex> =
+        sync::LazyLock::new(|| regex::Regex::new(r##"(\bid="|href="#|url\(#)"##).unwrap());
+
+    let malformed = || RrdError::Internal("Malformed graph SVG".to_string());
+    let image = std::str::from_utf8(image).map_err(|_| malformed())?;
+    // Skip the XML declaration and doctype
+    let root = &image[image.find("<svg").ok_or_else(malformed)?..];
+    let tag_end = root.find('>').ok_or_else(malformed)?;
+    // cairo sizes the root in points, which would scale the graph when nested
+    let tag = SIZE_ATTR.replace_all(&root[..tag_end], "");
+    let tag = tag.trim_end_matches('/');
+    let self_closing = root[..tag_end].ends_with('/');
+    let nested = format!(
+        r#"{tag} x="{x}" y="{y}" width="{width}" height="{height}"{}{}"#,
+        if self_closing { "/" } else { "" },
+        root[tag_end..].trim_end()
+    );
+    Ok(ID_REF
+        .replace_all(&nested, |c: &regex::Captures| {
+            format!("{}{id_prefix}", &c[1])
+        })
+        .into_owned())
+}
+
+fn svg_fill(color: Color) -> String {
+    let Color {
+        red, green, blue, ..
+    } = color;
+    let opacity = color.alpha.map_or(1.0, |a| f64::from(a) / 255.0);
+    format!(r##"fill="#{red:02X}{green:02X}{blue:02X}" fill-opacity="{opacity}""##)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Just enough raster image handling to composite PNGs.
+#[cfg(feature = "png")]
+mod png_image {
+    use crate::{
+        error::{RrdError, RrdResult},
+        ops::graph::Color,
+    };
+
+    /// 8 bit RGBA pixels.
+    #[derive(Debug, Clone, PartialEq)]
+    pub(super) struct Rgba {
+        pub(super) width: u32,
+        pub(super) height: u32,
+        pixels: Vec<u8>,
+    }
+
+    impl Rgba {
+        pub(super) fn filled(width: u32, height: u32, color: Color) -> Self {
+            let pixel = [
+                color.red,
+                color.green,
+                color.blue,
+                color.alpha.unwrap_or(0xFF),
+            ];
+            Self {
+                width,
+                height,
+                pixels: pixel.repeat(width as usize * height as usize),
+            }
+        }
+
+        pub(super) fn decode(png: &[u8]) -> RrdResult<Self> {
+            let malformed =
+                |e: png::DecodingError| RrdError::Internal(format!("Malformed graph PNG: {e}"));
+            let mut decoder = png::Decoder::new(png);
+            decoder.set_transformations(png::Transformations::normalize_to_color8());
+            let mut reader = decoder.read_info().map_err(malformed)?;
+            let mut buf = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut buf).map_err(malformed)?;
+            buf.truncate(info.buffer_size());
+
+            let pixels = match info.color_type {
+                png::ColorType::Rgba => buf,
+                png::ColorType::Rgb => buf
+                    .chunks_exact(3)
+                    .flat_map(|p| [p[0], p[1], p[2], 0xFF])
+                    .collect(),
+                png::ColorType::GrayscaleAlpha => buf
+                    .chunks_exact(2)
+                    .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                    .collect(),
+                png::ColorType::Grayscale => buf.iter().flat_map(|g| [*g, *g, *g, 0xFF]).collect(),
+                png::ColorType::Indexed => {
+                    return Err(RrdError::Internal(
+                        "Graph PNG palette was not expanded".to_string(),
+                    ))
+                }
+            };
+            Ok(Self {
+                width: info.width,
+                height: info.height,
+                pixels,
+            })
+        }
+
+        pub(super) fn encode(&self) -> RrdResult<Vec<u8>> {
+            let failed =
+                |e: png::EncodingError| RrdError::Internal(format!("PNG encoding failed: {e}"));
+            let mut out = Vec::new();
+            let mut encoder = png::Encoder::new(&mut out, self.width, self.height);
+            encoder.set_color(png::ColorType::Rgba);
+            encoder.set_depth(png::BitDepth::Eight);
+            let mut writer = encoder.write_header().map_err(failed)?;
+            writer.write_image_data(&self.pixels).map_err(failed)?;
+            writer.finish().map_err(failed)?;
+            Ok(out)
+        }
+
+        /// Remove rows below `height`.
+        pub(super) fn crop_height(&mut self, height: u32) {
+            self.height = self.height.min(height);
+            self.pixels
+                .truncate(self.width as usize * self.height as usize * 4);
+        }
+
+        /// Draw `image` over this image with its top left at `x`,`y`, blending by its alpha.
+        pub(super) fn draw(&mut self, image: &Rgba, x: u32, y: u32) {
+            let width = image.width.min(self.width.saturating_sub(x)) as usize;
+            let height = image.height.min(self.height.saturating_sub(y)) as usize;
+            for row in 0..height {
+                for column in 0..width {
+                    let src = (row * image.width as usize + column) * 4;
+                    let dst = ((y as usize + row) * self.width as usize + x as usize + column) * 4;
+                    let src = &image.pixels[src..src + 4];
+                    let dst = &mut self.pixels[dst..dst + 4];
+                    blend(dst, src);
+                }
+            }
+        }
+    }
+
+    /// Porter-Duff `src` over `dst`.
+    fn blend(dst: &mut [u8], src: &[u8]) {
+        let src_alpha = u32::from(src[3]);
+        if src_alpha == 0xFF {
+            dst.copy_from_slice(src);
+            return;
+        }
+        let dst_alpha = u32::from(dst[3]) * (0xFF - src_alpha) / 0xFF;
+        let out_alpha = src_alpha + dst_alpha;
+        if out_alpha == 0 {
+            dst.copy_from_slice(&[0, 0, 0, 0]);
+            return;
+        }
+        for c in 0..3 {
+            dst[c] =
+                ((u32::from(src[c]) * src_alpha + u32::from(dst[c]) * dst_alpha) / out_alpha) as u8;
+        }
+        dst[3] = out_alpha as u8;
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn color(hex: &str) -> Color {
+            hex.parse().unwrap()
+        }
+
+        #[test]
+        fn composite_round_trip() {
+            let mut canvas = Rgba::filled(4, 3, color("#FFFFFF"));
+            let opaque = Rgba::filled(2, 2, color("#FF0000"));
+            let translucent = Rgba::filled(2, 2, color("#0000FF80"));
+            // partly off the canvas
+            canvas.draw(&opaque, 3, 0);
+            canvas.draw(&translucent, 0, 1);
+
+            let decoded = Rgba::decode(&canvas.encode().unwrap()).unwrap();
+            assert_eq!(canvas, decoded);
+            let pixel = |x: usize, y: usize| {
+                let i = (y * 4 + x) * 4;
+                decoded.pixels[i..i + 4].to_vec()
+            };
+            assert_eq!(vec![0xFF, 0xFF, 0xFF, 0xFF], pixel(0, 0));
+            assert_eq!(vec![0xFF, 0, 0, 0xFF], pixel(3, 1));
+            assert_eq!(vec![0x7F, 0x7F, 0xFF, 0xFF], pixel(1, 2));
+        }
+
+        #[test]
+        fn crop() {
+            let mut image = Rgba::filled(3, 5, color("#000000"));
+            image.crop_height(2);
+            assert_eq!(
+                Rgba::filled(3, 2, color("#000000")),
+                Rgba::decode(&image.encode().unwrap()).unwrap()
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_uses_widest_column_and_tallest_row() {
+        let grid = Grid::new(&[(100, 50), (80, 70), (120, 40)], 2, 10, 30);
+        assert_eq!(
+            Grid {
+                width: 10 + 120 + 10 + 80 + 10,
+                height: 30 + 10 + 70 + 10 + 40 + 10,
+                positions: vec![(10, 40), (140, 40), (10, 120)],
+            },
+            grid
+        );
+
+        // fewer graphs than columns
+        let grid = Grid::new(&[(100, 50)], 3, 5, 0);
+        assert_eq!((110, 60), (grid.width, grid.height));
+    }
+
+    #[test]
+    fn nested_svg_is_positioned_and_has_unique_ids() {
+        let image = br##"<?xml version="1.0" encoding="UTF-8"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" width="481pt" height="141pt" viewBox="0 0 481 141" version="1.1">
+<defs><g><symbol overflow="visible" id="glyph0-1"><path d="M 1 1 "/></symbol></g>
+<clipPath id="clip1"><path d="M 0 0 L 1 1 Z"/></clipPath></defs>
+<g clip-path="url(#clip1)"><use xlink:href="#glyph0-1" x="51" y="15"/></g>
+</svg>
+"##;
+        let nested = nest_svg(image, "g3-", 10, 20, 481, 141).unwrap();
+        assert_eq!(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink" viewBox="0 0 481 141" version="1.1" x="10" y="20" width="481" height="141">
+<defs><g><symbol overflow="visible" id="g3-glyph0-1"><path d="M 1 1 "/></symbol></g>
+<clipPath id="g3-clip1"><path d="M 0 0 L 1 1 Z"/></clipPath></defs>
+<g clip-path="url(#g3-clip1)"><use xlink:href="#g3-glyph0-1" x="51" y="15"/></g>
+</svg>"##,
+            nested
+        );
+
+        assert!(matches!(
+            nest_svg(b"%PDF-1.5", "g0-", 0, 0, 1, 1),
+            Err(RrdError::Internal(_))
+        ));
+    }
+
+    #[test]
+    fn compose_svg_escapes_title() {
+        let image = br#"<svg viewBox="0 0 2 2" width="2pt" height="2pt"></svg>"#;
+        let grid = Grid::new(&[(2, 2)], 1, 0, SVG_TITLE_HEIGHT);
+        let svg = compose_svg(
+            Some("<b>&"),
+            "#112233".parse().unwrap(),
+            "#FFFFFF80".parse().unwrap(),
+            &grid,
+            &[(2, 2)],
+            &[image],
+        )
+        .unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert!(
+            svg.contains(r##"fill="#FFFFFF" fill-opacity="0.5019607843137255""##),
+            "{svg}"
+        );
+        assert!(svg.contains(">&lt;b&gt;&amp;</text>"), "{svg}");
+        assert!(
+            svg.contains(r#"<svg viewBox="0 0 2 2" x="0" y="30" width="2" height="2"></svg>"#),
+            "{svg}"
+        );
+    }
+
+    #[test]
+    fn unsupported_formats_are_rejected() {
+        let dashboard = Dashboard::new(1);
+        assert!(matches!(
+            dashboard.render(ImageFormat::Pdf),
+            Err(RrdError::InvalidArgument(_))
+        ));
+        // before any graphs are rendered
+        assert!(matches!(
+            dashboard.render(ImageFormat::Svg),
+            Err(RrdError::InvalidArgument(_))
+        ));
+    }
+
+    #[test]
+    fn graphs_without_defs_are_rejected() {
+        let mut dashboard = Dashboard::new(1);
+        dashboard.title = Some("Title".to_string());
+        dashboard.add(GraphProps::default(), vec![]);
+        for image_format in [
+            ImageFormat::Svg,
+            #[cfg(feature = "png")]
+            ImageFormat::Png,
+        ] {
+            match dashboard.render(image_format) {
+                Err(RrdError::InvalidArgument(message)) => assert_eq!(
+                    "Dashboard graph 0 must have at least one Def", message,
+                    "{image_format:?}"
+                ),
+                other => panic!("{image_format:?}: {other:?}"),
+            }
+        }
+    }
+}
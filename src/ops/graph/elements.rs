@@ -20,15 +20,20 @@
 //! - [`Shift`]
 //! - [`TextAlign`]
 //! - See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
+//!
+//! Text is either literal ([`Legend`], used for legends and comments) or a [`PrintFormat`] that
+//! mixes literal text with `printf`-style [`Directive`]s. Both are escaped when args are built, so
+//! `:`, `%` and backslashes in user-supplied text can't change the meaning of the arg. RRD paths
+//! in a [`Def`] are escaped the same way, and DS names are validated.
 
 use crate::{
     error::{InvalidArgument, RrdResult},
     ops::graph::{AppendArgs, Color},
-    util::path_to_str,
+    util::{self, path_to_str},
     ConsolidationFn, Timestamp,
 };
 use itertools::Itertools;
-use std::{fmt::Write as _, path::PathBuf, sync};
+use std::{fmt::Write as _, path::PathBuf, str::FromStr, sync};
 
 /// Enum expressing all possible elements.
 ///
@@ -36,6 +41,7 @@ use std::{fmt::Write as _, path::PathBuf, sync};
 /// `.into()` other elements ([`Def`], etc) into a common type in a `graph()` call.
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GraphElement {
     Def(Def),
     CDef(CDef),
@@ -74,9 +80,12 @@ impl AppendArgs for GraphElement {
 
 /// Define data to fetch from a DS.
 ///
+/// `rrd` may contain `:`, which is escaped. `ds_name` must be a valid DS name.
+///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_data.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Def {
     pub var_name: VarName,
     pub rrd: PathBuf,
@@ -91,10 +100,11 @@ pub struct Def {
 
 impl AppendArgs for Def {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        util::check_ds_name(&self.ds_name)?;
         let mut s = format!(
             "DEF:{}={}:{}:{}",
             self.var_name.name,
-            path_to_str(&self.rrd)?,
+            util::escape_field(path_to_str(&self.rrd)?)?,
             self.ds_name,
             self.consolidation_fn.as_arg_str(),
         );
@@ -128,12 +138,14 @@ impl From<Def> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_data.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VDef {
     pub var_name: VarName,
     pub rpn: String,
 }
 impl AppendArgs for VDef {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        check_rpn(&self.rpn)?;
         args.push(format!("VDEF:{}={}", self.var_name.name, self.rpn));
         Ok(())
     }
@@ -151,6 +163,7 @@ impl From<VDef> for GraphElement {
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CDef {
     pub var_name: VarName,
     pub rpn: String,
@@ -158,11 +171,21 @@ pub struct CDef {
 
 impl AppendArgs for CDef {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        check_rpn(&self.rpn)?;
         args.push(format!("CDEF:{}={}", self.var_name.name, self.rpn));
         Ok(())
     }
 }
 
+/// RPN expressions never legitimately contain `:`, so one would only split the arg.
+fn check_rpn(rpn: &str) -> Result<(), InvalidArgument> {
+    if rpn.contains(':') {
+        Err(InvalidArgument("RPN expressions can't contain ':'"))
+    } else {
+        Ok(())
+    }
+}
+
 impl From<CDef> for GraphElement {
     fn from(value: CDef) -> Self {
         Self::CDef(value)
@@ -175,6 +198,8 @@ impl From<CDef> for GraphElement {
 ///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_data.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
 pub struct VarName {
     name: String,
 }
@@ -192,6 +217,10 @@ impl VarName {
             Err(InvalidArgument("Invalid var name"))
         }
     }
+
+    pub(crate) fn name(&self) -> &str {
+        &self.name
+    }
 }
 
 impl TryFrom<String> for VarName {
@@ -209,15 +238,22 @@ impl TryFrom<&str> for VarName {
     }
 }
 
+impl From<VarName> for String {
+    fn from(value: VarName) -> Self {
+        value.name
+    }
+}
+
 /// Specify text to print on the graph.
 ///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Print {
     /// Must be a var name defined by a [`VDef`].
     pub var_name: VarName,
-    pub format: String,
+    pub format: PrintFormat,
     pub format_mode: Option<PrintFormatMode>,
 }
 
@@ -238,7 +274,8 @@ impl AppendArgs for Print {
         };
         args.push(format!(
             "PRINT:{}:{}{fmt_mode}",
-            self.var_name.name, self.format
+            self.var_name.name,
+            self.format.to_arg_string()?
         ));
         Ok(())
     }
@@ -255,6 +292,7 @@ impl From<Print> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum PrintFormatMode {
     StrfTime,
     ValStrfTime,
@@ -266,14 +304,19 @@ pub enum PrintFormatMode {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct GPrint {
     pub var_name: VarName,
-    pub format: String,
+    pub format: PrintFormat,
 }
 
 impl AppendArgs for GPrint {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
-        args.push(format!("GPRINT:{}:{}", self.var_name.name, self.format));
+        args.push(format!(
+            "GPRINT:{}:{}",
+            self.var_name.name,
+            self.format.to_arg_string()?
+        ));
         Ok(())
     }
 }
@@ -289,13 +332,16 @@ impl From<GPrint> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Comment {
-    pub text: String,
+    pub text: Legend,
 }
 
 impl AppendArgs for Comment {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
-        args.push(format!("COMMENT:{}", self.text));
+        let mut s = "COMMENT".to_string();
+        self.text.append_to(&mut s)?;
+        args.push(s);
         Ok(())
     }
 }
@@ -311,6 +357,7 @@ impl From<Comment> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct VRule {
     pub value: Value,
     pub color: Color,
@@ -324,7 +371,7 @@ impl AppendArgs for VRule {
         self.value.append_to(&mut s);
         self.color.append_to(&mut s);
         if let Some(l) = &self.legend {
-            l.append_to(&mut s);
+            l.append_to(&mut s)?;
         }
         if let Some(d) = &self.dashes {
             d.append_to(&mut s);
@@ -345,6 +392,7 @@ impl From<VRule> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     Variable(VarName),
     Timestamp(Timestamp),
@@ -387,6 +435,8 @@ impl From<f64> for Value {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Default, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Dashes {
     pub spacing: Option<DashSpacing>,
     pub offset: Option<u32>,
@@ -421,6 +471,7 @@ impl Dashes {
 ///
 /// See [`Dashes`] and <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DashSpacing {
     /// Must be positive
     Simple(u32),
@@ -435,6 +486,7 @@ pub enum DashSpacing {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct HRule {
     pub value: Value,
     pub color: Color,
@@ -448,7 +500,7 @@ impl AppendArgs for HRule {
         self.value.append_to(&mut s);
         self.color.append_to(&mut s);
         if let Some(l) = &self.legend {
-            l.append_to(&mut s);
+            l.append_to(&mut s)?;
         }
         if let Some(d) = &self.dashes {
             d.append_to(&mut s);
@@ -469,6 +521,7 @@ impl From<HRule> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Line {
     pub width: f64,
     pub value: VarName,
@@ -485,7 +538,7 @@ impl AppendArgs for Line {
         if let Some(cwl) = &self.color {
             cwl.color.append_to(&mut s);
             if let Some(l) = &cwl.legend {
-                l.append_to(&mut s);
+                l.append_to(&mut s)?;
             }
         }
 
@@ -518,6 +571,7 @@ impl From<Line> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ColorWithLegend<C> {
     pub color: C,
     pub legend: Option<Legend>,
@@ -528,6 +582,7 @@ pub struct ColorWithLegend<C> {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Area {
     pub value: VarName,
     pub color: Option<ColorWithLegend<AreaColor>>,
@@ -557,7 +612,7 @@ impl AppendArgs for Area {
             };
 
             if let Some(l) = &cwl.legend {
-                l.append_to(&mut s);
+                l.append_to(&mut s)?;
             }
 
             gh
@@ -600,6 +655,7 @@ impl From<Area> for GraphElement {
 /// See [`Area`] and <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AreaColor {
     Color(Color),
     Gradient {
@@ -614,6 +670,7 @@ pub enum AreaColor {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Tick {
     pub var_name: VarName,
     pub color: Color,
@@ -629,7 +686,7 @@ impl AppendArgs for Tick {
             write!(s, ":{f}").unwrap();
         }
         if let Some(l) = &self.legend {
-            l.append_to(&mut s);
+            l.append_to(&mut s)?;
         }
         args.push(s);
         Ok(())
@@ -647,6 +704,7 @@ impl From<Tick> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Shift {
     pub var_name: VarName,
     pub offset: Offset,
@@ -672,6 +730,7 @@ impl From<Shift> for GraphElement {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Offset {
     Variable(VarName),
     TimeDelta(f64),
@@ -692,6 +751,7 @@ impl Offset {
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextAlign {
     Left,
     Right,
@@ -720,28 +780,324 @@ impl From<TextAlign> for GraphElement {
     }
 }
 
-// TODO escape colons for the user
-/// Text to include in the legend for the containing element.
+/// Literal text to include in the legend for the containing element, or in a [`Comment`].
+///
+/// The text is shown as is: `:` is escaped for `librrd`, and `%` has no special meaning. To
+/// control where the text is placed, add a [`TextControl`] with [`Legend::with_control`] rather
+/// than writing a `\l`-style code into the text.
+///
+/// Use `parse()` to accept text already in `rrdtool` syntax, with `\:` escapes and an optional
+/// trailing control code. Legends used to be `String`s in that syntax, so existing ones should be
+/// parsed rather than passed to [`Legend::new`], which would show the escapes.
+///
+/// # Examples
 ///
-/// Colons (`:`) must be escaped as `\:`, which in a string literal needs the backslash escaped
-/// as well, so it would be typed `"\\:"`.
+/// ```
+/// use rrd::ops::graph::elements::{Legend, TextControl};
+///
+/// let legend = Legend::new("Load at 12:00").with_control(TextControl::Left);
+/// assert_eq!(legend, r"Load at 12\:00\l".parse().unwrap());
+/// ```
 ///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Legend(String);
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Legend {
+    text: String,
+    control: Option<TextControl>,
+}
 
 impl Legend {
-    /// Appends `:` followed by quote-wrapped legend text.
-    fn append_to(&self, s: &mut String) {
-        // It's unclear from the docs -- does this need to be quoted, or is that only to deal with
-        // shell command parsing?
-        write!(s, ":{}", self.0).unwrap()
+    /// Literal `text`, with no control code.
+    pub fn new(text: impl Into<String>) -> Self {
+        Self {
+            text: text.into(),
+            control: None,
+        }
+    }
+
+    /// Add `control` to the end of the text.
+    pub fn with_control(self, control: TextControl) -> Self {
+        Self {
+            control: Some(control),
+            ..self
+        }
+    }
+
+    /// The literal text, without escaping or control code.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The control code at the end of the text, if any.
+    pub fn control(&self) -> Option<TextControl> {
+        self.control
+    }
+
+    /// Appends `:` followed by the escaped legend text.
+    pub(crate) fn append_to(&self, s: &mut String) -> Result<(), InvalidArgument> {
+        s.push(':');
+        s.push_str(&escape_text(&self.text, self.control)?);
+        Ok(())
+    }
+}
+
+impl FromStr for Legend {
+    type Err = InvalidArgument;
+
+    /// Parse `rrdtool` legend syntax.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (text, control) = split_control(s);
+        Ok(Self {
+            text: text.replace("\\:", ":"),
+            control,
+        })
+    }
+}
+
+/// A control code at the end of legend, comment or print text.
+///
+/// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextControl {
+    /// `\l`: left justify, and end the line
+    Left,
+    /// `\n`: a synonym for `\l`
+    Newline,
+    /// `\r`: right justify, and end the line
+    Right,
+    /// `\c`: center, and end the line
+    Center,
+    /// `\j`: justify, and end the line
+    Justified,
+    /// `\g`: don't add a space after the text
+    Glue,
+    /// `\s`: add a small vertical space
+    Space,
+    /// `\u`: move up a line
+    Up,
+}
+
+impl TextControl {
+    fn as_char(self) -> char {
+        match self {
+            TextControl::Left => 'l',
+            TextControl::Newline => 'n',
+            TextControl::Right => 'r',
+            TextControl::Center => 'c',
+            TextControl::Justified => 'j',
+            TextControl::Glue => 'g',
+            TextControl::Space => 's',
+            TextControl::Up => 'u',
+        }
+    }
+
+    fn from_char(c: char) -> Option<Self> {
+        Some(match c {
+            'l' => TextControl::Left,
+            'n' => TextControl::Newline,
+            'r' => TextControl::Right,
+            'c' => TextControl::Center,
+            'j' => TextControl::Justified,
+            'g' => TextControl::Glue,
+            's' => TextControl::Space,
+            'u' => TextControl::Up,
+            _ => return None,
+        })
     }
 }
 
-impl<S: Into<String>> From<S> for Legend {
-    fn from(value: S) -> Self {
-        Self(value.into())
+/// Text for [`Print`] and [`GPrint`]: literal text mixed with `printf`-style [`Directive`]s.
+///
+/// Literal text is escaped, so `%` in it is printed as is rather than read as a directive.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::graph::elements::{Directive, PrintFormat, TextControl};
+///
+/// let format = PrintFormat::default()
+///     .text("100% of CPU: ")
+///     .directive(Directive::value(6, 2))
+///     .directive(Directive::unit())
+///     .control(TextControl::Left);
+/// assert_eq!(format, r"100%% of CPU\: %6.2lf%s\l".parse().unwrap());
+/// ```
+///
+/// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph_graph.en.html>
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PrintFormat {
+    parts: Vec<FormatPart>,
+    control: Option<TextControl>,
+}
+
+impl PrintFormat {
+    /// Append literal `text`.
+    pub fn text(mut self, text: impl Into<String>) -> Self {
+        self.parts.push(FormatPart::Text(text.into()));
+        self
+    }
+
+    /// Append a directive.
+    pub fn directive(mut self, directive: Directive) -> Self {
+        self.parts.push(FormatPart::Directive(directive));
+        self
+    }
+
+    /// Set the control code at the end of the text.
+    pub fn control(self, control: TextControl) -> Self {
+        Self {
+            control: Some(control),
+            ..self
+        }
+    }
+
+    /// The text and directives, in order.
+    pub fn parts(&self) -> &[FormatPart] {
+        &self.parts
+    }
+
+    /// The control code at the end of the text, if any.
+    pub fn text_control(&self) -> Option<TextControl> {
+        self.control
+    }
+
+    fn to_arg_string(&self) -> Result<String, InvalidArgument> {
+        let format = self
+            .parts
+            .iter()
+            .map(|p| match p {
+                FormatPart::Text(t) => t.replace('%', "%%"),
+                FormatPart::Directive(d) => d.0.clone(),
+            })
+            .join("");
+        escape_text(&format, self.control)
+    }
+}
+
+impl FromStr for PrintFormat {
+    type Err = InvalidArgument;
+
+    /// Parse `rrdtool` format syntax.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (format, control) = split_control(s);
+        let format = format.replace("\\:", ":");
+        let mut parts = vec![];
+        let mut text = String::new();
+        let mut rest = format.as_str();
+        while let Some(i) = rest.find('%') {
+            text.push_str(&rest[..i]);
+            rest = &rest[i..];
+            if let Some(after) = rest.strip_prefix("%%") {
+                text.push('%');
+                rest = after;
+            } else {
+                let m = DIRECTIVE_PREFIX
+                    .find(rest)
+                    .ok_or(InvalidArgument("Invalid format directive"))?;
+                if !text.is_empty() {
+                    parts.push(FormatPart::Text(std::mem::take(&mut text)));
+                }
+                parts.push(FormatPart::Directive(Directive(m.as_str().to_string())));
+                rest = &rest[m.end()..];
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(FormatPart::Text(text));
+        }
+        Ok(Self { parts, control })
+    }
+}
+
+/// Part of a [`PrintFormat`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FormatPart {
+    /// Literal text
+    Text(String),
+    /// A `printf`-style directive
+    Directive(Directive),
+}
+
+/// A single `printf`-style directive, like `%6.2lf`, `%s` or `%H`.
+///
+/// The available directives depend on the [`PrintFormatMode`]: numbers and `%s` for the SI unit
+/// by default, or `strftime` directives for time modes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "String", into = "String"))]
+pub struct Directive(String);
+
+static DIRECTIVE_PREFIX: sync::LazyLock<regex::Regex> =
+    sync::LazyLock::new(|| regex::Regex::new(r"^%[-+ 0#]*[0-9]*(\.[0-9]+)?l?[a-zA-Z]").unwrap());
+
+impl Directive {
+    /// Create a directive from `spec`, e.g. `"%6.2lf"`.
+    pub fn new(spec: impl Into<String>) -> Result<Self, InvalidArgument> {
+        let spec = spec.into();
+        match DIRECTIVE_PREFIX.find(&spec) {
+            Some(m) if m.end() == spec.len() => Ok(Self(spec)),
+            _ => Err(InvalidArgument("Invalid format directive")),
+        }
+    }
+
+    /// A floating point value, e.g. `%6.2lf`.
+    pub fn value(width: u8, precision: u8) -> Self {
+        Self(format!("%{width}.{precision}lf"))
+    }
+
+    /// The SI unit prefix for the preceding value, `%s`.
+    pub fn unit() -> Self {
+        Self("%s".to_string())
+    }
+}
+
+impl TryFrom<String> for Directive {
+    type Error = InvalidArgument;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<Directive> for String {
+    fn from(value: Directive) -> Self {
+        value.0
+    }
+}
+
+/// Escape `text` for a text field, appending `control` if set.
+///
+/// `librrd` reads a trailing backslash and control character as a control code, so without
+/// `control` the text can't end that way.
+fn escape_text(text: &str, control: Option<TextControl>) -> Result<String, InvalidArgument> {
+    match control {
+        // The control code follows any trailing backslash, so it can't escape the separator
+        Some(c) => Ok(format!("{}\\{}", text.replace(':', "\\:"), c.as_char())),
+        None => {
+            if let (_, Some(_)) = split_control(text) {
+                return Err(InvalidArgument(
+                    "Text can't end with a control code; use a TextControl",
+                ));
+            }
+            util::escape_field(text)
+        }
+    }
+}
+
+/// Split a trailing `\x` control code from `s`. `librrd` leaves a backslash followed by any
+/// other character as text.
+fn split_control(s: &str) -> (&str, Option<TextControl>) {
+    let mut chars = s.chars().rev();
+    match (chars.next(), chars.next()) {
+        (Some(c), Some('\\')) => match TextControl::from_char(c) {
+            Some(control) => (&s[..s.len() - 2], Some(control)),
+            None => (s, None),
+        },
+        _ => (s, None),
     }
 }
 
@@ -818,7 +1174,7 @@ mod tests {
         let mut args = vec![];
         Print {
             var_name: VarName::new("var".to_string()).unwrap(),
-            format: "fmt".into(),
+            format: "fmt".parse().unwrap(),
             format_mode: Some(PrintFormatMode::ValStrfTime),
         }
         .append_to(&mut args)
@@ -835,7 +1191,7 @@ mod tests {
         let mut args = vec![];
         GPrint {
             var_name: VarName::new("var".to_string()).unwrap(),
-            format: "fmt".into(),
+            format: "fmt".parse().unwrap(),
         }
         .append_to(&mut args)
         .unwrap();
@@ -850,7 +1206,7 @@ mod tests {
     fn comment() {
         let mut args = vec![];
         Comment {
-            text: "comment".into(),
+            text: Legend::new("comment"),
         }
         .append_to(&mut args)
         .unwrap();
@@ -867,7 +1223,7 @@ mod tests {
         VRule {
             value: Value::Variable(VarName::new("var").unwrap()),
             color: "#01020304".parse().unwrap(),
-            legend: Some("foo".to_string().into()),
+            legend: Some(Legend::new("foo")),
             dashes: Some(Dashes {
                 spacing: Some(DashSpacing::Simple(4)),
                 offset: Some(10),
@@ -911,7 +1267,7 @@ mod tests {
             value: VarName::new("var").unwrap(),
             color: Some(ColorWithLegend {
                 color: "#01020304".parse().unwrap(),
-                legend: Some("foo".to_string().into()),
+                legend: Some(Legend::new("foo")),
             }),
             stack: true,
             skip_scale: true,
@@ -996,4 +1352,129 @@ mod tests {
             args
         );
     }
+
+    #[test]
+    fn def_escapes_path() {
+        let def = |rrd: &str, ds_name: &str| {
+            let mut args = vec![];
+            Def {
+                var_name: VarName::new("var").unwrap(),
+                rrd: rrd.into(),
+                ds_name: ds_name.to_string(),
+                consolidation_fn: ConsolidationFn::Avg,
+                step: None,
+                start: None,
+                end: None,
+                reduce: None,
+            }
+            .append_to(&mut args)
+            .map(|_| args)
+        };
+
+        assert_eq!(
+            vec![r"DEF:var=/data/host\:8080/cpu.rrd:DS1:AVERAGE".to_string()],
+            def("/data/host:8080/cpu.rrd", "DS1").unwrap()
+        );
+        assert_eq!(
+            vec![r"DEF:var=C\:\data\cpu.rrd:DS1:AVERAGE".to_string()],
+            def(r"C:\data\cpu.rrd", "DS1").unwrap()
+        );
+        // would escape the separator
+        assert!(def(r"data\", "DS1").is_err());
+        // would inject fields
+        assert!(def("data.rrd", "DS1:MAX:step=1").is_err());
+        assert!(def("data.rrd", "").is_err());
+    }
+
+    #[test]
+    fn rpn_rejects_colons() {
+        let mut args = vec![];
+        assert!(CDef {
+            var_name: VarName::new("var").unwrap(),
+            rpn: "a,1,+:extra".to_string(),
+        }
+        .append_to(&mut args)
+        .is_err());
+        assert!(VDef {
+            var_name: VarName::new("var").unwrap(),
+            rpn: "a,AVERAGE:extra".to_string(),
+        }
+        .append_to(&mut args)
+        .is_err());
+        assert!(args.is_empty());
+    }
+
+    #[test]
+    fn legend_escaping() {
+        let arg = |legend: Legend| {
+            let mut s = String::new();
+            legend.append_to(&mut s).map(|_| s)
+        };
+
+        assert_eq!(r":12\:00\:00", arg(Legend::new("12:00:00")).unwrap());
+        assert_eq!(":100%", arg(Legend::new("100%")).unwrap());
+        // only a trailing backslash, or control code, is special
+        assert_eq!(r":a\lb", arg(Legend::new(r"a\lb")).unwrap());
+        assert_eq!(r":C\:\x", arg(Legend::new(r"C:\x")).unwrap());
+        assert_eq!(r":a\\:", arg(Legend::new(r"a\:")).unwrap());
+        assert_eq!(
+            r":Load\l",
+            arg(Legend::new("Load").with_control(TextControl::Left)).unwrap()
+        );
+        assert_eq!(
+            r":a\:\c",
+            arg(Legend::new("a:").with_control(TextControl::Center)).unwrap()
+        );
+        assert_eq!(
+            r":dir\\g",
+            arg(Legend::new(r"dir\").with_control(TextControl::Glue)).unwrap()
+        );
+
+        // literal text that librrd would read as a control code or an escaped separator
+        for text in [r"Load\l", r"Load\n", r"Load\", "\\"] {
+            assert!(arg(Legend::new(text)).is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn legend_parse() {
+        assert_eq!(Legend::new("a:b"), r"a\:b".parse().unwrap());
+        assert_eq!(
+            Legend::new("a:").with_control(TextControl::Right),
+            r"a\:\r".parse().unwrap()
+        );
+        assert_eq!(Legend::new("a:"), r"a\:".parse().unwrap());
+        assert_eq!(Legend::new(r"a\x"), r"a\x".parse().unwrap());
+    }
+
+    #[test]
+    fn print_format_escaping() {
+        let format = PrintFormat::default()
+            .text("50%: ")
+            .directive(Directive::value(5, 1))
+            .text("%s")
+            .control(TextControl::Justified);
+        assert_eq!(r"50%%\: %5.1lf%%s\j", format.to_arg_string().unwrap());
+        assert_eq!(format, r"50%%\: %5.1lf%%s\j".parse().unwrap());
+
+        let time = "%H:%M".parse::<PrintFormat>().unwrap();
+        assert_eq!(
+            [
+                FormatPart::Directive(Directive::new("%H").unwrap()),
+                FormatPart::Text(":".to_string()),
+                FormatPart::Directive(Directive::new("%M").unwrap()),
+            ],
+            time.parts()
+        );
+        assert_eq!(r"%H\:%M", time.to_arg_string().unwrap());
+
+        assert!(PrintFormat::default()
+            .text(r"trailing\")
+            .to_arg_string()
+            .is_err());
+        assert!("%".parse::<PrintFormat>().is_err());
+        assert!(Directive::new("%5.1lf extra").is_err());
+        assert!(Directive::new("%:").is_err());
+        assert!(Directive::new("text").is_err());
+    }
 }
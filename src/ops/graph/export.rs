@@ -0,0 +1,601 @@
+//! Parsing the data export formats of `rrd_graph`.
+//!
+//! See [`DataFormat`].
+
+use crate::{
+    data::Data,
+    error::{RrdError, RrdResult},
+    ops::graph::props::DataFormat,
+    Timestamp,
+};
+use nom::{branch, bytes, character::complete, combinator, multi, sequence, Finish, Parser};
+use rrd_sys::rrd_double;
+use std::{sync, time::Duration};
+
+/// The format to ask `librrd` for, to get data in `format`.
+///
+/// Separated values don't include the step, which can't be inferred from fewer than two rows, so
+/// they're requested as `JSONTIME` instead. The parsed data is the same either way.
+pub(crate) fn requested_format(format: DataFormat) -> DataFormat {
+    match format {
+        DataFormat::Csv | DataFormat::Tsv | DataFormat::Ssv => DataFormat::JsonTime,
+        other => other,
+    }
+}
+
+/// Parse the output of a graph rendered in `format`, which must be a
+/// [`requested_format`].
+pub(crate) fn parse(format: DataFormat, output: &[u8]) -> RrdResult<Data<Vec<rrd_double>>> {
+    // Legends are passed in as UTF-8, so they come back that way regardless of declared encoding
+    let text = String::from_utf8_lossy(output);
+    let export = match format {
+        DataFormat::Json | DataFormat::JsonTime => parse_json(&text)?,
+        DataFormat::Xml | DataFormat::XmlEnum => parse_xml(&text)?,
+        DataFormat::Csv | DataFormat::Tsv | DataFormat::Ssv => {
+            unreachable!("{format:?} is requested as JSONTIME")
+        }
+    };
+    export.into_data()
+}
+
+/// The contents of an export, before checking that they're consistent
+#[derive(Debug, Default)]
+struct Export {
+    legends: Vec<String>,
+    start: i64,
+    step: u64,
+    /// Each row's timestamp, for formats that include them, and values
+    rows: Vec<(Option<i64>, Vec<rrd_double>)>,
+}
+
+impl Export {
+    fn into_data(self) -> RrdResult<Data<Vec<rrd_double>>> {
+        if self.legends.is_empty() {
+            return Err(malformed("no columns"));
+        }
+        if self.rows.iter().any(|(_, v)| v.len() != self.legends.len()) {
+            return Err(malformed("row length doesn't match the number of columns"));
+        }
+
+        let start = self
+            .rows
+            .first()
+            .and_then(|(t, _)| *t)
+            .unwrap_or(self.start);
+        let step = self.step;
+        let evenly_spaced = self
+            .rows
+            .iter()
+            .zip(0_u64..)
+            .all(|((t, _), i)| t.is_none_or(|t| t.abs_diff(start) == i * step && t >= start));
+        if step == 0 || !evenly_spaced {
+            return Err(malformed("rows aren't evenly spaced"));
+        }
+
+        let start =
+            Timestamp::from_timestamp(start, 0).ok_or_else(|| malformed("invalid start time"))?;
+        Data::from_rows(
+            start,
+            Duration::from_secs(step),
+            self.legends,
+            self.rows.into_iter().flat_map(|(_, v)| v).collect(),
+        )
+    }
+}
+
+/// JSON, e.g.:
+///
+/// ```text
+/// { "about": "RRDtool graph JSON output",
+///   "meta": { "start": 1020611700, "step": 300, "end": 1020612000, "legend": [ "in" ] },
+///   "data": [ [ 3.4000000000e+00 ], [ null ] ]
+/// }
+/// ```
+///
+/// With `JSONTIME`, each row starts with its timestamp.
+fn parse_json(text: &str) -> RrdResult<Export> {
+    let (_rest, root) = combinator::all_consuming(json_value)
+        .parse_complete(text)
+        .finish()
+        .map_err(|_| malformed("invalid JSON"))?;
+    let meta = root.get("meta").ok_or_else(|| malformed("no meta"))?;
+    let meta_int = |key: &str| {
+        meta.get(key)
+            .and_then(Json::as_i64)
+            .ok_or_else(|| malformed(&format!("no {key}")))
+    };
+    let start = meta_int("start")?;
+    let step = u64::try_from(meta_int("step")?).map_err(|_| malformed("negative step"))?;
+    let legends = meta
+        .get("legend")
+        .and_then(Json::as_array)
+        .ok_or_else(|| malformed("no legend"))?
+        .iter()
+        .map(|l| l.as_str().map(str::to_string))
+        .collect::<Option<Vec<_>>>()
+        .ok_or_else(|| malformed("non-string legend"))?;
+
+    let rows = root
+        .get("data")
+        .and_then(Json::as_array)
+        .ok_or_else(|| malformed("no data"))?
+        .iter()
+        .map(|row| {
+            let row = row
+                .as_array()
+                .ok_or_else(|| malformed("row isn't an array"))?;
+            let (time, values) = match row.split_first() {
+                Some((time, values)) if row.len() == legends.len() + 1 => {
+                    let time = match time {
+                        Json::String(s) => parse_time(s)?,
+                        other => other.as_i64().ok_or_else(|| malformed("invalid time"))?,
+                    };
+                    (Some(time), values)
+                }
+                _ => (None, row),
+            };
+            let values = values
+                .iter()
+                .map(|v| match v {
+                    Json::Null => Ok(rrd_double::NAN),
+                    Json::Number(n) => Ok(*n),
+                    Json::String(s) => parse_value(s),
+                    _ => Err(malformed("invalid value")),
+                })
+                .collect::<RrdResult<_>>()?;
+            Ok((time, values))
+        })
+        .collect::<RrdResult<_>>()?;
+
+    Ok(Export {
+        legends,
+        start,
+        step,
+        rows,
+    })
+}
+
+/// The subset of JSON needed for exports
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    fn as_i64(&self) -> Option<i64> {
+        match self {
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 2_f64.powi(53) => Some(*n as i64),
+            _ => None,
+        }
+    }
+}
+
+/// A JSON value, with any surrounding whitespace.
+fn json_value(input: &str) -> nom::IResult<&str, Json> {
+    sequence::delimited(
+        complete::multispace0,
+        branch::alt((
+            combinator::map(json_string, Json::String),
+            combinator::map(
+                sequence::delimited(
+                    complete::char('['),
+                    multi::separated_list0(complete::char(','), json_value),
+                    sequence::preceded(complete::multispace0, complete::char(']')),
+                ),
+                Json::Array,
+            ),
+            combinator::map(
+                sequence::delimited(
+                    complete::char('{'),
+                    multi::separated_list0(
+                        complete::char(','),
+                        sequence::separated_pair(
+                            sequence::delimited(
+                                complete::multispace0,
+                                json_string,
+                                complete::multispace0,
+                            ),
+                            complete::char(':'),
+                            json_value,
+                        ),
+                    ),
+                    sequence::preceded(complete::multispace0, complete::char('}')),
+                ),
+                Json::Object,
+            ),
+            json_literal,
+        )),
+        complete::multispace0,
+    )
+    .parse_complete(input)
+}
+
+/// `null`, `true`, `false` or a number. `librrd` may write `nan` etc as bare numbers.
+fn json_literal(input: &str) -> nom::IResult<&str, Json> {
+    combinator::map_res(
+        bytes::complete::take_till1(|c: char| c.is_whitespace() || ",]}".contains(c)),
+        |token| match token {
+            "null" => Ok(Json::Null),
+            "true" => Ok(Json::Bool(true)),
+            "false" => Ok(Json::Bool(false)),
+            number => parse_value(number).map(Json::Number),
+        },
+    )
+    .parse_complete(input)
+}
+
+/// A `"`-quoted string with JSON escapes.
+fn json_string(input: &str) -> nom::IResult<&str, String> {
+    enum Fragment<'a> {
+        Literal(&'a str),
+        Escaped(char),
+    }
+
+    let escaped = branch::alt((
+        combinator::value('\n', complete::char('n')),
+        combinator::value('\r', complete::char('r')),
+        combinator::value('\t', complete::char('t')),
+        combinator::value('\u{8}', complete::char('b')),
+        combinator::value('\u{c}', complete::char('f')),
+        sequence::preceded(
+            complete::char('u'),
+            combinator::map_res(
+                bytes::complete::take_while_m_n(4, 4, |c: char| c.is_ascii_hexdigit()),
+                |hex| {
+                    // Surrogates can't be chars; rrdtool doesn't produce them
+                    u32::from_str_radix(hex, 16)
+                        .map(|code| char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER))
+                },
+            ),
+        ),
+        // `\"`, `\\`, `\/`, and anything unexpected, are taken literally
+        complete::anychar,
+    ));
+    let fragment = branch::alt((
+        combinator::map(bytes::complete::is_not("\"\\"), Fragment::Literal),
+        combinator::map(
+            sequence::preceded(complete::char('\\'), escaped),
+            Fragment::Escaped,
+        ),
+    ));
+    sequence::delimited(
+        complete::char('"'),
+        multi::fold_many0(fragment, String::new, |mut s, fragment| {
+            match fragment {
+                Fragment::Literal(literal) => s.push_str(literal),
+                Fragment::Escaped(c) => s.push(c),
+            }
+            s
+        }),
+        complete::char('"'),
+    )
+    .parse_complete(input)
+}
+
+/// XML, e.g.:
+///
+/// ```text
+/// <xport>
+///   <meta>
+///     <start>1020611700</start><step>300</step><end>1020612000</end>
+///     <legend><entry>in</entry></legend>
+///   </meta>
+///   <data>
+///     <row><t>1020611700</t><v>3.4000000000e+00</v></row>
+///   </data>
+/// </xport>
+/// ```
+///
+/// With `XMLENUM`, values are in `<v0>`, `<v1>`, etc.
+fn parse_xml(text: &str) -> RrdResult<Export> {
+    static ROW: sync::LazyLock<regex::Regex> =
+        sync::LazyLock::new(|| regex::Regex::new(r"(?s)<row>(.*?)</row>").unwrap());
+    static LEAF: sync::LazyLock<regex::Regex> = sync::LazyLock::new(|| {
+        regex::Regex::new(r"<([A-Za-z]+)[0-9]*>([^<]*)</[A-Za-z]+[0-9]*>").unwrap()
+    });
+
+    let meta = section(text, "meta").ok_or_else(|| malformed("no meta"))?;
+    let mut start = None;
+    let mut step = None;
+    let mut legends = vec![];
+    for leaf in LEAF.captures_iter(meta) {
+        let value = unescape_xml(&leaf[2]);
+        match &leaf[1] {
+            "start" => start = Some(parse_time(&value)?),
+            "step" => step = value.trim().parse::<u64>().ok(),
+            "entry" => legends.push(value),
+            _ => {}
+        }
+    }
+
+    let data = section(text, "data").ok_or_else(|| malformed("no data"))?;
+    let rows = ROW
+        .captures_iter(data)
+        .map(|row| {
+            let mut time = None;
+            let mut values = vec![];
+            for leaf in LEAF.captures_iter(&row[1]) {
+                match &leaf[1] {
+                    "t" => time = Some(parse_time(&leaf[2])?),
+                    "v" => values.push(parse_value(&leaf[2])?),
+                    _ => {}
+                }
+            }
+            Ok((time, values))
+        })
+        .collect::<RrdResult<_>>()?;
+
+    Ok(Export {
+        legends,
+        start: start.ok_or_else(|| malformed("no start"))?,
+        step: step.ok_or_else(|| malformed("no step"))?,
+        rows,
+    })
+}
+
+/// The contents of the first `<tag>` element in `text`.
+fn section<'a>(text: &'a str, tag: &str) -> Option<&'a str> {
+    let start = text.find(&format!("<{tag}>"))? + tag.len() + 2;
+    let len = text[start..].find(&format!("</{tag}>"))?;
+    Some(&text[start..start + len])
+}
+
+fn unescape_xml(s: &str) -> String {
+    let mut out = String::new();
+    let mut rest = s;
+    while let Some(i) = rest.find('&') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        let entity = rest.find(';').map(|end| (&rest[1..end], end));
+        let decoded = entity.and_then(|(name, end)| {
+            let c = match name {
+                "lt" => '<',
+                "gt" => '>',
+                "amp" => '&',
+                "quot" => '"',
+                "apos" => '\'',
+                _ => {
+                    let code = match name.strip_prefix("#x") {
+                        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                        None => name.strip_prefix('#')?.parse().ok(),
+                    }?;
+                    char::from_u32(code)?
+                }
+            };
+            Some((c, end))
+        });
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn parse_time(s: &str) -> RrdResult<i64> {
+    s.trim().parse().map_err(|_| {
+        // librrd formats times with the x-axis label format if one is set
+        malformed(&format!("unsupported timestamp {s:?}"))
+    })
+}
+
+/// Parse a value, mapping the ways `librrd` writes unknown values to `NaN`.
+fn parse_value(s: &str) -> RrdResult<rrd_double> {
+    let s = s.trim();
+    match s.to_ascii_lowercase().as_str() {
+        "nan" | "-nan" | "u" | "unkn" | "null" => Ok(rrd_double::NAN),
+        _ => s
+            .parse()
+            .map_err(|_| malformed(&format!("invalid value {s:?}"))),
+    }
+}
+
+fn malformed(detail: &str) -> RrdError {
+    RrdError::Internal(format!("Malformed graph export: {detail}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use itertools::Itertools;
+
+    const START: i64 = 1020611700;
+
+    /// Checks that `data` has columns `in` and `out "x"`, with rows every 300s from `START`.
+    fn assert_sample(data: Data<Vec<rrd_double>>) {
+        assert_eq!(Timestamp::from_timestamp(START, 0).unwrap(), data.start());
+        assert_eq!(Duration::from_secs(300), data.step());
+        assert_eq!(
+            &["in".to_string(), "out \"x\"".to_string()],
+            data.ds_names()
+        );
+        assert_eq!(
+            vec![Some(3.4), Some(1e6), None, Some(-2.0)],
+            data.values()
+                .iter()
+                .map(|v| (!v.is_nan()).then_some(*v))
+                .collect_vec()
+        );
+    }
+
+    #[test]
+    fn separated_values_are_requested_as_json() {
+        for format in [DataFormat::Csv, DataFormat::Tsv, DataFormat::Ssv] {
+            assert_eq!(DataFormat::JsonTime, requested_format(format));
+        }
+        assert_eq!(DataFormat::XmlEnum, requested_format(DataFormat::XmlEnum));
+    }
+
+    #[test]
+    fn json() {
+        let output = format!(
+            r#"{{ "about": "RRDtool graph JSON output",
+  "meta": {{
+    "start": {START},
+    "end": {},
+    "step": 300,
+    "legend": [
+      "in",
+      "out \"x\""
+    ],
+    "gprints": [ {{ "gprint": "3.40" }} ]
+  }},
+  "data": [
+    [ 3.4000000000e+00, 1.0000000000e+06 ],
+    [ null, -2.0000000000e+00 ]
+  ]
+}}
+"#,
+            START + 300
+        );
+        assert_sample(parse(DataFormat::Json, output.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn json_time() {
+        let output = format!(
+            r#"{{ "meta": {{ "start": {START}, "step": 300, "legend": [ "in", "out \"x\"" ] }},
+  "data": [
+    [ {START}, 3.4000000000e+00, 1.0000000000e+06 ],
+    [ {}, nan, -2.0000000000e+00 ]
+  ]
+}}"#,
+            START + 300
+        );
+        assert_sample(parse(DataFormat::JsonTime, output.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn xml() {
+        for (format, v0, v1) in [
+            (DataFormat::Xml, "v", "v"),
+            (DataFormat::XmlEnum, "v0", "v1"),
+        ] {
+            let output = format!(
+                r#"<?xml version="1.0" encoding="ISO-8859-1"?>
+
+<xport>
+  <meta>
+    <start>{START}</start>
+    <step>300</step>
+    <end>{}</end>
+    <rows>2</rows>
+    <columns>2</columns>
+    <legend>
+      <entry>in</entry>
+      <entry>out &quot;x&quot;</entry>
+    </legend>
+  </meta>
+  <data>
+    <row><t>{START}</t><{v0}>3.4000000000e+00</{v0}><{v1}>1.0000000000e+06</{v1}></row>
+    <row><t>{}</t><{v0}>NaN</{v0}><{v1}>-2.0000000000e+00</{v1}></row>
+  </data>
+</xport>
+"#,
+                START + 300,
+                START + 300
+            );
+            assert_sample(parse(format, output.as_bytes()).unwrap());
+        }
+    }
+
+    #[test]
+    fn malformed_exports_are_errors() {
+        for (format, output) in [
+            (DataFormat::Json, ""),
+            (DataFormat::Json, "{ \"meta\": { \"start\": 1 } }"),
+            (DataFormat::Json, "{ \"meta\": "),
+            (DataFormat::Json, "[ 1 ] [ 2 ]"),
+            (DataFormat::Json, "\"unterminated"),
+            (
+                DataFormat::JsonTime,
+                r#"{ "meta": { "start": 1, "step": 1, "legend": [ "in" ] },
+                     "data": [ [ 1, 1 ], [ 2, 1, 2 ] ] }"#,
+            ),
+            (
+                DataFormat::JsonTime,
+                r#"{ "meta": { "start": 1, "step": 1, "legend": [ "in" ] },
+                     "data": [ [ 1, 1 ], [ 3, 1 ] ] }"#,
+            ),
+            (
+                DataFormat::JsonTime,
+                r#"{ "meta": { "start": 1, "step": 1, "legend": [ "in" ] },
+                     "data": [ [ "12:00", 1 ] ] }"#,
+            ),
+            (DataFormat::Xml, "<xport></xport>"),
+        ] {
+            assert!(
+                matches!(parse(format, output.as_bytes()), Err(RrdError::Internal(_))),
+                "{format:?} {output:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn single_and_empty_rows() {
+        let output = format!(
+            r#"{{ "meta": {{ "start": {START}, "step": 300, "legend": [ "in" ] }},
+  "data": [ [ {START}, 1.5 ] ] }}"#
+        );
+        let data = parse(DataFormat::JsonTime, output.as_bytes()).unwrap();
+        assert_eq!(Duration::from_secs(300), data.step());
+        assert_eq!(&[1.5], data.values());
+
+        let output = format!(
+            r#"{{ "meta": {{ "start": {START}, "step": 300, "legend": [ "in" ] }}, "data": [] }}"#
+        );
+        let data = parse(DataFormat::JsonTime, output.as_bytes()).unwrap();
+        assert_eq!(Timestamp::from_timestamp(START, 0).unwrap(), data.start());
+        assert_eq!(0, data.row_count());
+    }
+
+    #[test]
+    fn json_strings() {
+        assert_eq!(
+            Ok(("", "a \"b\" \\ / é\t".to_string())),
+            json_string(r#""a \"b\" \\ \/ \u00e9\t""#)
+        );
+        assert_eq!(Ok(("", Json::Array(vec![]))), json_value(" [ ] "));
+    }
+
+    #[test]
+    fn xml_entities() {
+        assert_eq!(
+            "<a & b> \"c\" 'd' é &bogus",
+            unescape_xml("&lt;a &amp; b&gt; &quot;c&quot; &apos;d&apos; &#233; &bogus")
+        );
+    }
+}
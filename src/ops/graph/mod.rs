@@ -2,40 +2,182 @@
 //!
 //! There are many options for graphs. See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph.en.html> and
 //! <https://oss.oetiker.ch/rrdtool/tut/rrdtutorial.en.html> for more detail.
+pub mod dashboard;
 pub mod elements;
+mod export;
+pub mod parse;
+pub mod policy;
 pub mod props;
+mod quick;
+pub mod template;
+pub mod theme;
+
+pub use quick::{quick_graph, quick_graph_with_theme};
 
 use crate::error::InvalidArgument;
 use crate::{
-    error::{get_rrd_error, RrdError, RrdResult},
+    context,
+    data::Data,
+    error::{get_rrd_error, Operation, RrdError, RrdResult},
     ops::{
         graph::{
             elements::GraphElement,
-            props::{GraphProps, ImageFormat},
+            props::{DataFormat, GraphProps, ImageFormat},
         },
         info::{self, InfoValue},
+        version::{self, Capabilities},
     },
-    util::ArrayOfStrings,
+    util::{self, path_to_str, ArrayOfStrings},
     Timestamp,
 };
+use itertools::Itertools;
 use log::debug;
 use nom::Parser;
 use nom::{bytes, character::complete, combinator, sequence, Finish};
-use std::{collections, ffi::CString, fmt::Write as _};
+use rrd_sys::rrd_double;
+use std::{
+    collections,
+    ffi::CString,
+    fmt,
+    fmt::Write as _,
+    fs,
+    path::{Path, PathBuf},
+};
 
 /// Returns a tuple containing the graph image data in the specified format and metadata about the
 /// graph.
 ///
+/// To get the graph's data rather than an image, see [`graph_data`].
+///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph.en.html> or `/tests/tutorial.rs`.
 pub fn graph(
     image_format: ImageFormat,
     props: GraphProps,
     elements: &[GraphElement],
 ) -> RrdResult<(Vec<u8>, GraphMetadata)> {
+    check_gradient_support(elements)?;
+
+    let mut info = graph_v("-", graph_args(Some(image_format), props, elements)?)?;
+
+    // pull out image first so debug output isn't massive
+    let image = extract_info_value(&mut info, "image", |v| v.into_blob())?;
+
+    debug!("Graph output: {info:?}");
+
+    Ok((image, extract_metadata(info, elements)?))
+}
+
+/// Renders the graph to the file at `path`, rather than returning the image.
+///
+/// If `lazy` is true, `librrd` only regenerates the image if it's missing, or older than the time
+/// represented by one pixel of the graph. This is `rrdtool graph --lazy`, and is useful for
+/// periodically refreshing many images, most of which won't have changed. Whether the file was
+/// regenerated is found by comparing its modification time before and after.
+///
+/// Returns an error if `path` is `-`, which `librrd` treats as stdout; use [`graph`] to get the
+/// image in memory. Also returns an error if the graph has nothing to draw, e.g. only `PRINT`s,
+/// since `librrd` then doesn't write the file.
+///
+/// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph.en.html>.
+pub fn graph_to_file(
+    path: &Path,
+    image_format: ImageFormat,
+    props: GraphProps,
+    elements: &[GraphElement],
+    lazy: bool,
+) -> RrdResult<GraphFileOutcome> {
+    let filename = path_to_str(path)?;
+    if filename == "-" {
+        return Err(RrdError::InvalidArgument(
+            "Graph file can't be `-`".to_string(),
+        ));
+    }
+    check_gradient_support(elements)?;
+
+    let mut args = Vec::new();
+    if lazy {
+        args.push("--lazy".to_string());
+    }
+    args.extend(graph_args(Some(image_format), props, elements)?);
+    let modified = || fs::metadata(path).and_then(|m| m.modified()).ok();
+    let modified_before = modified();
+    let info = graph_v(filename, args)?;
+
+    debug!("Graph output: {info:?}");
+
+    let unchanged = lazy && modified_before.is_some() && modified() == modified_before;
+    file_outcome(info, elements, unchanged)
+}
+
+/// The outcome of [`graph_to_file`], from the graph's info and whether the file was left as it
+/// was.
+fn file_outcome(
+    mut info: collections::HashMap<String, InfoValue>,
+    elements: &[GraphElement],
+    unchanged: bool,
+) -> RrdResult<GraphFileOutcome> {
+    // librrd stops before laying out the graph, after computing the prints, if it's lazy and the
+    // file is recent enough, or if there's nothing to draw
+    if info.contains_key("graph_width") {
+        Ok(GraphFileOutcome::Regenerated(extract_metadata(
+            info, elements,
+        )?))
+    } else if unchanged {
+        Ok(GraphFileOutcome::Unchanged(extract_prints(
+            &mut info, elements,
+        )?))
+    } else {
+        Err(RrdError::InvalidArgument(
+            "Graph has nothing to draw, so no file was written".to_string(),
+        ))
+    }
+}
+
+/// Whether [`graph_to_file`] wrote the image.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GraphFileOutcome {
+    /// The image was rendered and written to the file
+    Regenerated(GraphMetadata),
+    /// The file was recent enough, so it was left as it was. `librrd` still computes the output
+    /// of each [`elements::Print`].
+    Unchanged(Vec<PrintEntry>),
+}
+
+/// Returns the graph's data, in the columns that would be exported in `format`.
+///
+/// The data is what the graph would plot, after `CDEF` and `VDEF` processing, with a column
+/// for each [`elements::Line`], [`elements::Area`] and [`elements::Tick`], named after its legend.
+/// `format` only affects how `librrd` passes the data back, since it's parsed into [`Data`]. CSV,
+/// TSV and SSV don't include the step, so they're requested as `JSONTIME` instead.
+///
+/// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph.en.html>.
+pub fn graph_data(
+    format: DataFormat,
+    props: GraphProps,
+    elements: &[GraphElement],
+) -> RrdResult<Data<Vec<rrd_double>>> {
+    version::require("Graph data formats", Capabilities::GRAPH_DATA_FORMATS)?;
+
+    let format = export::requested_format(format);
+    let mut args = Vec::new();
+    format.append_to(&mut args)?;
+    args.extend(graph_args(None, props, elements)?);
+    let mut info = graph_v("-", args)?;
+    let output = extract_info_value(&mut info, "image", |v| v.into_blob())?;
+    export::parse(format, &output)
+}
+
+/// Call `rrd_graph_v` with `filename` and `args`, returning its info.
+fn graph_v(
+    filename: &str,
+    args: Vec<String>,
+) -> RrdResult<collections::HashMap<String, InfoValue>> {
     // Need to include initial "graphv" command since that's how `rrdtool` invokes rrd_graph_v.
     // Filename `-` means include image data in the return hash rather than writing to a file
-    let mut args = vec!["graphv".to_string(), "-".to_string()];
-    args.extend(graph_args(Some(image_format), props, elements)?);
+    let args = ["graphv".to_string(), filename.to_string()]
+        .into_iter()
+        .chain(args)
+        .collect::<Vec<_>>();
 
     debug!("Graph: args={args:?}");
     let args = args
@@ -43,65 +185,33 @@ pub fn graph(
         .map(CString::new)
         .collect::<Result<ArrayOfStrings, _>>()?;
 
-    let info_ptr = unsafe {
-        rrd_sys::rrd_graph_v(
-            args.len().try_into().expect("Implausibly huge argc"),
-            // different librrd versions differ in mutability of this pointer
-            args.as_ptr() as _,
-        )
-    };
-    if info_ptr.is_null() {
-        return Err(get_rrd_error().unwrap_or_else(|| {
-            RrdError::Internal("No graph data produced, but no librrd error".to_string())
-        }));
-    }
-
-    let mut info = info::build_info_map(info_ptr);
-
-    // pull out image first so debug output isn't massive
-    let image = extract_info_value(&mut info, "image", |v| v.into_blob())?;
-
-    debug!("Graph output: {info:?}");
-
-    let graph_left = extract_info_value(&mut info, "graph_left", |v| v.into_count())?;
-    let graph_top = extract_info_value(&mut info, "graph_top", |v| v.into_count())?;
-    let graph_width = extract_info_value(&mut info, "graph_width", |v| v.into_count())?;
-    let graph_height = extract_info_value(&mut info, "graph_height", |v| v.into_count())?;
-    let image_width = extract_info_value(&mut info, "image_width", |v| v.into_count())?;
-    let image_height = extract_info_value(&mut info, "image_height", |v| v.into_count())?;
-    let graph_start =
-        extract_info_value(&mut info, "graph_start", |v| v.into_count()).map(|t| {
-            Timestamp::from_timestamp(t.try_into().expect("Graph start overflow"), 0)
-                .expect("Impossible graph start")
-        })?;
-    let graph_end = extract_info_value(&mut info, "graph_end", |v| v.into_count()).map(|t| {
-        Timestamp::from_timestamp(t.try_into().expect("Graph end overflow"), 0)
-            .expect("Impossible graph end")
+    let argc = args
+        .len()
+        .try_into()
+        .map_err(|_| RrdError::InvalidArgument("Too many graph args".to_string()))?;
+    let info_ptr = context::non_reentrant("rrd_graph_v", || {
+        let info_ptr = unsafe {
+            rrd_sys::rrd_graph_v(
+                argc,
+                // different librrd versions differ in mutability of this pointer
+                args.as_ptr() as _,
+            )
+        };
+        if info_ptr.is_null() {
+            return Err(get_rrd_error(Operation::Graph, None).unwrap_or_else(|| {
+                RrdError::Internal("No graph data produced, but no librrd error".to_string())
+            }));
+        }
+        Ok(info_ptr)
     })?;
-    let value_min = extract_info_value(&mut info, "value_min", |v| v.into_value())?;
-    let value_max = extract_info_value(&mut info, "value_max", |v| v.into_value())?;
 
-    Ok((
-        image,
-        GraphMetadata {
-            graph_left,
-            graph_top,
-            graph_width,
-            graph_height,
-            graph_start,
-            graph_end,
-            image_width,
-            image_height,
-            value_min,
-            value_max,
-            extra_info: info,
-        },
-    ))
+    info::build_info_map(info_ptr)
 }
 
 /// Returns a vector of graph arguments.
 ///
-/// Use this function to build command line or CGI template for a `RRD::GRAPH` tag.
+/// Use this function to build command line or CGI template for a `RRD::GRAPH` tag. Templates can
+/// be processed with [`template::TemplateProcessor`].
 ///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdcgi.en.html>.
 pub fn graph_args(
@@ -140,6 +250,77 @@ pub fn graph_args(
     Ok(args)
 }
 
+/// The inputs to [`graph`], for building the equivalent `rrdtool graph` command line, e.g. to
+/// reproduce a graph with the CLI.
+///
+/// Since [`graph`] returns the image rather than writing it to a file, the command writes it to
+/// `output`.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::graph::{elements, props, GraphCommand};
+/// use rrd::ConsolidationFn;
+///
+/// let value = elements::VarName::new("v").unwrap();
+/// let command = GraphCommand {
+///     output: "out.png".into(),
+///     image_format: Some(props::ImageFormat::Png),
+///     props: props::GraphProps::default(),
+///     elements: vec![
+///         elements::Def {
+///             var_name: value.clone(),
+///             rrd: "data.rrd".into(),
+///             ds_name: "speed".to_string(),
+///             consolidation_fn: ConsolidationFn::Avg,
+///             step: None,
+///             start: None,
+///             end: None,
+///             reduce: None,
+///         }
+///         .into(),
+///         elements::Line {
+///             width: 1.0,
+///             value,
+///             color: None,
+///             stack: false,
+///             skip_scale: false,
+///             dashes: None,
+///         }
+///         .into(),
+///     ],
+/// };
+/// println!("{}", command.to_rrdtool_command().unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub struct GraphCommand {
+    pub output: PathBuf,
+    pub image_format: Option<ImageFormat>,
+    pub props: GraphProps,
+    pub elements: Vec<GraphElement>,
+}
+
+impl GraphCommand {
+    /// Returns the `rrdtool` arguments, starting with `graph`.
+    ///
+    /// See [`graph_args`] for the arguments without `graph` and the output file.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec!["graph".to_string(), path_to_str(&self.output)?.to_string()];
+        args.extend(graph_args(
+            self.image_format,
+            self.props.clone(),
+            &self.elements,
+        )?);
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool graph` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
+}
+
 /// Metadata about a rendered graph.
 ///
 /// See [`graph`].
@@ -165,12 +346,49 @@ pub struct GraphMetadata {
     pub value_min: f64,
     /// Max value in the graph
     pub value_max: f64,
+    /// The output of each [`elements::Print`], in order
+    pub prints: Vec<PrintEntry>,
+    /// The items in the graph's legend, in order
+    pub legends: Vec<LegendEntry>,
     /// Additional data returned from `rrd_graph_v`.
     ///
     /// Contents depend on the commands given.
     pub extra_info: collections::HashMap<String, InfoValue>,
 }
 
+/// The output of a [`elements::Print`].
+///
+/// See [`GraphMetadata`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PrintEntry {
+    /// The index of the `Print` in the elements passed to [`graph`]
+    pub element_index: usize,
+    /// The formatted text
+    pub text: String,
+}
+
+/// An item in the graph's legend, drawn for an element with a legend, e.g. a [`elements::Line`].
+///
+/// The position covers the item's color box and text, in pixels from the top left of the image,
+/// e.g. for click targets.
+///
+/// See [`GraphMetadata`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LegendEntry {
+    /// The index of the element in the elements passed to [`graph`]
+    pub element_index: usize,
+    /// The legend text
+    pub text: String,
+    /// Offset in pixels from the left edge of the image
+    pub x: u32,
+    /// Offset in pixels from the top edge of the image
+    pub y: u32,
+    /// Width in pixels
+    pub width: u32,
+    /// Height in pixels
+    pub height: u32,
+}
+
 /// RGB(A) color.
 ///
 /// # Examples
@@ -206,15 +424,38 @@ pub struct Color {
 impl Color {
     /// Appends `#hex`.
     fn append_to(&self, s: &mut String) {
+        write!(s, "{self}").unwrap()
+    }
+}
+
+/// Formats as `#RRGGBB` or `#RRGGBBAA`, the same format accepted by `parse()`.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.alpha {
-            None => write!(s, "#{:02X}{:02X}{:02X}", self.red, self.green, self.blue,),
+            None => write!(f, "#{:02X}{:02X}{:02X}", self.red, self.green, self.blue,),
             Some(alpha) => write!(
-                s,
+                f,
                 "#{:02X}{:02X}{:02X}{:02X}",
                 self.red, self.green, self.blue, alpha
             ),
         }
-        .unwrap()
+    }
+}
+
+/// Serialized as a hex string.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Color {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from a hex string.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Color {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = <std::borrow::Cow<'de, str>>::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
     }
 }
 
@@ -247,13 +488,71 @@ impl std::str::FromStr for Color {
 }
 
 /// Incrementally build up the args to use in a graph invocation.
-trait AppendArgs {
+pub(crate) trait AppendArgs {
     /// Append suitable args to the args buffer.
     ///
     /// Returns Result to allow users to specify a PathBuf which may later fail conversion.
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()>;
 }
 
+/// Gradient areas need a newer `librrd` than other elements.
+fn check_gradient_support(elements: &[GraphElement]) -> RrdResult<()> {
+    if elements.iter().any(|e| {
+        matches!(
+            e,
+            GraphElement::Area(elements::Area {
+                color: Some(elements::ColorWithLegend {
+                    color: elements::AreaColor::Gradient { .. },
+                    ..
+                }),
+                ..
+            })
+        )
+    }) {
+        version::require("Gradient areas", Capabilities::GRADIENT_AREAS)?;
+    }
+    Ok(())
+}
+
+/// Build the metadata for a rendered graph from its info.
+fn extract_metadata(
+    mut info: collections::HashMap<String, InfoValue>,
+    elements: &[GraphElement],
+) -> RrdResult<GraphMetadata> {
+    let graph_left = extract_info_value(&mut info, "graph_left", |v| v.into_count())?;
+    let graph_top = extract_info_value(&mut info, "graph_top", |v| v.into_count())?;
+    let graph_width = extract_info_value(&mut info, "graph_width", |v| v.into_count())?;
+    let graph_height = extract_info_value(&mut info, "graph_height", |v| v.into_count())?;
+    let image_width = extract_info_value(&mut info, "image_width", |v| v.into_count())?;
+    let image_height = extract_info_value(&mut info, "image_height", |v| v.into_count())?;
+    let graph_start = extract_info_value(&mut info, "graph_start", |v| {
+        v.into_count().and_then(count_to_timestamp)
+    })?;
+    let graph_end = extract_info_value(&mut info, "graph_end", |v| {
+        v.into_count().and_then(count_to_timestamp)
+    })?;
+    let value_min = extract_info_value(&mut info, "value_min", |v| v.into_value())?;
+    let value_max = extract_info_value(&mut info, "value_max", |v| v.into_value())?;
+    let prints = extract_prints(&mut info, elements)?;
+    let legends = extract_legends(&mut info, elements)?;
+
+    Ok(GraphMetadata {
+        graph_left,
+        graph_top,
+        graph_width,
+        graph_height,
+        graph_start,
+        graph_end,
+        image_width,
+        image_height,
+        value_min,
+        value_max,
+        prints,
+        legends,
+        extra_info: info,
+    })
+}
+
 fn extract_info_value<T>(
     info: &mut collections::HashMap<String, InfoValue>,
     key: &str,
@@ -266,6 +565,85 @@ fn extract_info_value<T>(
         .ok_or_else(|| RrdError::Internal(format!("Graph info: unexpected {key} value type")))
 }
 
+/// Remove the `print[N]` entries from `info`, which are numbered by `Print` element.
+fn extract_prints(
+    info: &mut collections::HashMap<String, InfoValue>,
+    elements: &[GraphElement],
+) -> RrdResult<Vec<PrintEntry>> {
+    elements
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| matches!(e, GraphElement::Print(_)))
+        .enumerate()
+        .map(|(n, (element_index, _))| {
+            let text = extract_info_value(info, &format!("print[{n}]"), |v| v.into_string())?;
+            Ok(PrintEntry {
+                element_index,
+                text,
+            })
+        })
+        .collect()
+}
+
+/// Remove the `legend[N]` and `coords[N]` entries from `info`, which are numbered by legend item.
+///
+/// There are none if the legend isn't drawn, e.g. with `no_legend`.
+fn extract_legends(
+    info: &mut collections::HashMap<String, InfoValue>,
+    elements: &[GraphElement],
+) -> RrdResult<Vec<LegendEntry>> {
+    let mut legends = vec![];
+    let legend_elements = elements
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| has_legend_item(e))
+        .map(|(i, _)| i);
+    for (n, element_index) in legend_elements.enumerate() {
+        let key = format!("legend[{n}]");
+        if !info.contains_key(&key) {
+            break;
+        }
+        let text = extract_info_value(info, &key, |v| v.into_string())?;
+        let coords_key = format!("coords[{n}]");
+        let (x0, y0, x1, y1) = extract_info_value(info, &coords_key, |v| {
+            v.into_string()?
+                .split(',')
+                .map(|c| c.trim().parse::<u32>().ok())
+                .collect::<Option<Vec<_>>>()?
+                .into_iter()
+                .collect_tuple()
+        })?;
+        legends.push(LegendEntry {
+            element_index,
+            // librrd leaves room for the color box with leading spaces
+            text: text.strip_prefix("  ").map(str::to_string).unwrap_or(text),
+            x: x0,
+            y: y0,
+            width: x1.saturating_sub(x0),
+            height: y1.saturating_sub(y0),
+        });
+    }
+    Ok(legends)
+}
+
+/// Whether `librrd` draws a legend item (a color box and text) for `element`.
+fn has_legend_item(element: &GraphElement) -> bool {
+    let legend = match element {
+        GraphElement::HRule(e) => e.legend.as_ref(),
+        GraphElement::VRule(e) => e.legend.as_ref(),
+        GraphElement::Tick(e) => e.legend.as_ref(),
+        GraphElement::Line(e) => e.color.as_ref().and_then(|c| c.legend.as_ref()),
+        GraphElement::Area(e) => e.color.as_ref().and_then(|c| c.legend.as_ref()),
+        _ => None,
+    };
+    legend.is_some_and(|l| !l.text().is_empty())
+}
+
+/// Returns `None` if `t` isn't a plausible time
+fn count_to_timestamp(t: u64) -> Option<Timestamp> {
+    Timestamp::from_timestamp(t.try_into().ok()?, 0)
+}
+
 fn parse_hex_byte(input: &str) -> nom::IResult<&str, u8> {
     combinator::map_opt(
         sequence::pair(complete::anychar, complete::anychar),
@@ -283,6 +661,16 @@ fn parse_hex_byte(input: &str) -> nom::IResult<&str, u8> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn implausible_graph_times_are_rejected() {
+        assert_eq!(
+            Timestamp::from_timestamp(920804400, 0),
+            count_to_timestamp(920804400)
+        );
+        assert_eq!(None, count_to_timestamp(u64::MAX));
+        assert_eq!(None, count_to_timestamp(i64::MAX as u64));
+    }
+
     #[test]
     fn parse_color_no_alpha() {
         assert_eq!(
@@ -329,6 +717,76 @@ mod tests {
         assert!("#FFFFFFFFF".parse::<Color>().is_err());
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_color_as_hex() {
+        let color: Color = "#01234567".parse().unwrap();
+        let json = serde_json::to_value(color).unwrap();
+        assert_eq!(serde_json::json!("#01234567"), json);
+        assert_eq!(color, serde_json::from_value(json).unwrap());
+        assert!(serde_json::from_value::<Color>(serde_json::json!("#0123")).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_graph_definition_round_trip() {
+        let props = props::GraphProps {
+            labels: props::Labels {
+                title: Some("title".to_string()),
+                ..Default::default()
+            },
+            misc: props::Misc {
+                colors: [(props::ColorTag::Back, "#000000".parse().unwrap())]
+                    .into_iter()
+                    .collect(),
+                zoom: Some(props::Zoom::new(2.0).unwrap()),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let elements: Vec<elements::GraphElement> = vec![
+            elements::Def {
+                var_name: "v".try_into().unwrap(),
+                rrd: "data.rrd".into(),
+                ds_name: "ds".to_string(),
+                consolidation_fn: crate::ConsolidationFn::Max,
+                step: None,
+                start: Some(Timestamp::from_timestamp(1_000, 0).unwrap()),
+                end: None,
+                reduce: None,
+            }
+            .into(),
+            elements::Line {
+                width: 1.5,
+                value: "v".try_into().unwrap(),
+                color: Some(elements::ColorWithLegend {
+                    color: "#FF0000".parse().unwrap(),
+                    legend: Some(elements::Legend::new("legend")),
+                }),
+                stack: false,
+                skip_scale: false,
+                dashes: Some(elements::Dashes::default()),
+            }
+            .into(),
+        ];
+
+        let json = serde_json::to_string(&(&props, &elements)).unwrap();
+        let (props2, elements2): (props::GraphProps, Vec<elements::GraphElement>) =
+            serde_json::from_str(&json).unwrap();
+        assert_eq!(props, props2);
+        assert_eq!(elements, elements2);
+
+        // omitted props use defaults
+        let partial: props::GraphProps =
+            serde_json::from_str(r#"{"size": {"width": 100}}"#).unwrap();
+        assert_eq!(Some(100), partial.size.width);
+        assert_eq!(props::Labels::default(), partial.labels);
+
+        // validation still applies
+        assert!(serde_json::from_str::<elements::VarName>(r#""not valid!""#).is_err());
+        assert!(serde_json::from_str::<props::Zoom>("-1.0").is_err());
+    }
+
     #[test]
     fn cgi_template() {
         let var_name: elements::VarName = "myspeed".try_into().unwrap();
@@ -376,4 +834,144 @@ mod tests {
         ];
         assert_eq!(args, expected_args);
     }
+
+    #[test]
+    fn graph_to_file_rejects_stdout() {
+        let result = graph_to_file(
+            Path::new("-"),
+            ImageFormat::Png,
+            GraphProps::default(),
+            &[],
+            false,
+        );
+        assert!(
+            matches!(result, Err(RrdError::InvalidArgument(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn file_outcome_without_a_graph() {
+        let v = elements::VarName::new("v").unwrap();
+        let elements: [GraphElement; 1] = [elements::Print {
+            var_name: v,
+            format: "%1.0lf".parse().unwrap(),
+            format_mode: None,
+        }
+        .into()];
+        let info = || {
+            collections::HashMap::from([(
+                "print[0]".to_string(),
+                InfoValue::String("1".to_string()),
+            )])
+        };
+
+        assert_eq!(
+            GraphFileOutcome::Unchanged(vec![PrintEntry {
+                element_index: 0,
+                text: "1".to_string()
+            }]),
+            file_outcome(info(), &elements, true).unwrap()
+        );
+        // the file wasn't there, or was written without laying out a graph
+        let result = file_outcome(info(), &elements, false);
+        assert!(
+            matches!(result, Err(RrdError::InvalidArgument(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn prints_and_legends_are_linked_to_elements() {
+        let v = elements::VarName::new("v").unwrap();
+        let print = |format: &str| -> GraphElement {
+            elements::Print {
+                var_name: v.clone(),
+                format: format.parse().unwrap(),
+                format_mode: None,
+            }
+            .into()
+        };
+        let line = |legend: Option<&str>| -> GraphElement {
+            elements::Line {
+                width: 1.0,
+                value: v.clone(),
+                color: Some(elements::ColorWithLegend {
+                    color: "#FF0000".parse().unwrap(),
+                    legend: legend.map(elements::Legend::new),
+                }),
+                stack: false,
+                skip_scale: false,
+                dashes: None,
+            }
+            .into()
+        };
+        let elements = [
+            print("%1.0lf"),
+            line(None),
+            elements::Comment {
+                text: elements::Legend::new("comment"),
+            }
+            .into(),
+            line(Some("first")),
+            print("%2.0lf"),
+            line(Some("second")),
+        ];
+        let mut info = [
+            ("print[0]", "1"),
+            ("print[1]", "22"),
+            ("legend[0]", "  first"),
+            ("coords[0]", "10,100,60,114"),
+            ("legend[1]", "  second"),
+            ("coords[1]", "70,100,130,114"),
+            ("other", "kept"),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), InfoValue::String(v.to_string())))
+        .collect();
+
+        assert_eq!(
+            vec![
+                PrintEntry {
+                    element_index: 0,
+                    text: "1".to_string()
+                },
+                PrintEntry {
+                    element_index: 4,
+                    text: "22".to_string()
+                },
+            ],
+            extract_prints(&mut info, &elements).unwrap()
+        );
+        assert_eq!(
+            vec![
+                LegendEntry {
+                    element_index: 3,
+                    text: "first".to_string(),
+                    x: 10,
+                    y: 100,
+                    width: 50,
+                    height: 14,
+                },
+                LegendEntry {
+                    element_index: 5,
+                    text: "second".to_string(),
+                    x: 70,
+                    y: 100,
+                    width: 60,
+                    height: 14,
+                },
+            ],
+            extract_legends(&mut info, &elements).unwrap()
+        );
+        assert_eq!(vec!["other"], info.keys().collect_vec());
+
+        // no legend drawn
+        assert_eq!(
+            Vec::<LegendEntry>::new(),
+            extract_legends(&mut collections::HashMap::new(), &elements).unwrap()
+        );
+        // but prints are always produced
+        assert!(extract_prints(&mut collections::HashMap::new(), &elements).is_err());
+    }
 }
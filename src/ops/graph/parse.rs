@@ -0,0 +1,1866 @@
+//! Parse `rrdtool graph` args back into typed [`GraphProps`] and [`GraphElement`]s.
+//!
+//! This is the inverse of [`graph_args`](super::graph_args), for importing graphs defined as
+//! `rrdtool` command lines, e.g. from scripts or CGI templates. Anything that can't be represented
+//! by this crate's types, such as AT-style times or options the crate doesn't support, is reported
+//! as [`ParseError::Unsupported`] with the position of the arg rather than being dropped.
+//!
+//! # Examples
+//!
+//! ```
+//! use rrd::ops::graph::{elements::GraphElement, parse};
+//!
+//! let parsed = parse::parse_graph_args(&[
+//!     "--title",
+//!     "Temperature",
+//!     "DEF:t=temp.rrd:temp:AVERAGE",
+//!     "LINE2:t#FF0000:Outside",
+//! ])
+//! .unwrap();
+//! assert_eq!(Some("Temperature"), parsed.props.labels.title.as_deref());
+//! assert!(matches!(parsed.elements[1], GraphElement::Line(_)));
+//!
+//! let error = parse::parse_graph_args(&["--start", "now-1d"]).unwrap_err();
+//! assert!(matches!(error, parse::ParseError::Unsupported { index: 1, .. }));
+//! ```
+
+use crate::{
+    error::{InvalidArgument, RrdError},
+    ops::graph::{
+        elements::{
+            Area, AreaColor, CDef, ColorWithLegend, Comment, DashSpacing, Dashes, Def, GPrint,
+            GraphElement, HRule, Legend, Line, Offset, Print, PrintFormatMode, Shift, TextAlign,
+            Tick, VDef, VRule, Value, VarName,
+        },
+        props::{
+            AltAutoscale, AxisGridTimeUnit, ColorTag, FontParams, FontRenderMode, FontTag,
+            GraphProps, GraphRenderMode, ImageFormat, LegendDirection, LegendPosition, RightYAxis,
+            Units, UnitsExponent, XAxisGrid, YAxisFormatter, YAxisGrid, Zoom,
+        },
+        Color, GraphCommand,
+    },
+    util, ConsolidationFn, Timestamp,
+};
+use std::{path::PathBuf, str::FromStr};
+
+/// Graph inputs parsed by [`parse_graph_args`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedGraphArgs {
+    /// From `--imgformat`, if present
+    pub image_format: Option<ImageFormat>,
+    #[allow(missing_docs)]
+    pub props: GraphProps,
+    #[allow(missing_docs)]
+    pub elements: Vec<GraphElement>,
+}
+
+/// Args could not be parsed by [`parse_graph_args`].
+///
+/// `index` is the position of the offending arg in the args that were parsed.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseError {
+    /// A command line couldn't be split into args
+    #[error("Invalid command line: {0}")]
+    CommandLine(String),
+    /// A valid `rrdtool` arg that can't be represented by this crate's types
+    #[error("Unsupported arg {index} `{arg}`: {reason}")]
+    #[allow(missing_docs)]
+    Unsupported {
+        index: usize,
+        arg: String,
+        reason: String,
+    },
+    /// An arg that isn't valid `rrdtool` syntax
+    #[error("Invalid arg {index} `{arg}`: {reason}")]
+    #[allow(missing_docs)]
+    Invalid {
+        index: usize,
+        arg: String,
+        reason: String,
+    },
+}
+
+impl From<ParseError> for RrdError {
+    fn from(value: ParseError) -> Self {
+        RrdError::InvalidArgument(value.to_string())
+    }
+}
+
+/// Parse `rrdtool graph` options and elements, as produced by [`graph_args`](super::graph_args).
+///
+/// `args` doesn't include `graph` or the output file; see [`GraphCommand::from_args`] for a full
+/// command. Options may be long (`--width 400` or `--width=400`) or short (`-w 400` or `-w400`),
+/// and appear anywhere among the elements. Times must be Unix timestamps.
+pub fn parse_graph_args<S: AsRef<str>>(args: &[S]) -> Result<ParsedGraphArgs, ParseError> {
+    let args = args.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+    let (positional, mut state) = parse_options(&args, 0)?;
+    for (index, arg) in positional {
+        state.push_element(index, arg)?;
+    }
+    state.finish()
+}
+
+impl GraphCommand {
+    /// Parse a full `rrdtool graph` command, e.g. as returned by [`Self::to_args`].
+    ///
+    /// `args` may start with `rrdtool`, followed by `graph` or `graphv`, then the output file and
+    /// the args accepted by [`parse_graph_args`]. Error indexes are positions in `args`.
+    pub fn from_args<S: AsRef<str>>(args: &[S]) -> Result<Self, ParseError> {
+        let args = args.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let skip = usize::from(args.first() == Some(&"rrdtool"));
+        match args.get(skip) {
+            Some(&("graph" | "graphv")) => {}
+            other => {
+                return Err(ParseError::Invalid {
+                    index: skip,
+                    arg: other.copied().unwrap_or_default().to_string(),
+                    reason: "Expected `graph` or `graphv`".to_string(),
+                })
+            }
+        }
+
+        let (positional, mut state) = parse_options(&args[skip + 1..], skip + 1)?;
+        let mut positional = positional.into_iter();
+        // Like `rrdtool`, the output is the first arg that isn't an option
+        let (_, output) = positional.next().ok_or_else(|| ParseError::Invalid {
+            index: args.len(),
+            arg: String::new(),
+            reason: "Missing output file".to_string(),
+        })?;
+        for (index, arg) in positional {
+            state.push_element(index, arg)?;
+        }
+        let parsed = state.finish()?;
+
+        Ok(Self {
+            output: PathBuf::from(output),
+            image_format: parsed.image_format,
+            props: parsed.props,
+            elements: parsed.elements,
+        })
+    }
+
+    /// Parse a shell command line, e.g. as returned by [`Self::to_rrdtool_command`].
+    ///
+    /// See [`util::shell_split`] for the shell syntax that's accepted.
+    pub fn from_rrdtool_command(command: &str) -> Result<Self, ParseError> {
+        let args =
+            util::shell_split(command).map_err(|e| ParseError::CommandLine(e.0.to_string()))?;
+        Self::from_args(&args)
+    }
+}
+
+/// What's wrong with an arg, before its position is known.
+enum Problem {
+    Unsupported(String),
+    Invalid(String),
+}
+
+impl Problem {
+    fn unsupported(reason: impl Into<String>) -> Self {
+        Self::Unsupported(reason.into())
+    }
+
+    fn invalid(reason: impl Into<String>) -> Self {
+        Self::Invalid(reason.into())
+    }
+
+    fn at(self, index: usize, arg: &str) -> ParseError {
+        let arg = arg.to_string();
+        match self {
+            Problem::Unsupported(reason) => ParseError::Unsupported { index, arg, reason },
+            Problem::Invalid(reason) => ParseError::Invalid { index, arg, reason },
+        }
+    }
+}
+
+impl From<InvalidArgument> for Problem {
+    fn from(value: InvalidArgument) -> Self {
+        Self::Invalid(value.0.to_string())
+    }
+}
+
+/// How an option is handled.
+#[derive(Clone, Copy)]
+enum Kind {
+    Flag,
+    Value,
+    Unsupported(&'static str),
+}
+
+/// `rrdtool graph` options: long name, short name, and kind.
+const OPTIONS: &[(&str, Option<char>, Kind)] = &[
+    ("start", Some('s'), Kind::Value),
+    ("end", Some('e'), Kind::Value),
+    ("step", Some('S'), Kind::Value),
+    ("title", Some('t'), Kind::Value),
+    ("vertical-label", Some('v'), Kind::Value),
+    ("width", Some('w'), Kind::Value),
+    ("height", Some('h'), Kind::Value),
+    ("only-graph", Some('j'), Kind::Flag),
+    ("full-size-mode", Some('D'), Kind::Flag),
+    ("upper-limit", Some('u'), Kind::Value),
+    ("lower-limit", Some('l'), Kind::Value),
+    ("rigid", Some('r'), Kind::Flag),
+    ("allow-shrink", None, Kind::Flag),
+    ("alt-autoscale", Some('A'), Kind::Flag),
+    ("alt-autoscale-min", Some('J'), Kind::Flag),
+    ("alt-autoscale-max", Some('M'), Kind::Flag),
+    ("no-gridfit", Some('N'), Kind::Flag),
+    ("x-grid", Some('x'), Kind::Value),
+    ("week-fmt", None, Kind::Value),
+    ("y-grid", Some('y'), Kind::Value),
+    ("left-axis-formatter", None, Kind::Value),
+    ("left-axis-format", None, Kind::Value),
+    ("alt-y-grid", Some('Y'), Kind::Flag),
+    ("logarithmic", Some('o'), Kind::Flag),
+    ("units-exponent", Some('X'), Kind::Value),
+    ("units-length", Some('L'), Kind::Value),
+    ("units", None, Kind::Value),
+    ("right-axis", None, Kind::Value),
+    ("right-axis-label", None, Kind::Value),
+    ("right-axis-formatter", None, Kind::Value),
+    ("right-axis-format", None, Kind::Value),
+    ("no-legend", Some('g'), Kind::Flag),
+    ("force-rules-legend", Some('F'), Kind::Flag),
+    ("legend-position", None, Kind::Value),
+    ("legend-direction", None, Kind::Value),
+    ("color", Some('c'), Kind::Value),
+    ("grid-dash", None, Kind::Value),
+    ("border", None, Kind::Value),
+    ("dynamic-labels", None, Kind::Flag),
+    ("zoom", Some('m'), Kind::Value),
+    ("font", Some('n'), Kind::Value),
+    ("font-render-mode", Some('R'), Kind::Value),
+    ("font-smoothing-threshold", Some('B'), Kind::Value),
+    ("pango-markup", Some('P'), Kind::Flag),
+    ("graph-render-mode", Some('G'), Kind::Value),
+    ("slope-mode", Some('E'), Kind::Flag),
+    ("interlaced", Some('i'), Kind::Flag),
+    ("tabwidth", Some('T'), Kind::Value),
+    ("base", Some('b'), Kind::Value),
+    ("watermark", Some('W'), Kind::Value),
+    ("use-nan-for-all-missing-data", Some('Z'), Kind::Flag),
+    ("imgformat", Some('a'), Kind::Value),
+    (
+        "lazy",
+        Some('z'),
+        Kind::Unsupported("Use `graph_to_file`'s `lazy` param"),
+    ),
+    (
+        "daemon",
+        Some('d'),
+        Kind::Unsupported("rrdcached daemons aren't supported"),
+    ),
+    (
+        "imginfo",
+        Some('f'),
+        Kind::Unsupported("Use the returned `GraphMetadata` instead"),
+    ),
+    (
+        "no-minor",
+        Some('I'),
+        Kind::Unsupported("Not supported by `GraphProps`"),
+    ),
+    (
+        "disable-rrdtool-tag",
+        None,
+        Kind::Unsupported("Not supported by `GraphProps`"),
+    ),
+    (
+        "add-jsontime",
+        None,
+        Kind::Unsupported("Use `DataFormat::JsonTime` with `graph_data`"),
+    ),
+];
+
+/// Args that aren't options, with their indexes.
+type Positional<'a> = Vec<(usize, &'a str)>;
+
+/// Apply the options in `args` to a new [`State`], returning it and the other args with their
+/// indexes. `offset` is added to indexes in errors.
+fn parse_options<'a>(
+    args: &[&'a str],
+    offset: usize,
+) -> Result<(Positional<'a>, State), ParseError> {
+    let mut state = State::default();
+    let mut positional = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        let arg = args[i];
+        let unknown = || Problem::unsupported("Unknown option").at(offset + i, arg);
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value)),
+                None => (long, None),
+            };
+            let kind = OPTIONS
+                .iter()
+                .find(|(l, _, _)| *l == name)
+                .map(|(_, _, kind)| *kind)
+                .ok_or_else(unknown)?;
+            i = apply_option(&mut state, args, offset, i, name, kind, inline)?;
+        } else if let Some(letters) = arg.strip_prefix('-').filter(|l| !l.is_empty()) {
+            // Flags may be bundled, and the last may take a value, e.g. `-gw400`
+            for (j, letter) in letters.char_indices() {
+                let (name, kind) = OPTIONS
+                    .iter()
+                    .find(|(_, s, _)| *s == Some(letter))
+                    .map(|(l, _, kind)| (*l, *kind))
+                    .ok_or_else(unknown)?;
+                if let Kind::Value = kind {
+                    let rest = &letters[j + letter.len_utf8()..];
+                    let inline = (!rest.is_empty()).then_some(rest);
+                    i = apply_option(&mut state, args, offset, i, name, kind, inline)?;
+                    break;
+                }
+                apply_option(&mut state, args, offset, i, name, kind, None)?;
+            }
+        } else {
+            positional.push((offset + i, arg));
+        }
+        i += 1;
+    }
+    Ok((positional, state))
+}
+
+/// Apply option `name` from `args[i]`, with `inline` as its value if it was part of the arg,
+/// otherwise the next arg. Returns the index of the last arg used.
+fn apply_option(
+    state: &mut State,
+    args: &[&str],
+    offset: usize,
+    i: usize,
+    name: &str,
+    kind: Kind,
+    inline: Option<&str>,
+) -> Result<usize, ParseError> {
+    let (index, arg) = (offset + i, args[i]);
+    let last = match (kind, inline) {
+        (Kind::Unsupported(reason), _) => return Err(Problem::unsupported(reason).at(index, arg)),
+        (Kind::Flag, None) => {
+            state.apply_flag(name);
+            i
+        }
+        (Kind::Flag, Some(_)) => {
+            return Err(Problem::invalid("Option doesn't take a value").at(index, arg))
+        }
+        (Kind::Value, Some(value)) => {
+            state
+                .apply_value(name, value)
+                .map_err(|p| p.at(index, arg))?;
+            i
+        }
+        (Kind::Value, None) => {
+            let value = args
+                .get(i + 1)
+                .ok_or_else(|| Problem::invalid("Missing value").at(index, arg))?;
+            state
+                .apply_value(name, value)
+                .map_err(|p| p.at(index + 1, value))?;
+            i + 1
+        }
+    };
+    if name.starts_with("right-axis-") {
+        state
+            .right_axis_detail
+            .get_or_insert_with(|| (index, arg.to_string()));
+    }
+    Ok(last)
+}
+
+/// Parsed args so far.
+#[derive(Default)]
+struct State {
+    parsed: ParsedGraphArgs,
+    /// Whether `--right-axis` has set `scale` and `shift` in `props.right_y_axis`
+    right_axis_scale: bool,
+    /// The first `--right-axis-*` detail option, which requires `--right-axis`
+    right_axis_detail: Option<(usize, String)>,
+}
+
+impl State {
+    fn apply_flag(&mut self, name: &str) {
+        let props = &mut self.parsed.props;
+        match name {
+            "only-graph" => props.size.only_graph = true,
+            "full-size-mode" => props.size.full_size_mode = true,
+            "rigid" => props.limits.rigid = true,
+            "allow-shrink" => props.limits.allow_shrink = true,
+            // `librrd` ignores the min and max flags with `--alt-autoscale`
+            "alt-autoscale" => props.limits.alt_autoscale = Some(AltAutoscale::default()),
+            "alt-autoscale-min" | "alt-autoscale-max" => match &mut props.limits.alt_autoscale {
+                Some(AltAutoscale {
+                    alt_autoscale_min: false,
+                    alt_autoscale_max: false,
+                }) => {}
+                aa => {
+                    let aa = aa.get_or_insert_default();
+                    if name == "alt-autoscale-min" {
+                        aa.alt_autoscale_min = true;
+                    } else {
+                        aa.alt_autoscale_max = true;
+                    }
+                }
+            },
+            "no-gridfit" => props.limits.no_grid_fit = true,
+            "alt-y-grid" => props.y_axis.alt_y_grid = true,
+            "logarithmic" => props.y_axis.logarithmic = true,
+            "no-legend" => props.legend.no_legend = true,
+            "force-rules-legend" => props.legend.force_rules_legend = true,
+            "dynamic-labels" => props.misc.dynamic_labels = true,
+            "pango-markup" => props.misc.pango_markup = true,
+            "slope-mode" => props.misc.slope_mode = true,
+            "interlaced" => props.misc.interlaced = true,
+            "use-nan-for-all-missing-data" => props.misc.use_nan_for_all_missing_data = true,
+            _ => unreachable!("Unhandled flag {name}"),
+        }
+    }
+
+    fn apply_value(&mut self, name: &str, value: &str) -> Result<(), Problem> {
+        let props = &mut self.parsed.props;
+        match name {
+            "start" => props.time_range.start = Some(timestamp(value)?),
+            "end" => props.time_range.end = Some(timestamp(value)?),
+            "step" => props.time_range.step_seconds = Some(integer(value)?),
+            "title" => props.labels.title = Some(value.to_string()),
+            "vertical-label" => props.labels.vertical_label = Some(value.to_string()),
+            "width" => props.size.width = Some(integer(value)?),
+            "height" => props.size.height = Some(integer(value)?),
+            "upper-limit" => props.limits.upper_limit = Some(number(value)?),
+            "lower-limit" => props.limits.lower_limit = Some(number(value)?),
+            "x-grid" => props.x_axis.grid = Some(x_grid(value)?),
+            "week-fmt" => props.x_axis.week_format = Some(value.to_string()),
+            "y-grid" => {
+                props.y_axis.grid = Some(match value {
+                    "none" => YAxisGrid::None,
+                    _ => {
+                        let (grid_step, label_factor) = value
+                            .split_once(':')
+                            .ok_or_else(|| Problem::invalid("Expected `none` or `step:factor`"))?;
+                        YAxisGrid::Custom {
+                            grid_step: integer(grid_step)?,
+                            label_factor: integer(label_factor)?,
+                        }
+                    }
+                })
+            }
+            "left-axis-formatter" => props.y_axis.formatter = Some(formatter(value)?),
+            "left-axis-format" => props.y_axis.format = Some(value.to_string()),
+            "units-exponent" => {
+                props.y_axis.units_exponent = Some(UnitsExponent::new(integer(value)?)?)
+            }
+            "units-length" => props.y_axis.units_length = Some(integer(value)?),
+            "units" => {
+                props.y_axis.units = Some(match value {
+                    "si" => Units::Si,
+                    _ => return Err(Problem::invalid("Expected `si`")),
+                })
+            }
+            "right-axis" => {
+                let (scale, shift) = value
+                    .split_once(':')
+                    .ok_or_else(|| Problem::invalid("Expected `scale:shift`"))?;
+                let right_y_axis = right_y_axis(props);
+                right_y_axis.scale = number(scale)?;
+                right_y_axis.shift = integer(shift)?;
+                self.right_axis_scale = true;
+            }
+            "right-axis-label" | "right-axis-formatter" | "right-axis-format" => {
+                let right_y_axis = right_y_axis(props);
+                match name {
+                    "right-axis-label" => right_y_axis.label = Some(value.to_string()),
+                    "right-axis-formatter" => right_y_axis.formatter = Some(formatter(value)?),
+                    _ => right_y_axis.format = Some(value.to_string()),
+                }
+            }
+            "legend-position" => {
+                props.legend.legend_position = Some(match value {
+                    "north" => LegendPosition::North,
+                    "south" => LegendPosition::South,
+                    "east" => LegendPosition::East,
+                    "west" => LegendPosition::West,
+                    _ => return Err(Problem::invalid("Unknown legend position")),
+                })
+            }
+            "legend-direction" => {
+                props.legend.legend_direction = Some(match value {
+                    "topdown" => LegendDirection::TopDown,
+                    "bottomup" => LegendDirection::BottomUp,
+                    "bottomup2" => LegendDirection::BottomUp2,
+                    _ => return Err(Problem::invalid("Unknown legend direction")),
+                })
+            }
+            "color" => {
+                let split = value
+                    .find('#')
+                    .ok_or_else(|| Problem::invalid("Expected `TAG#color`"))?;
+                let tag: ColorTag = value[..split].parse()?;
+                props.misc.colors.insert(tag, value[split..].parse()?);
+            }
+            "grid-dash" => {
+                let (on, off) = value
+                    .split_once(':')
+                    .ok_or_else(|| Problem::invalid("Expected `on:off`"))?;
+                props.misc.grid_dash = Some((integer(on)?, integer(off)?));
+            }
+            "border" => props.misc.border = Some(integer(value)?),
+            "zoom" => props.misc.zoom = Some(Zoom::new(number(value)?)?),
+            "font" => {
+                let mut fields = value.splitn(3, ':');
+                let tag: FontTag = fields.next().unwrap_or_default().parse()?;
+                let size = fields
+                    .next()
+                    .ok_or_else(|| Problem::invalid("Expected `TAG:size[:font]`"))?;
+                props.misc.fonts.insert(
+                    tag,
+                    FontParams {
+                        size: integer(size)?,
+                        font: fields.next().map(str::to_string),
+                    },
+                );
+            }
+            "font-render-mode" => {
+                props.misc.font_render_mode = Some(match value {
+                    "normal" => FontRenderMode::Normal,
+                    "light" => FontRenderMode::Light,
+                    "mono" => FontRenderMode::Mono,
+                    _ => return Err(Problem::invalid("Unknown font render mode")),
+                })
+            }
+            "font-smoothing-threshold" => {
+                props.misc.font_smoothing_threshold = Some(integer(value)?)
+            }
+            "graph-render-mode" => {
+                props.misc.graph_render_mode = Some(match value {
+                    "normal" => GraphRenderMode::Normal,
+                    "mono" => GraphRenderMode::Mono,
+                    _ => return Err(Problem::invalid("Unknown graph render mode")),
+                })
+            }
+            "tabwidth" => props.misc.tab_width = Some(integer(value)?),
+            "base" => props.misc.base = Some(integer(value)?),
+            "watermark" => props.misc.watermark = Some(value.to_string()),
+            "imgformat" => {
+                self.parsed.image_format = Some(match value {
+                    "PNG" => ImageFormat::Png,
+                    "SVG" => ImageFormat::Svg,
+                    "EPS" => ImageFormat::Eps,
+                    "PDF" => ImageFormat::Pdf,
+                    "CSV" | "TSV" | "SSV" | "JSON" | "JSONTIME" | "XML" | "XMLENUM" => {
+                        return Err(Problem::unsupported(
+                            "Data formats are only supported by `graph_data`",
+                        ))
+                    }
+                    _ => return Err(Problem::invalid("Unknown image format")),
+                })
+            }
+            _ => unreachable!("Unhandled option {name}"),
+        }
+        Ok(())
+    }
+
+    fn push_element(&mut self, index: usize, arg: &str) -> Result<(), ParseError> {
+        let element = element(arg).map_err(|p| p.at(index, arg))?;
+        self.parsed.elements.push(element);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<ParsedGraphArgs, ParseError> {
+        if let (false, Some((index, arg))) = (self.right_axis_scale, self.right_axis_detail) {
+            return Err(Problem::invalid("Requires `--right-axis`").at(index, &arg));
+        }
+        Ok(self.parsed)
+    }
+}
+
+/// The right y axis, created with a zero scale and shift until `--right-axis` sets them.
+fn right_y_axis(props: &mut GraphProps) -> &mut RightYAxis {
+    props.right_y_axis.get_or_insert(RightYAxis {
+        scale: 0.0,
+        shift: 0,
+        label: None,
+        formatter: None,
+        format: None,
+    })
+}
+
+fn x_grid(value: &str) -> Result<XAxisGrid, Problem> {
+    if value == "none" {
+        return Ok(XAxisGrid::None);
+    }
+    let fields = value.splitn(8, ':').collect::<Vec<_>>();
+    let [base_grid_time, base_grid_step, major_grid_time, major_grid_step, labels_time, labels_step, label_placement, label_format] =
+        fields[..]
+    else {
+        return Err(Problem::invalid(
+            "Expected `none` or `GTM:GST:MTM:MST:LTM:LST:LPR:LFM`",
+        ));
+    };
+    Ok(XAxisGrid::Custom {
+        base_grid_time: time_unit(base_grid_time)?,
+        base_grid_step: integer(base_grid_step)?,
+        major_grid_time: time_unit(major_grid_time)?,
+        major_grid_step: integer(major_grid_step)?,
+        labels_time: time_unit(labels_time)?,
+        labels_step: integer(labels_step)?,
+        label_placement: integer(label_placement)?,
+        label_format: label_format.to_string(),
+    })
+}
+
+fn time_unit(s: &str) -> Result<AxisGridTimeUnit, Problem> {
+    Ok(match s {
+        "SECOND" => AxisGridTimeUnit::Second,
+        "MINUTE" => AxisGridTimeUnit::Minute,
+        "HOUR" => AxisGridTimeUnit::Hour,
+        "DAY" => AxisGridTimeUnit::Day,
+        "WEEK" => AxisGridTimeUnit::Week,
+        "MONTH" => AxisGridTimeUnit::Month,
+        "YEAR" => AxisGridTimeUnit::Year,
+        _ => return Err(Problem::invalid("Unknown time unit")),
+    })
+}
+
+fn formatter(s: &str) -> Result<YAxisFormatter, Problem> {
+    Ok(match s {
+        "numeric" => YAxisFormatter::Numeric,
+        "timestamp" => YAxisFormatter::Timestamp,
+        "duration" => YAxisFormatter::Duration,
+        _ => return Err(Problem::invalid("Unknown axis formatter")),
+    })
+}
+
+fn consolidation_fn(s: &str) -> Result<ConsolidationFn, Problem> {
+    Ok(match s {
+        "AVERAGE" => ConsolidationFn::Avg,
+        "MIN" => ConsolidationFn::Min,
+        "MAX" => ConsolidationFn::Max,
+        "LAST" => ConsolidationFn::Last,
+        _ => return Err(Problem::invalid("Unknown consolidation function")),
+    })
+}
+
+fn integer<T: FromStr>(s: &str) -> Result<T, Problem> {
+    s.parse()
+        .map_err(|_| Problem::invalid(format!("`{s}` isn't a valid integer")))
+}
+
+/// Parse a number, which must start with a digit, sign or `.`, to tell it apart from a var name.
+fn number(s: &str) -> Result<f64, Problem> {
+    s.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c))
+        .then(|| s.parse().ok())
+        .flatten()
+        .ok_or_else(|| Problem::invalid(format!("`{s}` isn't a valid number")))
+}
+
+/// Parse seconds since the epoch. `rrdtool` also accepts AT-style times, e.g. `now-1d`, which are
+/// relative to when the graph is rendered, so have no [`Timestamp`] equivalent.
+fn timestamp(s: &str) -> Result<Timestamp, Problem> {
+    if !s.is_empty() && s.chars().all(|c| c.is_ascii_digit()) {
+        s.parse()
+            .ok()
+            .and_then(|secs| Timestamp::from_timestamp(secs, 0))
+            .ok_or_else(|| Problem::invalid("Timestamp out of range"))
+    } else {
+        Err(Problem::unsupported(
+            "Only Unix timestamps are supported, not AT-style times",
+        ))
+    }
+}
+
+/// Split an element into its `:`-separated fields, leaving `\:` escapes in place.
+fn split_fields(arg: &str) -> Vec<&str> {
+    let mut fields = vec![];
+    let mut start = 0;
+    let mut escaped = false;
+    for (i, c) in arg.char_indices() {
+        if c == ':' && !escaped {
+            fields.push(&arg[start..i]);
+            start = i + 1;
+        }
+        escaped = c == '\\';
+    }
+    fields.push(&arg[start..]);
+    fields
+}
+
+fn element(arg: &str) -> Result<GraphElement, Problem> {
+    let fields = split_fields(arg);
+    let (kind, fields) = fields.split_first().unwrap();
+    Ok(match *kind {
+        "DEF" => def(fields)?.into(),
+        "CDEF" => {
+            let (var_name, rpn) = assignment(fields)?;
+            CDef { var_name, rpn }.into()
+        }
+        "VDEF" => {
+            let (var_name, rpn) = assignment(fields)?;
+            VDef { var_name, rpn }.into()
+        }
+        "PRINT" => print(fields)?.into(),
+        "GPRINT" => match fields {
+            [var_name, format] => GPrint {
+                var_name: VarName::new(*var_name)?,
+                format: format.parse()?,
+            }
+            .into(),
+            [_, cf, _] if consolidation_fn(cf).is_ok() => {
+                return Err(Problem::unsupported(
+                    "The deprecated `GPRINT:vname:CF:format` form isn't supported; use a VDEF",
+                ))
+            }
+            [_, _, mode] if print_format_mode(mode).is_some() => {
+                return Err(Problem::unsupported("`GPrint` has no format mode"))
+            }
+            _ => return Err(Problem::invalid("Expected `GPRINT:vname:format`")),
+        },
+        "COMMENT" => match fields {
+            [text] => Comment {
+                text: text.parse()?,
+            }
+            .into(),
+            _ => return Err(Problem::invalid("Expected `COMMENT:text`")),
+        },
+        "VRULE" | "HRULE" => {
+            let [value_color, rest @ ..] = fields else {
+                return Err(Problem::invalid("Missing value and color"));
+            };
+            let (value, color) = value_color
+                .split_once('#')
+                .ok_or_else(|| Problem::invalid("Missing color"))?;
+            let color = format!("#{color}").parse()?;
+            let trailing = Trailing::parse(rest, &["dashes", "dash-offset"])?;
+            if *kind == "VRULE" {
+                VRule {
+                    value: match timestamp(value) {
+                        Ok(t) => Value::Timestamp(t),
+                        Err(_) => Value::Variable(VarName::new(value)?),
+                    },
+                    color,
+                    legend: trailing.legend,
+                    dashes: trailing.dashes,
+                }
+                .into()
+            } else {
+                HRule {
+                    value: match number(value) {
+                        Ok(n) => Value::Constant(n),
+                        Err(_) => Value::Variable(VarName::new(value)?),
+                    },
+                    color,
+                    legend: trailing.legend,
+                    dashes: trailing.dashes,
+                }
+                .into()
+            }
+        }
+        "AREA" => {
+            let [value_color, rest @ ..] = fields else {
+                return Err(Problem::invalid("Missing value"));
+            };
+            let (value, colors) = split_colors(value_color)?;
+            let trailing = Trailing::parse(rest, &["STACK", "skipscale", "gradheight"])?;
+            let color = match colors[..] {
+                [] => None,
+                [color] => Some(AreaColor::Color(color)),
+                [color1, color2] => Some(AreaColor::Gradient {
+                    color1,
+                    color2,
+                    gradient_height: trailing.gradient_height,
+                }),
+                _ => return Err(Problem::invalid("Too many colors")),
+            };
+            if trailing.gradient_height.is_some() && colors.len() != 2 {
+                return Err(Problem::invalid("`gradheight` requires a gradient"));
+            }
+            Area {
+                value,
+                color: with_legend(color, trailing.legend)?,
+                stack: trailing.stack,
+                skip_scale: trailing.skip_scale,
+            }
+            .into()
+        }
+        "TICK" => {
+            let [value_color, rest @ ..] = fields else {
+                return Err(Problem::invalid("Missing value and color"));
+            };
+            let (var_name, colors) = split_colors(value_color)?;
+            let [color] = colors[..] else {
+                return Err(Problem::invalid("`TICK` requires a single color"));
+            };
+            let (fraction, rest) = match rest.split_first().map(|(f, r)| (number(f), r)) {
+                Some((Ok(fraction), rest)) => (Some(fraction), rest),
+                _ => (None, rest),
+            };
+            Tick {
+                var_name,
+                color,
+                fraction,
+                legend: Trailing::parse(rest, &[])?.legend,
+            }
+            .into()
+        }
+        "SHIFT" => match fields {
+            [var_name, offset] => Shift {
+                var_name: VarName::new(*var_name)?,
+                offset: match number(offset) {
+                    Ok(n) => Offset::TimeDelta(n),
+                    Err(_) => Offset::Variable(VarName::new(*offset)?),
+                },
+            }
+            .into(),
+            _ => return Err(Problem::invalid("Expected `SHIFT:vname:offset`")),
+        },
+        "TEXTALIGN" => match fields {
+            ["left"] => TextAlign::Left.into(),
+            ["right"] => TextAlign::Right.into(),
+            ["justified"] => TextAlign::Justified.into(),
+            ["center"] => TextAlign::Center.into(),
+            _ => {
+                return Err(Problem::invalid(
+                    "Expected `left`, `right`, `justified` or `center`",
+                ))
+            }
+        },
+        "STACK" => {
+            return Err(Problem::unsupported(
+                "The deprecated `STACK` element isn't supported; use `STACK` on a `LINE` or `AREA`",
+            ))
+        }
+        _ => match kind.strip_prefix("LINE") {
+            Some(width) => {
+                let [value_color, rest @ ..] = fields else {
+                    return Err(Problem::invalid("Missing value"));
+                };
+                let (value, colors) = split_colors(value_color)?;
+                let color = match colors[..] {
+                    [] => None,
+                    [color] => Some(color),
+                    _ => return Err(Problem::invalid("`LINE` can't have a gradient")),
+                };
+                let trailing =
+                    Trailing::parse(rest, &["STACK", "skipscale", "dashes", "dash-offset"])?;
+                Line {
+                    width: if width.is_empty() {
+                        1.0
+                    } else {
+                        number(width)?
+                    },
+                    value,
+                    color: with_legend(color, trailing.legend)?,
+                    stack: trailing.stack,
+                    skip_scale: trailing.skip_scale,
+                    dashes: trailing.dashes,
+                }
+                .into()
+            }
+            None => return Err(Problem::invalid("Unknown element")),
+        },
+    })
+}
+
+fn def(fields: &[&str]) -> Result<Def, Problem> {
+    let [assignment, ds_name, cf, options @ ..] = fields else {
+        return Err(Problem::invalid("Expected `DEF:vname=rrd:ds-name:CF`"));
+    };
+    let (var_name, rrd) = assignment
+        .split_once('=')
+        .ok_or_else(|| Problem::invalid("Expected `vname=rrd`"))?;
+    util::check_ds_name(ds_name)?;
+    let mut def = Def {
+        var_name: VarName::new(var_name)?,
+        rrd: PathBuf::from(rrd.replace("\\:", ":")),
+        ds_name: ds_name.to_string(),
+        consolidation_fn: consolidation_fn(cf)?,
+        step: None,
+        start: None,
+        end: None,
+        reduce: None,
+    };
+    for option in options {
+        match option.split_once('=') {
+            Some(("step", step)) => def.step = Some(integer(step)?),
+            Some(("start", start)) => def.start = Some(timestamp(start)?),
+            Some(("end", end)) => def.end = Some(timestamp(end)?),
+            Some(("reduce", reduce)) => def.reduce = Some(consolidation_fn(reduce)?),
+            Some(("daemon", _)) => {
+                return Err(Problem::unsupported("rrdcached daemons aren't supported"))
+            }
+            _ => return Err(Problem::invalid(format!("Unknown DEF option `{option}`"))),
+        }
+    }
+    Ok(def)
+}
+
+/// `vname=rpn` for `CDEF` and `VDEF`.
+fn assignment(fields: &[&str]) -> Result<(VarName, String), Problem> {
+    let [assignment] = fields else {
+        return Err(Problem::invalid("RPN expressions can't contain ':'"));
+    };
+    let (var_name, rpn) = assignment
+        .split_once('=')
+        .ok_or_else(|| Problem::invalid("Expected `vname=rpn`"))?;
+    Ok((VarName::new(var_name)?, rpn.to_string()))
+}
+
+fn print(fields: &[&str]) -> Result<Print, Problem> {
+    let (var_name, format, format_mode) = match fields {
+        [var_name, format] => (var_name, format, None),
+        [var_name, format, mode] if print_format_mode(mode).is_some() => {
+            (var_name, format, print_format_mode(mode))
+        }
+        [_, cf, _] if consolidation_fn(cf).is_ok() => {
+            return Err(Problem::unsupported(
+                "The deprecated `PRINT:vname:CF:format` form isn't supported; use a VDEF",
+            ))
+        }
+        _ => return Err(Problem::invalid("Expected `PRINT:vname:format[:mode]`")),
+    };
+    Ok(Print {
+        var_name: VarName::new(*var_name)?,
+        format: format.parse()?,
+        format_mode,
+    })
+}
+
+fn print_format_mode(s: &str) -> Option<PrintFormatMode> {
+    match s {
+        "strftime" => Some(PrintFormatMode::StrfTime),
+        "valstrftime" => Some(PrintFormatMode::ValStrfTime),
+        "valstrfduration" => Some(PrintFormatMode::ValStrfDuration),
+        _ => None,
+    }
+}
+
+/// Split `vname#color[#color2]` into the var name and colors.
+fn split_colors(field: &str) -> Result<(VarName, Vec<Color>), Problem> {
+    let mut parts = field.split('#');
+    let var_name = VarName::new(parts.next().unwrap_or_default())?;
+    let colors = parts
+        .map(|hex| format!("#{hex}").parse())
+        .collect::<Result<Vec<Color>, _>>()?;
+    Ok((var_name, colors))
+}
+
+/// `librrd` only shows a legend next to a color swatch.
+fn with_legend<C>(
+    color: Option<C>,
+    legend: Option<Legend>,
+) -> Result<Option<ColorWithLegend<C>>, Problem> {
+    match (color, legend) {
+        (Some(color), legend) => Ok(Some(ColorWithLegend { color, legend })),
+        (None, None) => Ok(None),
+        (None, Some(_)) => Err(Problem::invalid("A legend requires a color")),
+    }
+}
+
+/// The optional legend and keyword fields after an element's value and color.
+#[derive(Default)]
+struct Trailing {
+    legend: Option<Legend>,
+    stack: bool,
+    skip_scale: bool,
+    dashes: Option<Dashes>,
+    gradient_height: Option<f64>,
+}
+
+impl Trailing {
+    const KEYWORDS: &[&str] = &["STACK", "skipscale", "dashes", "dash-offset", "gradheight"];
+
+    /// Parse `fields`, where only keywords in `allowed` are valid. The legend, if any, must be
+    /// first, and is omitted if the field is empty.
+    fn parse(fields: &[&str], allowed: &[&str]) -> Result<Self, Problem> {
+        let mut trailing = Self::default();
+        for (i, field) in fields.iter().enumerate() {
+            let (keyword, value) = match field.split_once('=') {
+                Some((keyword, value)) => (keyword, Some(value)),
+                None => (*field, None),
+            };
+            if !Self::KEYWORDS.contains(&keyword) {
+                if i > 0 {
+                    return Err(Problem::invalid(format!("Unexpected field `{field}`")));
+                }
+                if !field.is_empty() {
+                    trailing.legend = Some(field.parse()?);
+                }
+                continue;
+            }
+            if !allowed.contains(&keyword) {
+                return Err(Problem::invalid(format!(
+                    "`{keyword}` isn't valid for this element"
+                )));
+            }
+            match (keyword, value) {
+                ("STACK", None) => trailing.stack = true,
+                ("skipscale", None) => trailing.skip_scale = true,
+                ("dashes", value) => {
+                    trailing.dashes = Some(Dashes {
+                        spacing: value.map(dash_spacing).transpose()?,
+                        offset: None,
+                    })
+                }
+                ("dash-offset", Some(offset)) => {
+                    trailing
+                        .dashes
+                        .as_mut()
+                        .ok_or_else(|| Problem::invalid("`dash-offset` requires `dashes`"))?
+                        .offset = Some(integer(offset)?)
+                }
+                ("gradheight", Some(height)) => trailing.gradient_height = Some(number(height)?),
+                _ => return Err(Problem::invalid(format!("Invalid `{keyword}`"))),
+            }
+        }
+        Ok(trailing)
+    }
+}
+
+fn dash_spacing(value: &str) -> Result<DashSpacing, Problem> {
+    let lengths = value
+        .split(',')
+        .map(integer)
+        .collect::<Result<Vec<u32>, _>>()?;
+    match lengths[..] {
+        [length] => Ok(DashSpacing::Simple(length)),
+        _ if lengths.len() % 2 == 0 => Ok(DashSpacing::Custom(
+            lengths.chunks(2).map(|pair| (pair[0], pair[1])).collect(),
+        )),
+        _ => Err(Problem::unsupported(
+            "An odd number of dash lengths can't be represented by `DashSpacing`",
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::graph::{
+        elements::{Directive, PrintFormat, TextControl},
+        graph_args,
+        props::{
+            AltAutoscale, Labels, Legend as LegendProps, Limits, Misc, Size, TimeRange, XAxis,
+            YAxis,
+        },
+    };
+    use proptest::{collection, option, prelude::*};
+
+    fn args(args: &[&str]) -> Result<ParsedGraphArgs, ParseError> {
+        parse_graph_args(args)
+    }
+
+    #[test]
+    fn short_and_long_options() {
+        let parsed = args(&[
+            "-w400",
+            "-h",
+            "100",
+            "--title=CPU",
+            "--vertical-label",
+            "%",
+            "-gE",
+            "-a",
+            "SVG",
+            "--legend-position",
+            "west",
+            "--units=si",
+        ])
+        .unwrap();
+        assert_eq!(Some(ImageFormat::Svg), parsed.image_format);
+        let props = parsed.props;
+        assert_eq!(
+            (Some(400), Some(100)),
+            (props.size.width, props.size.height)
+        );
+        assert_eq!(Some("CPU"), props.labels.title.as_deref());
+        assert_eq!(Some("%"), props.labels.vertical_label.as_deref());
+        assert!(props.legend.no_legend && props.misc.slope_mode);
+        assert_eq!(Some(LegendPosition::West), props.legend.legend_position);
+        assert_eq!(Some(Units::Si), props.y_axis.units);
+    }
+
+    #[test]
+    fn elements() {
+        let parsed = args(&[
+            r"DEF:in=/data/host\:1.rrd:in:AVERAGE:step=60:reduce=MAX",
+            "LINE:in#FF0000::STACK",
+            "AREA:in#00FF00#0000FF:Traffic:gradheight=-5",
+            "TICK:in#FF000080:Ticks",
+            r"GPRINT:in:%6.2lf%s\l",
+        ])
+        .unwrap();
+        assert_eq!(
+            vec![
+                GraphElement::Def(Def {
+                    var_name: VarName::new("in").unwrap(),
+                    rrd: "/data/host:1.rrd".into(),
+                    ds_name: "in".to_string(),
+                    consolidation_fn: ConsolidationFn::Avg,
+                    step: Some(60),
+                    start: None,
+                    end: None,
+                    reduce: Some(ConsolidationFn::Max),
+                }),
+                GraphElement::Line(Line {
+                    width: 1.0,
+                    value: VarName::new("in").unwrap(),
+                    color: Some(ColorWithLegend {
+                        color: "#FF0000".parse().unwrap(),
+                        legend: None,
+                    }),
+                    stack: true,
+                    skip_scale: false,
+                    dashes: None,
+                }),
+                GraphElement::Area(Area {
+                    value: VarName::new("in").unwrap(),
+                    color: Some(ColorWithLegend {
+                        color: AreaColor::Gradient {
+                            color1: "#00FF00".parse().unwrap(),
+                            color2: "#0000FF".parse().unwrap(),
+                            gradient_height: Some(-5.0),
+                        },
+                        legend: Some(Legend::new("Traffic")),
+                    }),
+                    stack: false,
+                    skip_scale: false,
+                }),
+                GraphElement::Tick(Tick {
+                    var_name: VarName::new("in").unwrap(),
+                    color: "#FF000080".parse().unwrap(),
+                    fraction: None,
+                    legend: Some(Legend::new("Ticks")),
+                }),
+                GraphElement::GPrint(GPrint {
+                    var_name: VarName::new("in").unwrap(),
+                    format: PrintFormat::default()
+                        .directive(Directive::value(6, 2))
+                        .directive(Directive::unit())
+                        .control(TextControl::Left),
+                }),
+            ],
+            parsed.elements
+        );
+    }
+
+    #[test]
+    fn reports_unsupported_args() {
+        let unsupported = |a: &[&str]| match args(a) {
+            Err(ParseError::Unsupported { index, arg, .. }) => (index, arg),
+            other => panic!("{a:?}: {other:?}"),
+        };
+        assert_eq!((1, "--lazy".to_string()), unsupported(&["-w1", "--lazy"]));
+        assert_eq!((0, "-gz".to_string()), unsupported(&["-gz"]));
+        assert_eq!((1, "end-1h".to_string()), unsupported(&["-s", "end-1h"]));
+        assert_eq!(
+            (0, "--start=now".to_string()),
+            unsupported(&["--start=now"])
+        );
+        assert_eq!(
+            (2, "DEF:v=a.rrd:ds:AVERAGE:start=-1d".to_string()),
+            unsupported(&["-w", "1", "DEF:v=a.rrd:ds:AVERAGE:start=-1d"])
+        );
+        assert_eq!(
+            (0, "GPRINT:v:MAX:%lf".to_string()),
+            unsupported(&["GPRINT:v:MAX:%lf"])
+        );
+        assert_eq!(
+            (0, "--imgformat=CSV".to_string()),
+            unsupported(&["--imgformat=CSV"])
+        );
+        assert_eq!(
+            (0, "--no-such-option".to_string()),
+            unsupported(&["--no-such-option"])
+        );
+        assert_eq!(
+            (1, "LINE:v#000000:dashes=1,2,3".to_string()),
+            unsupported(&["DEF:v=a.rrd:ds:MAX", "LINE:v#000000:dashes=1,2,3"])
+        );
+    }
+
+    #[test]
+    fn cacti_command_with_alt_autoscale_max() {
+        let command = GraphCommand::from_rrdtool_command(
+            "rrdtool graph - --imgformat=PNG --start=1700000000 --end=1700086400 \
+            --title='Load Average' --rigid --height=120 --width=500 --alt-autoscale-max \
+            --lower-limit='0' --vertical-label='processes in the run queue' \
+            DEF:a='/var/lib/cacti/rra/host_load_1min_5.rrd':'load_1min':AVERAGE \
+            AREA:a#EACC00FF:'1 Minute Average'",
+        )
+        .unwrap();
+        // The flag doesn't consume the next arg
+        assert!(command.props.limits.rigid);
+        assert_eq!(Some(0.0), command.props.limits.lower_limit);
+        assert_eq!(
+            Some(AltAutoscale {
+                alt_autoscale_min: false,
+                alt_autoscale_max: true,
+            }),
+            command.props.limits.alt_autoscale
+        );
+        assert_eq!(2, command.elements.len());
+    }
+
+    #[test]
+    fn alt_autoscale_flags() {
+        let alt_autoscale = |argv: &[&str]| args(argv).unwrap().props.limits.alt_autoscale;
+        let aa = |alt_autoscale_min, alt_autoscale_max| {
+            Some(AltAutoscale {
+                alt_autoscale_min,
+                alt_autoscale_max,
+            })
+        };
+        assert_eq!(aa(false, false), alt_autoscale(&["-A"]));
+        assert_eq!(aa(true, false), alt_autoscale(&["-J"]));
+        assert_eq!(aa(true, true), alt_autoscale(&["-M", "-J"]));
+        // `--alt-autoscale` takes precedence, whatever the order
+        assert_eq!(aa(false, false), alt_autoscale(&["-A", "-J"]));
+        assert_eq!(aa(false, false), alt_autoscale(&["-M", "-A"]));
+    }
+
+    #[test]
+    fn reports_invalid_args() {
+        let invalid = |a: &[&str]| match args(a) {
+            Err(ParseError::Invalid { index, .. }) => index,
+            other => panic!("{a:?}: {other:?}"),
+        };
+        assert_eq!(1, invalid(&["--width", "wide"]));
+        assert_eq!(0, invalid(&["--height"]));
+        assert_eq!(0, invalid(&["--rigid=yes"]));
+        assert_eq!(0, invalid(&["LINE1:v:No color"]));
+        assert_eq!(0, invalid(&["VRULE:v"]));
+        assert_eq!(0, invalid(&["CDEF:v=a,b:c"]));
+        assert_eq!(0, invalid(&["AREA:v#000000:legend:gradheight=5"]));
+        assert_eq!(0, invalid(&["LINE:v#000000:dash-offset=5"]));
+        assert_eq!(0, invalid(&["BOX:v"]));
+        assert_eq!(1, invalid(&["-w1", "--right-axis-label", "Right"]));
+    }
+
+    #[test]
+    fn graph_command_from_rrdtool_command() {
+        let command = GraphCommand::from_rrdtool_command(
+            "rrdtool graph 'out dir/graph.png' --imgformat PNG \\\n  DEF:v=a.rrd:ds:LAST 'LINE2:v#00FF00:Speed'",
+        )
+        .unwrap();
+        assert_eq!(PathBuf::from("out dir/graph.png"), command.output);
+        assert_eq!(Some(ImageFormat::Png), command.image_format);
+        assert_eq!(2, command.elements.len());
+        assert_eq!(
+            command,
+            GraphCommand::from_rrdtool_command(&command.to_rrdtool_command().unwrap()).unwrap()
+        );
+
+        assert!(matches!(
+            GraphCommand::from_args(&["rrdtool", "fetch", "a.rrd"]),
+            Err(ParseError::Invalid { index: 1, .. })
+        ));
+        assert!(matches!(
+            GraphCommand::from_args(&["graph", "--width", "wide"]),
+            Err(ParseError::Invalid { index: 2, .. })
+        ));
+        assert!(matches!(
+            GraphCommand::from_rrdtool_command("rrdtool graph $OUT"),
+            Err(ParseError::CommandLine(_))
+        ));
+    }
+
+    // Strategies for values that `graph_args` produces unambiguously
+
+    fn text() -> impl Strategy<Value = String> {
+        "[A-Z][A-Za-z0-9 :,.]{0,12}"
+    }
+
+    fn var_name() -> impl Strategy<Value = VarName> {
+        "[a-z][a-z0-9_]{0,8}".prop_map(|s| VarName::new(s).unwrap())
+    }
+
+    fn finite() -> impl Strategy<Value = f64> {
+        any::<f64>().prop_filter("finite", |f| f.is_finite())
+    }
+
+    fn timestamp() -> impl Strategy<Value = Timestamp> {
+        (0..4_000_000_000i64).prop_map(|secs| Timestamp::from_timestamp(secs, 0).unwrap())
+    }
+
+    fn color() -> impl Strategy<Value = Color> {
+        (any::<[u8; 3]>(), option::of(any::<u8>())).prop_map(|([red, green, blue], alpha)| Color {
+            red,
+            green,
+            blue,
+            alpha,
+        })
+    }
+
+    fn legend() -> impl Strategy<Value = Legend> {
+        (
+            text().prop_filter("not a keyword", |t| {
+                !Trailing::KEYWORDS.contains(&t.as_str())
+            }),
+            option::of(prop_oneof![
+                Just(TextControl::Left),
+                Just(TextControl::Newline),
+                Just(TextControl::Right),
+                Just(TextControl::Center),
+                Just(TextControl::Justified),
+                Just(TextControl::Glue),
+                Just(TextControl::Space),
+                Just(TextControl::Up),
+            ]),
+        )
+            .prop_map(|(text, control)| match control {
+                Some(control) => Legend::new(text).with_control(control),
+                None => Legend::new(text),
+            })
+    }
+
+    fn print_format() -> impl Strategy<Value = PrintFormat> {
+        // Alternate text and directives, since adjacent text parts would be merged
+        (
+            option::of(text()),
+            collection::vec(
+                (
+                    prop_oneof![
+                        (0..20u8, 0..6u8).prop_map(|(w, p)| Directive::value(w, p)),
+                        Just(Directive::unit()),
+                        Just(Directive::new("%H").unwrap()),
+                    ],
+                    option::of("[a-z %]{1,6}"),
+                ),
+                0..3,
+            ),
+        )
+            .prop_map(|(first, rest)| {
+                let mut format = PrintFormat::default();
+                if let Some(text) = first {
+                    format = format.text(text);
+                }
+                for (directive, text) in rest {
+                    format = format.directive(directive);
+                    if let Some(text) = text {
+                        format = format.text(text);
+                    }
+                }
+                format
+            })
+    }
+
+    fn consolidation() -> impl Strategy<Value = ConsolidationFn> {
+        prop_oneof![
+            Just(ConsolidationFn::Avg),
+            Just(ConsolidationFn::Min),
+            Just(ConsolidationFn::Max),
+            Just(ConsolidationFn::Last),
+        ]
+    }
+
+    fn dashes() -> impl Strategy<Value = Dashes> {
+        (
+            option::of(prop_oneof![
+                (1..100u32).prop_map(DashSpacing::Simple),
+                collection::vec((1..100u32, 1..100u32), 1..4).prop_map(DashSpacing::Custom),
+            ]),
+            option::of(0..100u32),
+        )
+            .prop_map(|(spacing, offset)| Dashes { spacing, offset })
+    }
+
+    fn element() -> impl Strategy<Value = GraphElement> {
+        prop_oneof![
+            (
+                var_name(),
+                "/[a-z:]{1,8}\\.rrd",
+                "[A-Za-z0-9_]{1,19}",
+                consolidation(),
+                option::of(1..3600u32),
+                option::of(timestamp()),
+                option::of(timestamp()),
+                option::of(consolidation()),
+            )
+                .prop_map(
+                    |(var_name, rrd, ds_name, consolidation_fn, step, start, end, reduce)| {
+                        Def {
+                            var_name,
+                            rrd: rrd.into(),
+                            ds_name,
+                            consolidation_fn,
+                            step,
+                            start,
+                            end,
+                            reduce,
+                        }
+                        .into()
+                    }
+                ),
+            (var_name(), "[a-z0-9,+]{1,12}")
+                .prop_map(|(var_name, rpn)| CDef { var_name, rpn }.into()),
+            (var_name(), "[a-z0-9,]{1,12}")
+                .prop_map(|(var_name, rpn)| VDef { var_name, rpn }.into()),
+            (
+                var_name(),
+                print_format(),
+                option::of(prop_oneof![
+                    Just(PrintFormatMode::StrfTime),
+                    Just(PrintFormatMode::ValStrfTime),
+                    Just(PrintFormatMode::ValStrfDuration),
+                ])
+            )
+                .prop_map(|(var_name, format, format_mode)| Print {
+                    var_name,
+                    format,
+                    format_mode
+                }
+                .into()),
+            (var_name(), print_format())
+                .prop_map(|(var_name, format)| GPrint { var_name, format }.into()),
+            legend().prop_map(|text| Comment { text }.into()),
+            (
+                prop_oneof![
+                    var_name().prop_map(Value::Variable),
+                    timestamp().prop_map(Value::Timestamp),
+                ],
+                color(),
+                option::of(legend()),
+                option::of(dashes()),
+            )
+                .prop_map(|(value, color, legend, dashes)| VRule {
+                    value,
+                    color,
+                    legend,
+                    dashes
+                }
+                .into()),
+            (
+                prop_oneof![
+                    var_name().prop_map(Value::Variable),
+                    finite().prop_map(Value::Constant),
+                ],
+                color(),
+                option::of(legend()),
+                option::of(dashes()),
+            )
+                .prop_map(|(value, color, legend, dashes)| HRule {
+                    value,
+                    color,
+                    legend,
+                    dashes
+                }
+                .into()),
+            (
+                finite(),
+                var_name(),
+                option::of((color(), option::of(legend()))),
+                any::<bool>(),
+                any::<bool>(),
+                option::of(dashes()),
+            )
+                .prop_map(|(width, value, color, stack, skip_scale, dashes)| Line {
+                    width,
+                    value,
+                    color: color.map(|(color, legend)| ColorWithLegend { color, legend }),
+                    stack,
+                    skip_scale,
+                    dashes,
+                }
+                .into()),
+            (
+                var_name(),
+                option::of((
+                    prop_oneof![
+                        color().prop_map(AreaColor::Color),
+                        (color(), color(), option::of(finite())).prop_map(
+                            |(color1, color2, gradient_height)| AreaColor::Gradient {
+                                color1,
+                                color2,
+                                gradient_height,
+                            }
+                        ),
+                    ],
+                    option::of(legend()),
+                )),
+                any::<bool>(),
+                any::<bool>(),
+            )
+                .prop_map(|(value, color, stack, skip_scale)| Area {
+                    value,
+                    color: color.map(|(color, legend)| ColorWithLegend { color, legend }),
+                    stack,
+                    skip_scale,
+                }
+                .into()),
+            (
+                var_name(),
+                color(),
+                option::of(0.0..1.0f64),
+                option::of(legend())
+            )
+                .prop_map(|(var_name, color, fraction, legend)| Tick {
+                    var_name,
+                    color,
+                    fraction,
+                    legend,
+                }
+                .into()),
+            (
+                var_name(),
+                prop_oneof![
+                    var_name().prop_map(Offset::Variable),
+                    finite().prop_map(Offset::TimeDelta),
+                ]
+            )
+                .prop_map(|(var_name, offset)| Shift { var_name, offset }.into()),
+            prop_oneof![
+                Just(TextAlign::Left),
+                Just(TextAlign::Right),
+                Just(TextAlign::Justified),
+                Just(TextAlign::Center),
+            ]
+            .prop_map(GraphElement::from),
+        ]
+    }
+
+    fn props() -> impl Strategy<Value = GraphProps> {
+        let time_range = (
+            option::of(timestamp()),
+            option::of(timestamp()),
+            option::of(any::<u32>()),
+        )
+            .prop_map(|(start, end, step_seconds)| TimeRange {
+                start,
+                end,
+                step_seconds,
+            });
+        let labels =
+            (option::of(text()), option::of(text())).prop_map(|(title, vertical_label)| Labels {
+                title,
+                vertical_label,
+            });
+        let size = (
+            option::of(any::<u32>()),
+            option::of(any::<u32>()),
+            any::<bool>(),
+            any::<bool>(),
+        )
+            .prop_map(|(width, height, full_size_mode, only_graph)| Size {
+                width,
+                height,
+                full_size_mode,
+                only_graph,
+            });
+        let limits = (
+            option::of(finite()),
+            option::of(finite()),
+            any::<bool>(),
+            any::<bool>(),
+            option::of((any::<bool>(), any::<bool>()).prop_map(
+                |(alt_autoscale_min, alt_autoscale_max)| AltAutoscale {
+                    alt_autoscale_min,
+                    alt_autoscale_max,
+                },
+            )),
+            any::<bool>(),
+        )
+            .prop_map(
+                |(upper_limit, lower_limit, rigid, allow_shrink, alt_autoscale, no_grid_fit)| {
+                    Limits {
+                        upper_limit,
+                        lower_limit,
+                        rigid,
+                        allow_shrink,
+                        alt_autoscale,
+                        no_grid_fit,
+                    }
+                },
+            );
+        let time_unit = || {
+            prop_oneof![
+                Just(AxisGridTimeUnit::Second),
+                Just(AxisGridTimeUnit::Minute),
+                Just(AxisGridTimeUnit::Hour),
+                Just(AxisGridTimeUnit::Day),
+                Just(AxisGridTimeUnit::Week),
+                Just(AxisGridTimeUnit::Month),
+                Just(AxisGridTimeUnit::Year),
+            ]
+        };
+        let x_axis = (
+            option::of(prop_oneof![
+                Just(XAxisGrid::None),
+                (
+                    (time_unit(), any::<u32>(), time_unit(), any::<u32>()),
+                    (time_unit(), any::<u32>(), any::<u32>(), "%[a-zA-Z: ]{0,8}"),
+                )
+                    .prop_map(
+                        |(
+                            (base_grid_time, base_grid_step, major_grid_time, major_grid_step),
+                            (labels_time, labels_step, label_placement, label_format),
+                        )| XAxisGrid::Custom {
+                            base_grid_time,
+                            base_grid_step,
+                            major_grid_time,
+                            major_grid_step,
+                            labels_time,
+                            labels_step,
+                            label_placement,
+                            label_format,
+                        }
+                    ),
+            ]),
+            option::of(text()),
+        )
+            .prop_map(|(grid, week_format)| XAxis { grid, week_format });
+        let formatter = || {
+            prop_oneof![
+                Just(YAxisFormatter::Numeric),
+                Just(YAxisFormatter::Timestamp),
+                Just(YAxisFormatter::Duration),
+            ]
+        };
+        let y_axis = (
+            option::of(prop_oneof![
+                Just(YAxisGrid::None),
+                (any::<u32>(), any::<u32>()).prop_map(|(grid_step, label_factor)| {
+                    YAxisGrid::Custom {
+                        grid_step,
+                        label_factor,
+                    }
+                }),
+            ]),
+            option::of(formatter()),
+            option::of(text()),
+            any::<bool>(),
+            any::<bool>(),
+            option::of((-6..=6i8).prop_map(|e| UnitsExponent::new(e * 3).unwrap())),
+            option::of(any::<u8>()),
+            option::of(Just(Units::Si)),
+        )
+            .prop_map(
+                |(
+                    grid,
+                    formatter,
+                    format,
+                    alt_y_grid,
+                    logarithmic,
+                    units_exponent,
+                    units_length,
+                    units,
+                )| YAxis {
+                    grid,
+                    formatter,
+                    format,
+                    alt_y_grid,
+                    logarithmic,
+                    units_exponent,
+                    units_length,
+                    units,
+                },
+            );
+        let right_y_axis = option::of(
+            (
+                finite(),
+                any::<u32>(),
+                option::of(text()),
+                option::of(formatter()),
+                option::of(text()),
+            )
+                .prop_map(|(scale, shift, label, formatter, format)| RightYAxis {
+                    scale,
+                    shift,
+                    label,
+                    formatter,
+                    format,
+                }),
+        );
+        let legend = (
+            any::<bool>(),
+            any::<bool>(),
+            option::of(prop_oneof![
+                Just(LegendPosition::North),
+                Just(LegendPosition::South),
+                Just(LegendPosition::East),
+                Just(LegendPosition::West),
+            ]),
+            option::of(prop_oneof![
+                Just(LegendDirection::TopDown),
+                Just(LegendDirection::BottomUp),
+                Just(LegendDirection::BottomUp2),
+            ]),
+        )
+            .prop_map(
+                |(no_legend, force_rules_legend, legend_position, legend_direction)| LegendProps {
+                    no_legend,
+                    force_rules_legend,
+                    legend_position,
+                    legend_direction,
+                },
+            );
+        let misc = (
+            (
+                collection::hash_map(
+                    prop_oneof![
+                        Just(ColorTag::Back),
+                        Just(ColorTag::Canvas),
+                        Just(ColorTag::ShadeA),
+                        Just(ColorTag::ShadeB),
+                        Just(ColorTag::Grid),
+                        Just(ColorTag::MGrid),
+                        Just(ColorTag::Font),
+                        Just(ColorTag::Axis),
+                        Just(ColorTag::Frame),
+                        Just(ColorTag::Arrow),
+                    ],
+                    color(),
+                    0..3,
+                ),
+                option::of((any::<u32>(), any::<u32>())),
+                option::of(any::<u32>()),
+                any::<bool>(),
+                option::of(
+                    finite()
+                        .prop_filter("positive", |z| *z > 0.0)
+                        .prop_map(|z| Zoom::new(z).unwrap()),
+                ),
+                collection::hash_map(
+                    prop_oneof![
+                        Just(FontTag::Default),
+                        Just(FontTag::Title),
+                        Just(FontTag::Axis),
+                        Just(FontTag::Unit),
+                        Just(FontTag::Legend),
+                        Just(FontTag::Watermark),
+                    ],
+                    (any::<u32>(), option::of("[A-Za-z :]{1,10}"))
+                        .prop_map(|(size, font)| FontParams { size, font }),
+                    0..3,
+                ),
+            ),
+            (
+                option::of(prop_oneof![
+                    Just(FontRenderMode::Normal),
+                    Just(FontRenderMode::Light),
+                    Just(FontRenderMode::Mono),
+                ]),
+                option::of(any::<u32>()),
+                any::<bool>(),
+                option::of(prop_oneof![
+                    Just(GraphRenderMode::Normal),
+                    Just(GraphRenderMode::Mono),
+                ]),
+                any::<bool>(),
+                any::<bool>(),
+                option::of(any::<u32>()),
+                option::of(any::<u32>()),
+                option::of(text()),
+                any::<bool>(),
+            ),
+        )
+            .prop_map(
+                |(
+                    (colors, grid_dash, border, dynamic_labels, zoom, fonts),
+                    (
+                        font_render_mode,
+                        font_smoothing_threshold,
+                        pango_markup,
+                        graph_render_mode,
+                        slope_mode,
+                        interlaced,
+                        tab_width,
+                        base,
+                        watermark,
+                        use_nan_for_all_missing_data,
+                    ),
+                )| Misc {
+                    colors,
+                    grid_dash,
+                    border,
+                    dynamic_labels,
+                    zoom,
+                    fonts,
+                    font_render_mode,
+                    font_smoothing_threshold,
+                    pango_markup,
+                    graph_render_mode,
+                    slope_mode,
+                    interlaced,
+                    tab_width,
+                    base,
+                    watermark,
+                    use_nan_for_all_missing_data,
+                },
+            );
+        (
+            time_range,
+            labels,
+            size,
+            limits,
+            x_axis,
+            y_axis,
+            right_y_axis,
+            legend,
+            misc,
+        )
+            .prop_map(
+                |(time_range, labels, size, limits, x_axis, y_axis, right_y_axis, legend, misc)| {
+                    GraphProps {
+                        time_range,
+                        labels,
+                        size,
+                        limits,
+                        x_axis,
+                        y_axis,
+                        right_y_axis,
+                        legend,
+                        misc,
+                    }
+                },
+            )
+    }
+
+    proptest! {
+        #[test]
+        fn props_round_trip(
+            image_format in option::of(prop_oneof![
+                Just(ImageFormat::Png),
+                Just(ImageFormat::Svg),
+                Just(ImageFormat::Eps),
+                Just(ImageFormat::Pdf),
+            ]),
+            props in props(),
+        ) {
+            // `graph_args` requires a Def and something to draw
+            let elements = [
+                Def {
+                    var_name: VarName::new("v").unwrap(),
+                    rrd: "a.rrd".into(),
+                    ds_name: "ds".to_string(),
+                    consolidation_fn: ConsolidationFn::Avg,
+                    step: None,
+                    start: None,
+                    end: None,
+                    reduce: None,
+                }
+                .into(),
+                GPrint {
+                    var_name: VarName::new("v").unwrap(),
+                    format: PrintFormat::default().directive(Directive::unit()),
+                }
+                .into(),
+            ];
+            let args = graph_args(image_format, props.clone(), &elements).unwrap();
+            let parsed = parse_graph_args(&args).unwrap();
+            prop_assert_eq!(image_format, parsed.image_format);
+            prop_assert_eq!(props, parsed.props);
+            prop_assert_eq!(elements.to_vec(), parsed.elements);
+        }
+
+        #[test]
+        fn elements_round_trip(elements in collection::vec(element(), 1..8)) {
+            let mut args = vec![];
+            for element in &elements {
+                crate::ops::graph::AppendArgs::append_to(element, &mut args).unwrap();
+            }
+            let parsed = parse_graph_args(&args).unwrap();
+            prop_assert_eq!(GraphProps::default(), parsed.props);
+            prop_assert_eq!(elements, parsed.elements);
+        }
+    }
+}
@@ -0,0 +1,452 @@
+//! Limits for rendering graphs from untrusted definitions.
+//!
+//! A graph definition can name any RRD, and can ask for an image large enough to exhaust memory.
+//! When definitions come from users, e.g. on a shared dashboard server, check them with a
+//! [`GraphPolicy`] first.
+
+use crate::{
+    data::Data,
+    error::{RrdError, RrdResult},
+    ops::graph::{
+        elements::{Def, GraphElement},
+        props::{DataFormat, GraphProps, ImageFormat},
+        GraphFileOutcome, GraphMetadata,
+    },
+    Timestamp,
+};
+use rrd_sys::rrd_double;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Restrictions applied to a graph definition before it's rendered.
+///
+/// - Every [`Def`] must read an RRD inside the root directory, after resolving symlinks and `..`.
+///   Relative paths are resolved against the root.
+/// - Image size, zoom, font size, element count and time span are capped.
+/// - Options that read other files, i.e. font files, are rejected.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rrd::ops::graph::{policy::GraphPolicy, props};
+/// # fn elements() -> Vec<rrd::ops::graph::elements::GraphElement> { vec![] }
+///
+/// let policy = GraphPolicy::new("/var/lib/rrd")?;
+/// let (image, _metadata) = policy.graph(
+///     props::ImageFormat::Png,
+///     props::GraphProps::default(),
+///     &elements(),
+/// )?;
+/// # Ok::<(), rrd::error::RrdError>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphPolicy {
+    /// Canonical path of the directory RRDs must be in
+    root: PathBuf,
+    /// Max `--width`, in pixels
+    pub max_width: u32,
+    /// Max `--height`, in pixels
+    pub max_height: u32,
+    /// Max `--zoom`
+    pub max_zoom: f64,
+    /// Max font size, in points
+    pub max_font_size: u32,
+    /// Max number of elements
+    pub max_elements: usize,
+    /// Max time span of the graph, and of each [`Def`]
+    pub max_time_span: Duration,
+    /// Max rows of data the graph's `--step`, or a [`Def`]'s step, can ask for, i.e. the time
+    /// span divided by the step. Without a step, `librrd` picks one that gives about a row per
+    /// pixel.
+    pub max_rows: u64,
+}
+
+/// `rrdgraph` draws the last day if no start is given.
+const DEFAULT_SPAN: chrono::TimeDelta = chrono::TimeDelta::days(1);
+
+impl GraphPolicy {
+    /// A policy with conservative default limits, allowing only RRDs in `root`.
+    ///
+    /// Returns an error if `root` can't be canonicalized, e.g. because it doesn't exist.
+    pub fn new(root: impl AsRef<Path>) -> RrdResult<Self> {
+        let root = root.as_ref();
+        let root = root.canonicalize().map_err(|e| {
+            RrdError::InvalidArgument(format!("Graph policy root {root:?} is not accessible: {e}"))
+        })?;
+        Ok(Self {
+            root,
+            max_width: 2000,
+            max_height: 2000,
+            max_zoom: 4.0,
+            max_font_size: 72,
+            max_elements: 200,
+            max_time_span: Duration::from_secs(5 * 366 * 24 * 60 * 60),
+            max_rows: 100_000,
+        })
+    }
+
+    /// The canonical root directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Check `props` and `elements` against the policy.
+    ///
+    /// Returns `elements` with every [`Def`] path replaced by its canonical path, so that the RRDs
+    /// read are the ones that were checked, whatever the current directory.
+    pub fn apply(
+        &self,
+        props: &GraphProps,
+        elements: &[GraphElement],
+    ) -> RrdResult<Vec<GraphElement>> {
+        self.check_props(props)?;
+        if elements.len() > self.max_elements {
+            return Err(violation(format!(
+                "{} elements is more than the maximum {}",
+                elements.len(),
+                self.max_elements
+            )));
+        }
+
+        let (graph_start, graph_end) = graph_time_range(props);
+        elements
+            .iter()
+            .map(|element| match element {
+                GraphElement::Def(def) => {
+                    self.check_time_range(
+                        def.start.unwrap_or(graph_start),
+                        def.end.unwrap_or(graph_end),
+                        def.step.or(props.time_range.step_seconds),
+                    )?;
+                    Ok(GraphElement::Def(Def {
+                        rrd: self.confine(&def.rrd)?,
+                        ..def.clone()
+                    }))
+                }
+                other => Ok(other.clone()),
+            })
+            .collect()
+    }
+
+    /// Check the graph definition with [`GraphPolicy::apply`], then render it with
+    /// [`graph`](super::graph).
+    pub fn graph(
+        &self,
+        image_format: ImageFormat,
+        props: GraphProps,
+        elements: &[GraphElement],
+    ) -> RrdResult<(Vec<u8>, GraphMetadata)> {
+        let elements = self.apply(&props, elements)?;
+        super::graph(image_format, props, &elements)
+    }
+
+    /// Check the graph definition with [`GraphPolicy::apply`], then render it to `path` with
+    /// [`graph_to_file`](super::graph_to_file).
+    ///
+    /// `path` is trusted, and isn't confined to the root directory.
+    pub fn graph_to_file(
+        &self,
+        path: &Path,
+        image_format: ImageFormat,
+        props: GraphProps,
+        elements: &[GraphElement],
+        lazy: bool,
+    ) -> RrdResult<GraphFileOutcome> {
+        let elements = self.apply(&props, elements)?;
+        super::graph_to_file(path, image_format, props, &elements, lazy)
+    }
+
+    /// Check the graph definition with [`GraphPolicy::apply`], then export its data with
+    /// [`graph_data`](super::graph_data).
+    pub fn graph_data(
+        &self,
+        format: DataFormat,
+        props: GraphProps,
+        elements: &[GraphElement],
+    ) -> RrdResult<Data<Vec<rrd_double>>> {
+        let elements = self.apply(&props, elements)?;
+        super::graph_data(format, props, &elements)
+    }
+
+    fn check_props(&self, props: &GraphProps) -> RrdResult<()> {
+        if let Some(width) = props.size.width.filter(|w| *w > self.max_width) {
+            return Err(violation(format!(
+                "Width {width} is more than the maximum {}",
+                self.max_width
+            )));
+        }
+        if let Some(height) = props.size.height.filter(|h| *h > self.max_height) {
+            return Err(violation(format!(
+                "Height {height} is more than the maximum {}",
+                self.max_height
+            )));
+        }
+        if let Some(zoom) = props.misc.zoom.map(f64::from) {
+            if zoom > self.max_zoom {
+                return Err(violation(format!(
+                    "Zoom {zoom} is more than the maximum {}",
+                    self.max_zoom
+                )));
+            }
+        }
+        for params in props.misc.fonts.values() {
+            if params.font.is_some() {
+                return Err(violation("Fonts can't be set".to_string()));
+            }
+            if params.size > self.max_font_size {
+                return Err(violation(format!(
+                    "Font size {} is more than the maximum {}",
+                    params.size, self.max_font_size
+                )));
+            }
+        }
+
+        let (start, end) = graph_time_range(props);
+        self.check_time_range(start, end, props.time_range.step_seconds)
+    }
+
+    /// Check the time span from `start` to `end`, and the rows it has at `step` seconds.
+    fn check_time_range(
+        &self,
+        start: Timestamp,
+        end: Timestamp,
+        step: Option<u32>,
+    ) -> RrdResult<()> {
+        self.check_time_span(start, end)?;
+        let Some(step) = step else {
+            return Ok(());
+        };
+        let span = u64::try_from((end - start).num_seconds()).unwrap_or_default();
+        let rows = span / u64::from(step.max(1));
+        if rows > self.max_rows {
+            Err(violation(format!(
+                "Step of {step}s gives {rows} rows, which is more than the maximum {}",
+                self.max_rows
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub(crate) fn check_time_span(&self, start: Timestamp, end: Timestamp) -> RrdResult<()> {
+        // A reversed range is an error for librrd to report
+        let span = (end - start).to_std().unwrap_or_default();
+        if span > self.max_time_span {
+            Err(violation(format!(
+                "Time span of {}s is more than the maximum {}s",
+                span.as_secs(),
+                self.max_time_span.as_secs()
+            )))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Returns the canonical form of `rrd`, if it's in the root directory.
+    pub(crate) fn confine(&self, rrd: &Path) -> RrdResult<PathBuf> {
+        // Don't include the canonical path in errors, as it could reveal where a symlink points
+        let not_allowed = || violation(format!("RRD {rrd:?} is not in the allowed directory"));
+        let canonical = self
+            .root
+            .join(rrd)
+            .canonicalize()
+            .map_err(|_| not_allowed())?;
+        if canonical.starts_with(&self.root) && canonical.is_file() {
+            Ok(canonical)
+        } else {
+            Err(not_allowed())
+        }
+    }
+}
+
+/// The start and end `librrd` will use for `props`.
+fn graph_time_range(props: &GraphProps) -> (Timestamp, Timestamp) {
+    let end = props.time_range.end.unwrap_or_else(chrono::Utc::now);
+    let start = props.time_range.start.unwrap_or(end - DEFAULT_SPAN);
+    (start, end)
+}
+
+fn violation(message: String) -> RrdError {
+    RrdError::PolicyViolation(message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ops::graph::{
+            elements::{Line, VarName},
+            props::{FontParams, FontTag, Zoom},
+        },
+        ConsolidationFn,
+    };
+    use std::fs;
+
+    fn def(rrd: impl Into<PathBuf>) -> GraphElement {
+        def_struct(rrd).into()
+    }
+
+    fn def_struct(rrd: impl Into<PathBuf>) -> Def {
+        Def {
+            var_name: VarName::new("v").unwrap(),
+            rrd: rrd.into(),
+            ds_name: "ds".to_string(),
+            consolidation_fn: ConsolidationFn::Avg,
+            step: None,
+            start: None,
+            end: None,
+            reduce: None,
+        }
+    }
+
+    fn line() -> GraphElement {
+        Line {
+            width: 1.0,
+            value: VarName::new("v").unwrap(),
+            color: None,
+            stack: false,
+            skip_scale: false,
+            dashes: None,
+        }
+        .into()
+    }
+
+    fn assert_violation<T: std::fmt::Debug>(result: RrdResult<T>) {
+        assert!(
+            matches!(result, Err(RrdError::PolicyViolation(_))),
+            "{result:?}"
+        );
+    }
+
+    #[test]
+    fn confines_paths() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/ok.rrd"), b"").unwrap();
+        fs::write(tempdir.path().join("secret.rrd"), b"").unwrap();
+        let policy = GraphPolicy::new(&root).unwrap();
+        let props = GraphProps::default();
+
+        let canonical = policy.root().join("sub/ok.rrd");
+        for allowed in [PathBuf::from("sub/ok.rrd"), root.join("sub/../sub/ok.rrd")] {
+            let elements = policy.apply(&props, &[def(allowed), line()]).unwrap();
+            assert_eq!(def(canonical.clone()), elements[0]);
+        }
+
+        for denied in [
+            tempdir.path().join("secret.rrd"),
+            PathBuf::from("../secret.rrd"),
+            PathBuf::from("sub/missing.rrd"),
+            PathBuf::from("sub"),
+        ] {
+            assert_violation(policy.apply(&props, &[def(denied), line()]));
+        }
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(tempdir.path().join("secret.rrd"), root.join("link.rrd"))
+                .unwrap();
+            assert_violation(policy.apply(&props, &[def("link.rrd"), line()]));
+        }
+    }
+
+    #[test]
+    fn caps_props() {
+        let tempdir = tempfile::tempdir().unwrap();
+        fs::write(tempdir.path().join("ok.rrd"), b"").unwrap();
+        let policy = GraphPolicy::new(tempdir.path()).unwrap();
+        let elements = [def("ok.rrd"), line()];
+        let check = |props: GraphProps| policy.apply(&props, &elements);
+
+        check(GraphProps::default()).unwrap();
+
+        let mut props = GraphProps::default();
+        props.size.width = Some(100_000);
+        assert_violation(check(props));
+
+        let mut props = GraphProps::default();
+        props.size.height = Some(100_000);
+        assert_violation(check(props));
+
+        let mut props = GraphProps::default();
+        props.misc.zoom = Some(Zoom::new(100.0).unwrap());
+        assert_violation(check(props));
+
+        let mut props = GraphProps::default();
+        props.misc.fonts.insert(
+            FontTag::Title,
+            FontParams {
+                size: 10,
+                font: Some("/etc/passwd".to_string()),
+            },
+        );
+        assert_violation(check(props));
+
+        let mut props = GraphProps::default();
+        props.misc.fonts.insert(
+            FontTag::Title,
+            FontParams {
+                size: 10_000,
+                font: None,
+            },
+        );
+        assert_violation(check(props));
+
+        let mut props = GraphProps::default();
+        props.time_range.start = Some(Timestamp::from_timestamp(0, 0).unwrap());
+        assert_violation(check(props));
+    }
+
+    #[test]
+    fn caps_elements() {
+        let tempdir = tempfile::tempdir().unwrap();
+        fs::write(tempdir.path().join("ok.rrd"), b"").unwrap();
+        let policy = GraphPolicy {
+            max_elements: 3,
+            ..GraphPolicy::new(tempdir.path()).unwrap()
+        };
+        let props = GraphProps::default();
+
+        policy
+            .apply(&props, &[def("ok.rrd"), line(), line()])
+            .unwrap();
+        assert_violation(policy.apply(&props, &[def("ok.rrd"), line(), line(), line()]));
+
+        let mut long_def = def_struct("ok.rrd");
+        long_def.start = Some(Timestamp::from_timestamp(0, 0).unwrap());
+        assert_violation(policy.apply(&props, &[long_def.into(), line()]));
+    }
+
+    #[test]
+    fn caps_rows() {
+        let tempdir = tempfile::tempdir().unwrap();
+        fs::write(tempdir.path().join("ok.rrd"), b"").unwrap();
+        let policy = GraphPolicy {
+            max_rows: 1440,
+            ..GraphPolicy::new(tempdir.path()).unwrap()
+        };
+        // the default span is a day
+        let stepped = |step| {
+            let mut def = def_struct("ok.rrd");
+            def.step = Some(step);
+            def.into()
+        };
+        let props = GraphProps::default();
+
+        policy.apply(&props, &[def("ok.rrd"), line()]).unwrap();
+        policy.apply(&props, &[stepped(60), line()]).unwrap();
+        assert_violation(policy.apply(&props, &[stepped(59), line()]));
+        assert_violation(policy.apply(&props, &[stepped(0), line()]));
+
+        let mut props = GraphProps::default();
+        props.time_range.step_seconds = Some(60);
+        policy.apply(&props, &[def("ok.rrd"), line()]).unwrap();
+        // a Def's own step is used instead of the graph's
+        assert_violation(policy.apply(&props, &[stepped(59), line()]));
+        props.time_range.step_seconds = Some(1);
+        assert_violation(policy.apply(&props, &[def("ok.rrd"), line()]));
+    }
+}
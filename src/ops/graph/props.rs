@@ -3,7 +3,7 @@
 use crate::error::{InvalidArgument, RrdResult};
 use crate::ops::graph::Color;
 use crate::{ops::graph::AppendArgs, Timestamp};
-use std::collections;
+use std::{collections, str::FromStr};
 
 /// Top level graph properties.
 ///
@@ -26,6 +26,8 @@ use std::collections;
 /// ```
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct GraphProps {
     pub time_range: TimeRange,
     pub labels: Labels,
@@ -61,6 +63,8 @@ impl AppendArgs for GraphProps {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct TimeRange {
     pub start: Option<Timestamp>,
     pub end: Option<Timestamp>,
@@ -90,6 +94,8 @@ impl AppendArgs for TimeRange {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Labels {
     pub title: Option<String>,
     pub vertical_label: Option<String>,
@@ -115,6 +121,8 @@ impl AppendArgs for Labels {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Size {
     pub width: Option<u32>,
     pub height: Option<u32>,
@@ -150,6 +158,8 @@ impl AppendArgs for Size {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Limits {
     pub upper_limit: Option<f64>,
     pub lower_limit: Option<f64>,
@@ -180,16 +190,16 @@ impl AppendArgs for Limits {
         }
 
         if let Some(aa) = &self.alt_autoscale {
-            args.push("--alt-autoscale".to_string());
+            if !aa.alt_autoscale_min && !aa.alt_autoscale_max {
+                args.push("--alt-autoscale".to_string());
+            }
 
-            if let Some(min) = aa.alt_autoscale_min {
+            if aa.alt_autoscale_min {
                 args.push("--alt-autoscale-min".to_string());
-                args.push(format!("{min}"));
             }
 
-            if let Some(max) = aa.alt_autoscale_max {
+            if aa.alt_autoscale_max {
                 args.push("--alt-autoscale-max".to_string());
-                args.push(format!("{max}"));
             }
         }
 
@@ -201,14 +211,20 @@ impl AppendArgs for Limits {
     }
 }
 
-/// Limits for alternate autoscale algorithm.
+/// Which limits the alternate autoscale algorithm adjusts.
+///
+/// With neither set, it's `--alt-autoscale`, which adjusts both limits. Otherwise it's
+/// `--alt-autoscale-min` and/or `--alt-autoscale-max`, which adjust just those limits.
 ///
 /// See [`Limits`]
 #[derive(Default, Debug, Clone, PartialEq)]
-#[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct AltAutoscale {
-    pub alt_autoscale_min: Option<f64>,
-    pub alt_autoscale_max: Option<f64>,
+    /// Adjust the lower limit
+    pub alt_autoscale_min: bool,
+    /// Adjust the upper limit
+    pub alt_autoscale_max: bool,
 }
 
 /// X axis format.
@@ -216,6 +232,8 @@ pub struct AltAutoscale {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct XAxis {
     pub grid: Option<XAxisGrid>,
     pub week_format: Option<String>,
@@ -259,6 +277,7 @@ impl AppendArgs for XAxis {
 /// See [`GraphProps`]
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum XAxisGrid {
     None,
     Custom {
@@ -278,6 +297,7 @@ pub enum XAxisGrid {
 /// See [`GraphProps`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AxisGridTimeUnit {
     Second,
     Minute,
@@ -307,6 +327,8 @@ impl AxisGridTimeUnit {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct YAxis {
     pub grid: Option<YAxisGrid>,
     pub formatter: Option<YAxisFormatter>,
@@ -380,6 +402,7 @@ impl AppendArgs for YAxis {
 /// See [`GraphProps`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum YAxisGrid {
     None,
     Custom { grid_step: u32, label_factor: u32 },
@@ -390,6 +413,7 @@ pub enum YAxisGrid {
 /// See [`GraphProps`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum YAxisFormatter {
     Numeric,
     Timestamp,
@@ -415,6 +439,8 @@ impl AppendArgs for YAxisFormatter {
 ///
 /// See [`GraphProps`]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "i8", into = "i8"))]
 pub struct UnitsExponent {
     /// Must be a multiple of 3.
     pub exp: i8,
@@ -425,6 +451,7 @@ pub struct UnitsExponent {
 /// See [`GraphProps`]
 #[derive(Debug, Clone, Copy, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Units {
     Si,
 }
@@ -440,11 +467,26 @@ impl UnitsExponent {
     }
 }
 
+impl TryFrom<i8> for UnitsExponent {
+    type Error = InvalidArgument;
+
+    fn try_from(value: i8) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<UnitsExponent> for i8 {
+    fn from(value: UnitsExponent) -> Self {
+        value.exp
+    }
+}
+
 /// Right y axis format.
 ///
 /// See [`GraphProps`]
 #[derive(Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct RightYAxis {
     pub scale: f64,
     pub shift: u32,
@@ -482,6 +524,8 @@ impl AppendArgs for RightYAxis {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Legend {
     pub no_legend: bool,
     pub force_rules_legend: bool,
@@ -529,6 +573,7 @@ impl AppendArgs for Legend {
 /// See [`Legend`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LegendPosition {
     North,
     South,
@@ -541,6 +586,7 @@ pub enum LegendPosition {
 /// See [`Legend`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum LegendDirection {
     TopDown,
     BottomUp,
@@ -552,8 +598,10 @@ pub enum LegendDirection {
 /// See [`GraphProps`]
 #[derive(Default, Debug, Clone, PartialEq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Misc {
-    // Skipping `lazy` as it is inapplicable when generating an in-memory graph
+    // Skipping `lazy` as it only applies when writing a file, so it's a `graph_to_file` param
     // Skipping daemon as we don't support daemons
     // Skipping imginfo as the example usage is an antipattern (no html attr escaping),
     // and seeems better done in Rust logic anyway.
@@ -581,19 +629,7 @@ impl AppendArgs for Misc {
     fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
         for (tag, color) in &self.colors {
             args.push("--color".to_string());
-            let mut s = match tag {
-                ColorTag::Back => "BACK",
-                ColorTag::Canvas => "CANVAS",
-                ColorTag::ShadeA => "SHADEA",
-                ColorTag::ShadeB => "SHADEB",
-                ColorTag::Grid => "GRID",
-                ColorTag::MGrid => "MGRID",
-                ColorTag::Font => "FONT",
-                ColorTag::Axis => "AXIS",
-                ColorTag::Frame => "FRAME",
-                ColorTag::Arrow => "ARROW",
-            }
-            .to_string();
+            let mut s = tag.as_str().to_string();
             color.append_to(&mut s);
             args.push(s);
         }
@@ -619,15 +655,7 @@ impl AppendArgs for Misc {
 
         for (tag, font_params) in &self.fonts {
             args.push("--font".to_string());
-            let tag = match tag {
-                FontTag::Default => "DEFAULT",
-                FontTag::Title => "TITLE",
-                FontTag::Axis => "AXIS",
-                FontTag::Unit => "UNIT",
-                FontTag::Legend => "LEGEND",
-                FontTag::Watermark => "WATERMARK",
-            }
-            .to_string();
+            let tag = tag.as_str();
             args.push(match &font_params.font {
                 None => format!("{tag}:{}", font_params.size),
                 Some(f) => format!("{tag}:{}:{f}", font_params.size),
@@ -687,6 +715,7 @@ impl AppendArgs for Misc {
 /// See [`Misc`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ColorTag {
     Back,
     Canvas,
@@ -700,10 +729,55 @@ pub enum ColorTag {
     Arrow,
 }
 
+impl ColorTag {
+    const ALL: [ColorTag; 10] = [
+        ColorTag::Back,
+        ColorTag::Canvas,
+        ColorTag::ShadeA,
+        ColorTag::ShadeB,
+        ColorTag::Grid,
+        ColorTag::MGrid,
+        ColorTag::Font,
+        ColorTag::Axis,
+        ColorTag::Frame,
+        ColorTag::Arrow,
+    ];
+
+    /// The tag's name in `--color` args, e.g. `SHADEA`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ColorTag::Back => "BACK",
+            ColorTag::Canvas => "CANVAS",
+            ColorTag::ShadeA => "SHADEA",
+            ColorTag::ShadeB => "SHADEB",
+            ColorTag::Grid => "GRID",
+            ColorTag::MGrid => "MGRID",
+            ColorTag::Font => "FONT",
+            ColorTag::Axis => "AXIS",
+            ColorTag::Frame => "FRAME",
+            ColorTag::Arrow => "ARROW",
+        }
+    }
+}
+
+/// Parses the name returned by `as_str()`.
+impl FromStr for ColorTag {
+    type Err = InvalidArgument;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|tag| tag.as_str() == s)
+            .ok_or(InvalidArgument("Unknown color tag"))
+    }
+}
+
 /// Zoom level.
 ///
 /// See [`Misc`]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "f64", into = "f64"))]
 pub struct Zoom {
     zoom: f64,
 }
@@ -719,11 +793,26 @@ impl Zoom {
     }
 }
 
+impl TryFrom<f64> for Zoom {
+    type Error = InvalidArgument;
+
+    fn try_from(value: f64) -> Result<Self, Self::Error> {
+        Self::new(value)
+    }
+}
+
+impl From<Zoom> for f64 {
+    fn from(value: Zoom) -> Self {
+        value.zoom
+    }
+}
+
 /// Font size and name.
 ///
 /// See [`Misc`]
 #[derive(Debug, Clone, PartialEq, Eq)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FontParams {
     pub size: u32,
     pub font: Option<String>,
@@ -734,6 +823,7 @@ pub struct FontParams {
 /// See [`Misc`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FontTag {
     Default,
     Title,
@@ -743,11 +833,47 @@ pub enum FontTag {
     Watermark,
 }
 
+impl FontTag {
+    const ALL: [FontTag; 6] = [
+        FontTag::Default,
+        FontTag::Title,
+        FontTag::Axis,
+        FontTag::Unit,
+        FontTag::Legend,
+        FontTag::Watermark,
+    ];
+
+    /// The tag's name in `--font` args, e.g. `TITLE`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FontTag::Default => "DEFAULT",
+            FontTag::Title => "TITLE",
+            FontTag::Axis => "AXIS",
+            FontTag::Unit => "UNIT",
+            FontTag::Legend => "LEGEND",
+            FontTag::Watermark => "WATERMARK",
+        }
+    }
+}
+
+/// Parses the name returned by `as_str()`.
+impl FromStr for FontTag {
+    type Err = InvalidArgument;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|tag| tag.as_str() == s)
+            .ok_or(InvalidArgument("Unknown font tag"))
+    }
+}
+
 /// Font render mode.
 ///
 /// See [`Misc`]
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FontRenderMode {
     Normal,
     Light,
@@ -775,6 +901,7 @@ impl AppendArgs for FontRenderMode {
 /// See [`Misc`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum GraphRenderMode {
     Normal,
     Mono,
@@ -800,12 +927,13 @@ impl AppendArgs for GraphRenderMode {
 /// See [`Misc`]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ImageFormat {
     Png,
     Svg,
     Eps,
     Pdf,
-    // skipping non-image export formats
+    // data export formats are in `DataFormat`, since they produce data rather than an image
 }
 
 impl AppendArgs for ImageFormat {
@@ -825,11 +953,69 @@ impl AppendArgs for ImageFormat {
     }
 }
 
+/// Data export format, rather than an image, for [`graph_data`](super::graph_data).
+///
+/// The exported data is the graph's data after `CDEF` and `VDEF` processing: a column for each
+/// [`Line`](super::elements::Line), [`Area`](super::elements::Area) and
+/// [`Tick`](super::elements::Tick), named after its legend.
+///
+/// See <https://oss.oetiker.ch/rrdtool/doc/rrdgraph.en.html>
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DataFormat {
+    /// Comma separated values
+    Csv,
+    /// Tab separated values
+    Tsv,
+    /// Semicolon separated values
+    Ssv,
+    /// JSON
+    Json,
+    /// JSON, with a timestamp in each row
+    JsonTime,
+    /// XML, like `rrdtool xport`
+    Xml,
+    /// XML, with enumerated value tags (`<v0>`, `<v1>`, ...) in each row
+    XmlEnum,
+}
+
+impl AppendArgs for DataFormat {
+    fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        args.push("--imgformat".to_string());
+        args.push(
+            match self {
+                DataFormat::Csv => "CSV",
+                DataFormat::Tsv => "TSV",
+                DataFormat::Ssv => "SSV",
+                DataFormat::Json => "JSON",
+                DataFormat::JsonTime => "JSONTIME",
+                DataFormat::Xml => "XML",
+                DataFormat::XmlEnum => "XMLENUM",
+            }
+            .to_string(),
+        );
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use itertools::Itertools;
 
+    #[test]
+    fn tag_names_round_trip() {
+        for tag in ColorTag::ALL {
+            assert_eq!(Ok(tag), tag.as_str().parse());
+        }
+        for tag in FontTag::ALL {
+            assert_eq!(Ok(tag), tag.as_str().parse());
+        }
+        assert!("shadea".parse::<ColorTag>().is_err());
+        assert!("LABEL".parse::<FontTag>().is_err());
+    }
+
     // at least a baseline check that some sane args are produced
     #[test]
     fn everything_set() {
@@ -855,8 +1041,8 @@ mod tests {
                 rigid: true,
                 allow_shrink: true,
                 alt_autoscale: Some(AltAutoscale {
-                    alt_autoscale_min: Some(1.1),
-                    alt_autoscale_max: Some(2.2),
+                    alt_autoscale_min: true,
+                    alt_autoscale_max: true,
                 }),
                 no_grid_fit: true,
             },
@@ -959,11 +1145,8 @@ mod tests {
             "1",
             "--rigid",
             "--allow-shrink",
-            "--alt-autoscale",
             "--alt-autoscale-min",
-            "1.1",
             "--alt-autoscale-max",
-            "2.2",
             "--no-gridfit",
             // x axis
             "--x-grid",
@@ -0,0 +1,329 @@
+//! Graphs generated from an RRD's structure, for a quick look at its data.
+
+use crate::{
+    error::{RrdError, RrdResult},
+    ops::{
+        graph::{
+            elements::{
+                Area, AreaColor, ColorWithLegend, Comment, Def, Directive, GPrint, GraphElement,
+                Legend, Line, PrintFormat, TextControl, VDef, VarName,
+            },
+            props::{GraphProps, Labels, Misc, Size, TimeRange},
+            theme::Theme,
+        },
+        info::{self, InfoValue},
+    },
+    ConsolidationFn,
+};
+use std::{collections, path::Path};
+
+/// Returns a graph of every DS in the RRD at `path`, over `time_range`.
+///
+/// The graph has a line for each DS, or an area if there's only one, colored from
+/// [`Theme::light`]'s palette, and a legend table with the last, average and max value of each.
+/// The result can be customized before it's passed to [`graph`](super::graph).
+///
+/// # Examples
+///
+/// ```no_run
+/// use rrd::ops::graph::{self, props};
+/// use std::path::Path;
+///
+/// let (props, elements) = graph::quick_graph(Path::new("data.rrd"), props::TimeRange::default())?;
+/// let (image, _metadata) = graph::graph(props::ImageFormat::Png, props, &elements)?;
+/// # Ok::<(), rrd::error::RrdError>(())
+/// ```
+pub fn quick_graph(
+    path: &Path,
+    time_range: TimeRange,
+) -> RrdResult<(GraphProps, Vec<GraphElement>)> {
+    quick_graph_with_theme(path, time_range, &Theme::light())
+}
+
+/// Like [`quick_graph`], with colors and fonts from `theme`.
+pub fn quick_graph_with_theme(
+    path: &Path,
+    time_range: TimeRange,
+    theme: &Theme,
+) -> RrdResult<(GraphProps, Vec<GraphElement>)> {
+    let info = info::info(path)?;
+    build(path, &info, time_range, theme)
+}
+
+/// Build the graph from the RRD's `info`.
+fn build(
+    path: &Path,
+    info: &collections::HashMap<String, InfoValue>,
+    time_range: TimeRange,
+    theme: &Theme,
+) -> RrdResult<(GraphProps, Vec<GraphElement>)> {
+    let ds_names = ds_names(info)?;
+    if ds_names.is_empty() {
+        return Err(RrdError::InvalidArgument(format!(
+            "RRD {path:?} has no data sources"
+        )));
+    }
+    let consolidation_fn = consolidation_fn(info)?;
+
+    let mut props = GraphProps {
+        time_range,
+        labels: Labels {
+            title: path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            vertical_label: None,
+        },
+        size: Size {
+            width: Some(600),
+            height: Some(200),
+            ..Default::default()
+        },
+        misc: Misc {
+            slope_mode: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    theme.apply(&mut props);
+
+    let name_width = ds_names.iter().map(|n| n.len()).max().unwrap_or_default();
+    // Each value is 9 characters wide: `%8.2lf` and a unit prefix, or a space
+    let mut elements = vec![Comment {
+        text: Legend::new(format!(
+            "{:name_width$}  {:>9}{:>9}{:>9}",
+            "", "Last", "Average", "Max"
+        ))
+        .with_control(TextControl::Newline),
+    }
+    .into()];
+
+    let single = ds_names.len() == 1;
+    for (i, ds_name) in ds_names.into_iter().enumerate() {
+        let var_name = VarName::new(format!("ds{i}"))?;
+        // A line without a color is invisible, so fall back to red if the palette is empty
+        let color = theme
+            .series_color(i)
+            .unwrap_or_else(|| "#FF0000".parse().unwrap());
+        let legend = Some(Legend::new(format!("{ds_name:name_width$}")));
+
+        elements.push(
+            Def {
+                var_name: var_name.clone(),
+                rrd: path.to_path_buf(),
+                ds_name,
+                consolidation_fn,
+                step: None,
+                start: None,
+                end: None,
+                reduce: None,
+            }
+            .into(),
+        );
+        elements.push(if single {
+            Area {
+                value: var_name.clone(),
+                color: Some(ColorWithLegend {
+                    color: AreaColor::Color(color),
+                    legend,
+                }),
+                stack: false,
+                skip_scale: false,
+            }
+            .into()
+        } else {
+            Line {
+                width: 1.5,
+                value: var_name.clone(),
+                color: Some(ColorWithLegend { color, legend }),
+                stack: false,
+                skip_scale: false,
+                dashes: None,
+            }
+            .into()
+        });
+
+        let stats = [("last", "LAST"), ("avg", "AVERAGE"), ("max", "MAXIMUM")];
+        for (n, (suffix, function)) in stats.into_iter().enumerate() {
+            let stat_name = VarName::new(format!("ds{i}_{suffix}"))?;
+            elements.push(
+                VDef {
+                    var_name: stat_name.clone(),
+                    rpn: format!("ds{i},{function}"),
+                }
+                .into(),
+            );
+            let mut format = PrintFormat::default()
+                .directive(Directive::value(8, 2))
+                .directive(Directive::unit());
+            if n == stats.len() - 1 {
+                format = format.control(TextControl::Newline);
+            }
+            elements.push(
+                GPrint {
+                    var_name: stat_name,
+                    format,
+                }
+                .into(),
+            );
+        }
+    }
+
+    Ok((props, elements))
+}
+
+/// The DS names in `info`, in the order they're defined in the RRD.
+fn ds_names(info: &collections::HashMap<String, InfoValue>) -> RrdResult<Vec<String>> {
+    let mut indexed = info
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix("ds[")?.strip_suffix("].index")?;
+            Some((name, value))
+        })
+        .map(|(name, value)| match value {
+            InfoValue::Count(index) => Ok((*index, name.to_string())),
+            _ => Err(RrdError::Internal(format!(
+                "Info: unexpected ds[{name}].index value type"
+            ))),
+        })
+        .collect::<RrdResult<Vec<_>>>()?;
+    indexed.sort();
+    Ok(indexed.into_iter().map(|(_, name)| name).collect())
+}
+
+/// `AVERAGE` if the RRD has an RRA for it, otherwise the function of the first RRA.
+fn consolidation_fn(info: &collections::HashMap<String, InfoValue>) -> RrdResult<ConsolidationFn> {
+    let mut cfs = info
+        .iter()
+        .filter_map(|(key, value)| {
+            let index = key.strip_prefix("rra[")?.strip_suffix("].cf")?;
+            Some((index.parse::<usize>().ok()?, value))
+        })
+        .map(|(index, value)| {
+            let cf = match value {
+                InfoValue::String(s) => match s.as_str() {
+                    "AVERAGE" => Some(ConsolidationFn::Avg),
+                    "MIN" => Some(ConsolidationFn::Min),
+                    "MAX" => Some(ConsolidationFn::Max),
+                    "LAST" => Some(ConsolidationFn::Last),
+                    // e.g. HWPREDICT, which can't be graphed with a plain DEF
+                    _ => None,
+                },
+                _ => {
+                    return Err(RrdError::Internal(format!(
+                        "Info: unexpected rra[{index}].cf value type"
+                    )))
+                }
+            };
+            Ok((index, cf))
+        })
+        .collect::<RrdResult<Vec<_>>>()?;
+    cfs.sort_by_key(|(index, _)| *index);
+
+    let cfs = cfs.into_iter().filter_map(|(_, cf)| cf).collect::<Vec<_>>();
+    if cfs.contains(&ConsolidationFn::Avg) {
+        Ok(ConsolidationFn::Avg)
+    } else {
+        cfs.first()
+            .copied()
+            .ok_or_else(|| RrdError::InvalidArgument("RRD has no graphable RRAs".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::graph::graph_args;
+
+    fn info(entries: &[(&str, InfoValue)]) -> collections::HashMap<String, InfoValue> {
+        entries
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn graphs_every_ds_in_order() {
+        let info = info(&[
+            ("ds[out].index", InfoValue::Count(1)),
+            ("ds[in].index", InfoValue::Count(0)),
+            ("ds[in].type", InfoValue::String("COUNTER".to_string())),
+            ("rra[0].cf", InfoValue::String("MAX".to_string())),
+            ("rra[1].cf", InfoValue::String("AVERAGE".to_string())),
+        ]);
+        let (props, elements) = build(
+            Path::new("/tmp/traffic.rrd"),
+            &info,
+            TimeRange::default(),
+            &Theme::light(),
+        )
+        .unwrap();
+        assert_eq!(Some("traffic.rrd"), props.labels.title.as_deref());
+
+        let args = graph_args(None, props, &elements).unwrap();
+        let elements_args = args
+            .iter()
+            .skip_while(|a| !a.starts_with("COMMENT:"))
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                r"COMMENT:          Last  Average      Max\n",
+                "DEF:ds0=/tmp/traffic.rrd:in:AVERAGE",
+                "LINE1.5:ds0#4E79A7:in ",
+                "VDEF:ds0_last=ds0,LAST",
+                "GPRINT:ds0_last:%8.2lf%s",
+                "VDEF:ds0_avg=ds0,AVERAGE",
+                "GPRINT:ds0_avg:%8.2lf%s",
+                "VDEF:ds0_max=ds0,MAXIMUM",
+                r"GPRINT:ds0_max:%8.2lf%s\n",
+                "DEF:ds1=/tmp/traffic.rrd:out:AVERAGE",
+                "LINE1.5:ds1#F28E2B:out",
+                "VDEF:ds1_last=ds1,LAST",
+                "GPRINT:ds1_last:%8.2lf%s",
+                "VDEF:ds1_avg=ds1,AVERAGE",
+                "GPRINT:ds1_avg:%8.2lf%s",
+                "VDEF:ds1_max=ds1,MAXIMUM",
+                r"GPRINT:ds1_max:%8.2lf%s\n",
+            ],
+            elements_args
+        );
+    }
+
+    #[test]
+    fn single_ds_is_an_area() {
+        let info = info(&[
+            ("ds[temp].index", InfoValue::Count(0)),
+            ("rra[0].cf", InfoValue::String("HWPREDICT".to_string())),
+            ("rra[1].cf", InfoValue::String("MAX".to_string())),
+        ]);
+        let (_, elements) = build(
+            Path::new("temp.rrd"),
+            &info,
+            TimeRange::default(),
+            &Theme::dark(),
+        )
+        .unwrap();
+        assert!(matches!(
+            &elements[1],
+            GraphElement::Def(Def {
+                consolidation_fn: ConsolidationFn::Max,
+                ..
+            })
+        ));
+        assert!(matches!(&elements[2], GraphElement::Area(_)));
+    }
+
+    #[test]
+    fn rejects_rrd_without_data_sources() {
+        let info = info(&[("rra[0].cf", InfoValue::String("AVERAGE".to_string()))]);
+        assert!(matches!(
+            build(
+                Path::new("empty.rrd"),
+                &info,
+                TimeRange::default(),
+                &Theme::light()
+            ),
+            Err(RrdError::InvalidArgument(_))
+        ));
+    }
+}
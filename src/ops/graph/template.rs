@@ -0,0 +1,662 @@
+//! Process `rrdcgi` templates: HTML pages with `<RRD::...>` tags that render graphs.
+//!
+//! Supported tags:
+//!
+//! - `<RRD::GRAPH file args...>` renders a graph from `rrdtool graph` args (see
+//!   [`parse`](super::parse)), and is replaced by an image reference for `file`. The reference is
+//!   built from `--imginfo` (default `<IMG SRC="%s" WIDTH="%lu" HEIGHT="%lu">`), where `%s` is the
+//!   file name without its directory. `--lazy` is accepted and ignored, since graphs are always
+//!   rendered.
+//! - `<RRD::PRINT n>` is replaced by the output of the `n`th `PRINT` (from 0) of the previous
+//!   `<RRD::GRAPH>`.
+//! - `<RRD::SETVAR name value>` sets a variable, and `<RRD::GETVAR name>` is replaced by its value.
+//! - `<RRD::TIME::NOW format>` is replaced by the current local time, formatted with `strftime`
+//!   syntax.
+//! - `<RRD::INCLUDE file>` is replaced by the file's contents, which are processed as a template.
+//!   The file must be in [`TemplateProcessor::include_dir`].
+//!
+//! Tags can be nested, e.g. `<RRD::GRAPH <RRD::GETVAR name>.png ...>`, and are evaluated in the
+//! order they appear. Tag args are separated by whitespace, and can be quoted with `"` or `'`.
+//!
+//! Unlike `rrdcgi`, a tag's output isn't processed again (except for included files), so variable
+//! values can't inject tags. `<RRD::GETVAR>` and `<RRD::PRINT>` output is HTML-escaped, except
+//! inside another tag's args, where it's used as is. The `%s` file name in `--imginfo` is
+//! HTML-escaped too. Untrusted values can still pick the RRDs a `<RRD::GRAPH>` reads,
+//! so set [`TemplateProcessor::policy`] when variables come from requests.
+//!
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrdcgi.en.html>.
+
+use crate::{
+    error::{RrdError, RrdResult},
+    ops::graph::{
+        self, elements::GraphElement, policy::GraphPolicy, props::GraphProps, props::ImageFormat,
+        GraphCommand, GraphMetadata,
+    },
+    Timestamp,
+};
+use std::{
+    collections, fs,
+    path::{Path, PathBuf},
+};
+
+/// Processes `rrdcgi` templates. See the [module docs](self) for the supported tags.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rrd::ops::graph::{policy::GraphPolicy, template::TemplateProcessor};
+///
+/// # let host_param = "db1";
+/// // `host` comes from the request, so only allow RRDs in /var/lib/rrd
+/// let mut processor = TemplateProcessor {
+///     policy: Some(GraphPolicy::new("/var/lib/rrd")?),
+///     ..Default::default()
+/// };
+/// processor.vars.insert("host".to_string(), host_param.to_string());
+/// let page = processor.process(
+///     r#"<H1>Load on <RRD::GETVAR host></H1>
+/// <RRD::GRAPH load.png --imginfo '<IMG SRC="/graphs/%s">'
+///     DEF:load=<RRD::GETVAR host>-load.rrd:load:AVERAGE LINE2:load#FF0000:Load>"#,
+/// )?;
+/// for graph in &page.graphs {
+///     // serve at /graphs/load.png
+///     println!("{:?}: {} bytes", graph.path, graph.image.len());
+/// }
+/// # Ok::<(), rrd::error::RrdError>(())
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TemplateProcessor {
+    /// Initial variables for `<RRD::GETVAR>`, e.g. from request params. See the
+    /// [module docs](self) for how they're escaped.
+    pub vars: collections::HashMap<String, String>,
+    /// Directory that `<RRD::INCLUDE>` files must be in, and that relative paths are resolved
+    /// against, or the current directory if `None`
+    pub include_dir: Option<PathBuf>,
+    /// If set, graphs are checked and rendered with [`GraphPolicy::graph`]
+    pub policy: Option<GraphPolicy>,
+    /// The time for `<RRD::TIME::NOW>`, or the current time if `None`
+    pub now: Option<Timestamp>,
+}
+
+/// The output of [`TemplateProcessor::process`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedPage {
+    /// The template with its tags replaced
+    pub html: String,
+    /// The graphs rendered for `<RRD::GRAPH>` tags, in order
+    pub graphs: Vec<RenderedGraph>,
+}
+
+/// A graph rendered for an `<RRD::GRAPH>` tag.
+///
+/// `rrdcgi` writes the image to `path`, but it's returned here, to be served or written as needed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RenderedGraph {
+    /// The file name in the tag.
+    ///
+    /// This can come from variables, so it's untrusted: don't join it onto an output directory
+    /// without checking that the result stays in the directory.
+    pub path: PathBuf,
+    #[allow(missing_docs)]
+    pub image_format: ImageFormat,
+    #[allow(missing_docs)]
+    pub image: Vec<u8>,
+    #[allow(missing_docs)]
+    pub metadata: GraphMetadata,
+}
+
+const TAG_START: &str = "<RRD::";
+
+/// `rrdcgi`'s image reference, used when a graph has no `--imginfo`.
+const DEFAULT_IMG_INFO: &str = r#"<IMG SRC="%s" WIDTH="%lu" HEIGHT="%lu">"#;
+
+/// Includes nested deeper than this are assumed to be a loop.
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+impl TemplateProcessor {
+    /// Process `template`, rendering its graphs.
+    pub fn process(&self, template: &str) -> RrdResult<RenderedPage> {
+        self.process_with(template, |image_format, props, elements| {
+            match &self.policy {
+                Some(policy) => policy.graph(image_format, props, elements),
+                None => graph::graph(image_format, props, elements),
+            }
+        })
+    }
+
+    /// Process the template in the file at `path`.
+    pub fn process_file(&self, path: impl AsRef<Path>) -> RrdResult<RenderedPage> {
+        self.process(&read_template(path.as_ref())?)
+    }
+
+    /// Process `template`, rendering graphs with `render`.
+    fn process_with<R>(&self, template: &str, render: R) -> RrdResult<RenderedPage>
+    where
+        R: FnMut(ImageFormat, GraphProps, &[GraphElement]) -> RrdResult<(Vec<u8>, GraphMetadata)>,
+    {
+        let mut run = Run {
+            processor: self,
+            render,
+            vars: self.vars.clone(),
+            graphs: vec![],
+            include_depth: 0,
+        };
+        let html = run.expand(template, false)?;
+        Ok(RenderedPage {
+            html,
+            graphs: run.graphs,
+        })
+    }
+}
+
+/// The state while processing a template.
+struct Run<'a, R> {
+    processor: &'a TemplateProcessor,
+    render: R,
+    vars: collections::HashMap<String, String>,
+    graphs: Vec<RenderedGraph>,
+    include_depth: usize,
+}
+
+impl<R> Run<'_, R>
+where
+    R: FnMut(ImageFormat, GraphProps, &[GraphElement]) -> RrdResult<(Vec<u8>, GraphMetadata)>,
+{
+    /// Returns `text` with its tags replaced by their output. `nested` is whether the output is
+    /// part of another tag's args, rather than HTML.
+    fn expand(&mut self, text: &str, nested: bool) -> RrdResult<String> {
+        let mut output = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find(TAG_START) {
+            output.push_str(&rest[..start]);
+            let (tag_output, len) = self.tag(&rest[start..], nested)?;
+            output.push_str(&tag_output);
+            rest = &rest[start + len..];
+        }
+        output.push_str(rest);
+        Ok(output)
+    }
+
+    /// Evaluate the tag at the start of `text`, returning its output and its length in `text`.
+    fn tag(&mut self, text: &str, nested: bool) -> RrdResult<(String, usize)> {
+        let name_len = text[TAG_START.len()..]
+            .find(|c: char| c.is_whitespace() || c == '>')
+            .unwrap_or(text.len() - TAG_START.len());
+        let name = &text[TAG_START.len()..TAG_START.len() + name_len];
+
+        let mut args = vec![];
+        // `None` between args, so that `""` is an empty arg rather than nothing
+        let mut arg: Option<String> = None;
+        let mut quote = None;
+        let mut i = TAG_START.len() + name_len;
+        loop {
+            let rest = &text[i..];
+            let Some(c) = rest.chars().next() else {
+                return Err(template_error(format!("<RRD::{name}> isn't closed")));
+            };
+            if rest.starts_with(TAG_START) {
+                let (tag_output, len) = self.tag(rest, true)?;
+                arg.get_or_insert_default().push_str(&tag_output);
+                i += len;
+                continue;
+            }
+            match (quote, c) {
+                (None, '>') => {
+                    args.extend(arg.take());
+                    i += 1;
+                    break;
+                }
+                (None, c) if c.is_whitespace() => args.extend(arg.take()),
+                (None, '"' | '\'') => {
+                    quote = Some(c);
+                    arg.get_or_insert_default();
+                }
+                (Some(q), c) if c == q => quote = None,
+                // Only the quote character can be escaped, since rrdtool args use backslashes
+                (Some(q), '\\') if rest[1..].starts_with(q) => {
+                    arg.get_or_insert_default().push(q);
+                    i += 1;
+                }
+                (_, c) => arg.get_or_insert_default().push(c),
+            }
+            i += c.len_utf8();
+        }
+
+        Ok((self.evaluate(name, &args, nested)?, i))
+    }
+
+    fn evaluate(&mut self, name: &str, args: &[String], nested: bool) -> RrdResult<String> {
+        match name {
+            "GRAPH" => self.graph(args),
+            "PRINT" => {
+                let [n] = expect_args(name, args)?;
+                let index = n
+                    .parse::<usize>()
+                    .map_err(|_| template_error(format!("<RRD::PRINT {n}> isn't a number")))?;
+                let prints = &self
+                    .graphs
+                    .last()
+                    .ok_or_else(|| template_error("<RRD::PRINT> must follow <RRD::GRAPH>"))?
+                    .metadata
+                    .prints;
+                let text = prints.get(index).map(|p| &p.text).ok_or_else(|| {
+                    template_error(format!(
+                        "<RRD::PRINT {n}>: the graph has {} PRINTs",
+                        prints.len()
+                    ))
+                })?;
+                Ok(if nested {
+                    text.clone()
+                } else {
+                    escape_html(text)
+                })
+            }
+            "SETVAR" => {
+                let [var, value] = expect_args(name, args)?;
+                self.vars.insert(var.clone(), value.clone());
+                Ok(String::new())
+            }
+            "GETVAR" => {
+                let [var] = expect_args(name, args)?;
+                let value = self
+                    .vars
+                    .get(var)
+                    .ok_or_else(|| template_error(format!("Variable {var:?} isn't set")))?;
+                Ok(if nested {
+                    value.clone()
+                } else {
+                    escape_html(value)
+                })
+            }
+            "TIME::NOW" => {
+                let [format] = expect_args(name, args)?;
+                let items = chrono::format::StrftimeItems::new(format)
+                    .parse()
+                    .map_err(|_| template_error(format!("Invalid time format {format:?}")))?;
+                let now = self.processor.now.unwrap_or_else(chrono::Utc::now);
+                Ok(now
+                    .with_timezone(&chrono::Local)
+                    .format_with_items(items.into_iter())
+                    .to_string())
+            }
+            "INCLUDE" => {
+                let [file] = expect_args(name, args)?;
+                let path = self.include_path(file)?;
+                if self.include_depth == MAX_INCLUDE_DEPTH {
+                    return Err(template_error(format!(
+                        "Includes are nested more than {MAX_INCLUDE_DEPTH} deep at {path:?}"
+                    )));
+                }
+                let template = read_template(&path)?;
+                self.include_depth += 1;
+                let output = self.expand(&template, nested);
+                self.include_depth -= 1;
+                output
+            }
+            _ => Err(template_error(format!("Unsupported tag <RRD::{name}>"))),
+        }
+    }
+
+    /// The canonical path of `file`, which must be in the include directory.
+    fn include_path(&self, file: &str) -> RrdResult<PathBuf> {
+        // Don't include the canonical path in errors, as it could reveal where a symlink points
+        let not_allowed = || {
+            template_error(format!(
+                "<RRD::INCLUDE {file}> is not in the include directory"
+            ))
+        };
+        let dir = match &self.processor.include_dir {
+            Some(dir) => dir.canonicalize(),
+            None => std::env::current_dir().and_then(|dir| dir.canonicalize()),
+        }
+        .map_err(|e| template_error(format!("The include directory is not accessible: {e}")))?;
+        let path = dir.join(file).canonicalize().map_err(|_| not_allowed())?;
+        if path.starts_with(&dir) && path.is_file() {
+            Ok(path)
+        } else {
+            Err(not_allowed())
+        }
+    }
+
+    fn graph(&mut self, args: &[String]) -> RrdResult<String> {
+        let mut img_info = DEFAULT_IMG_INFO.to_string();
+        let mut graph_args = vec!["graph".to_string()];
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                // rrdcgi pages usually avoid re-rendering with `--lazy`, but there's no file here
+                "--lazy" | "-z" => {}
+                "--imginfo" | "-f" => {
+                    img_info = args
+                        .next()
+                        .ok_or_else(|| template_error("<RRD::GRAPH>: --imginfo needs a value"))?
+                        .clone();
+                }
+                other => match other
+                    .strip_prefix("--imginfo=")
+                    .or_else(|| other.strip_prefix("-f"))
+                {
+                    Some(value) => img_info = value.to_string(),
+                    None => graph_args.push(other.to_string()),
+                },
+            }
+        }
+
+        let command = GraphCommand::from_args(&graph_args)
+            .map_err(|e| template_error(format!("<RRD::GRAPH>: {e}")))?;
+        let image_format = command.image_format.unwrap_or(ImageFormat::Png);
+        let (image, metadata) = (self.render)(image_format, command.props, &command.elements)?;
+
+        let file_name = command
+            .output
+            .file_name()
+            .map(|f| f.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let html = format_img_info(
+            &img_info,
+            &file_name,
+            metadata.image_width,
+            metadata.image_height,
+        )?;
+        self.graphs.push(RenderedGraph {
+            path: command.output,
+            image_format,
+            image,
+            metadata,
+        });
+        Ok(html)
+    }
+}
+
+/// Returns `args`, or an error if there aren't `N` of them.
+fn expect_args<'a, const N: usize>(name: &str, args: &'a [String]) -> RrdResult<&'a [String; N]> {
+    args.try_into().map_err(|_| {
+        template_error(format!(
+            "<RRD::{name}> takes {N} args, but has {}",
+            args.len()
+        ))
+    })
+}
+
+/// Format `img_info` like `rrdtool`'s `printf` call: `%s` is the HTML-escaped file name, then two
+/// integer directives are the width and height.
+fn format_img_info(img_info: &str, file_name: &str, width: u64, height: u64) -> RrdResult<String> {
+    let mut output = String::new();
+    let mut directives = 0;
+    let mut rest = img_info;
+    while let Some(i) = rest.find('%') {
+        output.push_str(&rest[..i]);
+        rest = &rest[i + 1..];
+        if let Some(after) = rest.strip_prefix('%') {
+            output.push('%');
+            rest = after;
+            continue;
+        }
+        // Skip length modifiers, e.g. the `l` in `%lu`
+        let conversion = rest
+            .char_indices()
+            .find(|(_, c)| !"lhz".contains(*c))
+            .map(|(i, c)| (c, i + c.len_utf8()));
+        match (directives, conversion) {
+            (0, Some(('s', end))) => {
+                output.push_str(&escape_html(file_name));
+                rest = &rest[end..];
+            }
+            (1 | 2, Some(('u' | 'd' | 'i', end))) => {
+                let size = if directives == 1 { width } else { height };
+                output.push_str(&size.to_string());
+                rest = &rest[end..];
+            }
+            _ => {
+                return Err(template_error(format!(
+                    "--imginfo {img_info:?} must have `%s`, then width and height directives"
+                )))
+            }
+        }
+        directives += 1;
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+fn read_template(path: &Path) -> RrdResult<String> {
+    fs::read_to_string(path).map_err(|e| {
+        RrdError::InvalidArgument(format!("Template file {path:?} can't be read: {e}"))
+    })
+}
+
+/// Returns `text` with the characters that are special in HTML replaced by entities.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn template_error(message: impl Into<String>) -> RrdError {
+    RrdError::InvalidArgument(format!("Template: {}", message.into()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ops::graph::PrintEntry;
+
+    fn metadata(prints: &[&str]) -> GraphMetadata {
+        let time = Timestamp::from_timestamp(0, 0).unwrap();
+        GraphMetadata {
+            graph_left: 0,
+            graph_top: 0,
+            graph_width: 400,
+            graph_height: 100,
+            graph_start: time,
+            graph_end: time,
+            image_width: 481,
+            image_height: 141,
+            value_min: 0.0,
+            value_max: 0.0,
+            prints: prints
+                .iter()
+                .enumerate()
+                .map(|(element_index, text)| PrintEntry {
+                    element_index,
+                    text: text.to_string(),
+                })
+                .collect(),
+            legends: vec![],
+            extra_info: Default::default(),
+        }
+    }
+
+    /// Process `template`, with each graph returning a `PRINT` of its title
+    fn process(processor: &TemplateProcessor, template: &str) -> RrdResult<RenderedPage> {
+        processor.process_with(template, |_, props, elements| {
+            assert!(!elements.is_empty());
+            let title = props.labels.title.unwrap_or_default();
+            Ok((vec![1, 2, 3], metadata(&[&title])))
+        })
+    }
+
+    const GRAPH: &str = "DEF:v=data.rrd:ds:AVERAGE LINE1:v#FF0000";
+
+    #[test]
+    fn graph_tags() {
+        let page = process(
+            &TemplateProcessor::default(),
+            &format!(
+                "<P><RRD::GRAPH img/a.png --lazy --title 'First graph' {GRAPH}></P>\n\
+                 <RRD::PRINT 0>\n\
+                 <RRD::GRAPH b.svg --imgformat SVG --title=\"Say \\\"hi\\\"\" \
+                 --imginfo '<img src=\"/g/%s\" width=%lu height=%lu alt=\"100%%\">' {GRAPH}>\n\
+                 <RRD::PRINT 0>"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            "<P><IMG SRC=\"a.png\" WIDTH=\"481\" HEIGHT=\"141\"></P>\n\
+             First graph\n\
+             <img src=\"/g/b.svg\" width=481 height=141 alt=\"100%\">\n\
+             Say &quot;hi&quot;",
+            page.html
+        );
+        assert_eq!(
+            vec![
+                (PathBuf::from("img/a.png"), ImageFormat::Png),
+                (PathBuf::from("b.svg"), ImageFormat::Svg)
+            ],
+            page.graphs
+                .iter()
+                .map(|g| (g.path.clone(), g.image_format))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(vec![1, 2, 3], page.graphs[0].image);
+    }
+
+    #[test]
+    fn variables_and_nesting() {
+        let mut processor = TemplateProcessor::default();
+        processor.vars.insert("host".to_string(), "db1".to_string());
+        let page = process(
+            &processor,
+            &format!(
+                "<RRD::SETVAR title \"<RRD::GETVAR host> load\">\
+                 <RRD::GRAPH <RRD::GETVAR host>.png -t <RRD::GETVAR title> {GRAPH}>\
+                 [<RRD::PRINT 0>] [<RRD::GETVAR title>]"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            r#"<IMG SRC="db1.png" WIDTH="481" HEIGHT="141">[db1 load] [db1 load]"#,
+            page.html
+        );
+
+        // values are HTML-escaped, not processed as tags, but are used as is in args
+        processor
+            .vars
+            .insert("html".to_string(), "<RRD::GETVAR host>".to_string());
+        assert_eq!(
+            "&lt;RRD::GETVAR host&gt;",
+            process(&processor, "<RRD::GETVAR html>").unwrap().html
+        );
+    }
+
+    #[test]
+    fn html_escaping() {
+        let mut processor = TemplateProcessor::default();
+        processor
+            .vars
+            .insert("host".to_string(), r#"x" onerror="alert(1)"#.to_string());
+        processor
+            .vars
+            .insert("quoted".to_string(), r#"'a' & "b""#.to_string());
+        let page = process(
+            &processor,
+            &format!(
+                "<RRD::GRAPH <RRD::GETVAR host>.png -t <RRD::GETVAR quoted> {GRAPH}>\n\
+                 <RRD::PRINT 0>\n\
+                 <RRD::GRAPH b.png -t <RRD::PRINT 0> {GRAPH}>"
+            ),
+        )
+        .unwrap();
+        assert_eq!(
+            "<IMG SRC=\"x&quot; onerror=&quot;alert(1).png\" WIDTH=\"481\" HEIGHT=\"141\">\n\
+             &#39;a&#39; &amp; &quot;b&quot;\n\
+             <IMG SRC=\"b.png\" WIDTH=\"481\" HEIGHT=\"141\">",
+            page.html
+        );
+        // the path is returned as is, and PRINT output is used as is in args
+        assert_eq!(
+            PathBuf::from(r#"x" onerror="alert(1).png"#),
+            page.graphs[0].path
+        );
+        assert_eq!(r#"'a' & "b""#, page.graphs[1].metadata.prints[0].text);
+    }
+
+    #[test]
+    fn time_now() {
+        let processor = TemplateProcessor {
+            now: Some(Timestamp::from_timestamp(1_750_000_000, 0).unwrap()),
+            ..Default::default()
+        };
+        assert_eq!(
+            "Generated in 2025",
+            process(&processor, "Generated in <RRD::TIME::NOW %Y>")
+                .unwrap()
+                .html
+        );
+        assert!(process(&processor, "<RRD::TIME::NOW %Q>").is_err());
+    }
+
+    #[test]
+    fn include() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(
+            dir.path().join("header.html"),
+            "<H1><RRD::GETVAR title></H1>",
+        )
+        .unwrap();
+        fs::write(dir.path().join("loop.html"), "<RRD::INCLUDE loop.html>").unwrap();
+        let processor = TemplateProcessor {
+            include_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            "<H1>Graphs</H1>",
+            process(
+                &processor,
+                "<RRD::SETVAR title Graphs><RRD::INCLUDE header.html>"
+            )
+            .unwrap()
+            .html
+        );
+        assert!(process(&processor, "<RRD::INCLUDE loop.html>").is_err());
+        assert!(process(&processor, "<RRD::INCLUDE missing.html>").is_err());
+
+        let outside = tempfile::NamedTempFile::new().unwrap();
+        fs::write(outside.path(), "secret").unwrap();
+        let name = outside.path().file_name().unwrap().to_str().unwrap();
+        for file in [
+            outside.path().to_str().unwrap().to_string(),
+            format!("../{name}"),
+            format!("{}/../{name}", dir.path().display()),
+        ] {
+            assert!(
+                process(&processor, &format!("<RRD::INCLUDE '{file}'>")).is_err(),
+                "{file}"
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let processor = TemplateProcessor::default();
+        for template in [
+            "<RRD::CV name>",
+            "<RRD::GETVAR unset>",
+            "<RRD::GETVAR a b>",
+            "<RRD::PRINT 0>",
+            &format!("<RRD::GRAPH a.png {GRAPH}><RRD::PRINT 1>"),
+            &format!("<RRD::GRAPH a.png --start now-1d {GRAPH}>"),
+            &format!("<RRD::GRAPH a.png --imginfo '%lu' {GRAPH}>"),
+            "<RRD::GETVAR 'unclosed>",
+            "<RRD::GETVAR",
+        ] {
+            assert!(
+                matches!(
+                    process(&processor, template),
+                    Err(RrdError::InvalidArgument(_))
+                ),
+                "{template}"
+            );
+        }
+    }
+}
@@ -0,0 +1,361 @@
+//! Reusable color and font presets for graphs.
+//!
+//! A [`Theme`] fills in [`Misc::colors`] and [`Misc::fonts`], and has a palette of colors for
+//! the lines and areas in a graph. There are built-in light, dark and high contrast themes, and
+//! themes can be loaded from a file.
+
+use crate::{
+    error::{RrdError, RrdResult},
+    ops::graph::{
+        props::{ColorTag, FontParams, FontTag, GraphProps, Misc},
+        Color,
+    },
+};
+use std::{collections, fs, path::Path, str};
+
+/// Colors and fonts to apply to graphs, and colors for their elements.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::graph::{props::GraphProps, theme::Theme};
+///
+/// let theme = Theme::dark();
+/// let mut props = GraphProps::default();
+/// theme.apply(&mut props);
+/// // e.g. for each line in the graph
+/// let first_line_color = theme.series_color(0).unwrap();
+/// ```
+///
+/// # File format
+///
+/// A theme file has a `key = value` setting per line. Blank lines and lines starting with `#` are
+/// ignored. Tags are the names `rrdtool` uses for `--color` and `--font`.
+///
+/// ```text
+/// # Start from a built-in theme: light, dark or high-contrast
+/// base = dark
+/// color.BACK = #101010
+/// color.MGRID = #FF000080
+/// # size, or size:font
+/// font.DEFAULT = 9
+/// font.TITLE = 12:DejaVu Sans
+/// palette = #FF0000, #00FF00, #0000FF
+/// ```
+///
+/// `base`, if present, must be the first setting. Without it, the theme starts empty, i.e. with
+/// `rrdtool`'s default colors and fonts and no palette.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Theme {
+    /// Colors for parts of the graph
+    pub colors: collections::HashMap<ColorTag, Color>,
+    /// Fonts for text in the graph
+    pub fonts: collections::HashMap<FontTag, FontParams>,
+    /// Colors for series, i.e. the lines and areas in a graph, in order
+    pub palette: Vec<Color>,
+}
+
+impl Theme {
+    /// Dark text on a light background, similar to `rrdtool`'s defaults.
+    pub fn light() -> Self {
+        Self::builtin(
+            &[
+                (ColorTag::Back, "#F0F0F0"),
+                (ColorTag::Canvas, "#FFFFFF"),
+                (ColorTag::ShadeA, "#CBCBCB"),
+                (ColorTag::ShadeB, "#999999"),
+                (ColorTag::Grid, "#8C8C8C80"),
+                (ColorTag::MGrid, "#C8828280"),
+                (ColorTag::Font, "#000000"),
+                (ColorTag::Axis, "#404040"),
+                (ColorTag::Frame, "#404040"),
+                (ColorTag::Arrow, "#FF0000"),
+            ],
+            &[],
+            &[
+                "#4E79A7", "#F28E2B", "#E15759", "#76B7B2", "#59A14F", "#EDC948", "#B07AA1",
+                "#FF9DA7", "#9C755F", "#BAB0AC",
+            ],
+        )
+    }
+
+    /// Light text on a dark background.
+    pub fn dark() -> Self {
+        Self::builtin(
+            &[
+                (ColorTag::Back, "#1E1E1E"),
+                (ColorTag::Canvas, "#252526"),
+                (ColorTag::ShadeA, "#1E1E1E"),
+                (ColorTag::ShadeB, "#1E1E1E"),
+                (ColorTag::Grid, "#5A5A5A80"),
+                (ColorTag::MGrid, "#8A8A8A80"),
+                (ColorTag::Font, "#D4D4D4"),
+                (ColorTag::Axis, "#A0A0A0"),
+                (ColorTag::Frame, "#A0A0A0"),
+                (ColorTag::Arrow, "#D4D4D4"),
+            ],
+            &[],
+            &[
+                "#8AB4F8", "#F6AE2D", "#F28B82", "#81C995", "#FDD663", "#C58AF9", "#78D9EC",
+                "#FCAD70", "#FF8BCB", "#E8EAED",
+            ],
+        )
+    }
+
+    /// White text and saturated colors on black, with larger fonts.
+    pub fn high_contrast() -> Self {
+        Self::builtin(
+            &[
+                (ColorTag::Back, "#000000"),
+                (ColorTag::Canvas, "#000000"),
+                (ColorTag::ShadeA, "#000000"),
+                (ColorTag::ShadeB, "#000000"),
+                (ColorTag::Grid, "#808080"),
+                (ColorTag::MGrid, "#FFFFFF"),
+                (ColorTag::Font, "#FFFFFF"),
+                (ColorTag::Axis, "#FFFFFF"),
+                (ColorTag::Frame, "#FFFFFF"),
+                (ColorTag::Arrow, "#FFFF00"),
+            ],
+            &[(FontTag::Default, 10), (FontTag::Title, 13)],
+            &[
+                "#FFFF00", "#00FFFF", "#FF00FF", "#00FF00", "#FF8000", "#FFFFFF",
+            ],
+        )
+    }
+
+    /// Reads a theme from a file in the format described above.
+    pub fn from_file(path: impl AsRef<Path>) -> RrdResult<Self> {
+        let path = path.as_ref();
+        fs::read_to_string(path)
+            .map_err(|e| {
+                RrdError::InvalidArgument(format!("Theme file {path:?} can't be read: {e}"))
+            })?
+            .parse()
+    }
+
+    /// Sets the theme's colors and fonts in `props`, replacing any already set for the same tags.
+    ///
+    /// Colors and fonts the theme doesn't set are left as they are.
+    pub fn apply(&self, props: &mut GraphProps) {
+        let Misc { colors, fonts, .. } = &mut props.misc;
+        colors.extend(self.colors.iter().map(|(tag, color)| (*tag, *color)));
+        fonts.extend(self.fonts.iter().map(|(tag, font)| (*tag, font.clone())));
+    }
+
+    /// The color for the series at `index`, cycling through the palette.
+    ///
+    /// Returns `None` if the palette is empty.
+    pub fn series_color(&self, index: usize) -> Option<Color> {
+        if self.palette.is_empty() {
+            None
+        } else {
+            Some(self.palette[index % self.palette.len()])
+        }
+    }
+
+    /// A built-in theme by the name used for `base` in theme files.
+    fn named(name: &str) -> Option<Self> {
+        match name {
+            "light" => Some(Self::light()),
+            "dark" => Some(Self::dark()),
+            "high-contrast" => Some(Self::high_contrast()),
+            _ => None,
+        }
+    }
+
+    fn builtin(colors: &[(ColorTag, &str)], fonts: &[(FontTag, u32)], palette: &[&str]) -> Self {
+        Self {
+            colors: colors
+                .iter()
+                .map(|(tag, hex)| (*tag, hex.parse().unwrap()))
+                .collect(),
+            fonts: fonts
+                .iter()
+                .map(|(tag, size)| {
+                    (
+                        *tag,
+                        FontParams {
+                            size: *size,
+                            font: None,
+                        },
+                    )
+                })
+                .collect(),
+            palette: palette.iter().map(|hex| hex.parse().unwrap()).collect(),
+        }
+    }
+}
+
+impl str::FromStr for Theme {
+    type Err = RrdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut theme = Theme::default();
+        let settings = s
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+        for (setting_index, (line_number, line)) in settings.enumerate() {
+            let invalid = |message: &str| {
+                RrdError::InvalidArgument(format!("Theme line {line_number}: {message}"))
+            };
+            let (key, value) = line
+                .split_once('=')
+                .map(|(k, v)| (k.trim(), v.trim()))
+                .ok_or_else(|| invalid("expected `key = value`"))?;
+
+            if key == "base" {
+                if setting_index != 0 {
+                    return Err(invalid("`base` must be the first setting"));
+                }
+                theme = Theme::named(value).ok_or_else(|| invalid("unknown base theme"))?;
+            } else if key == "palette" {
+                theme.palette = value
+                    .split(',')
+                    .map(|c| c.trim().parse())
+                    .collect::<Result<_, _>>()
+                    .map_err(|_| invalid("invalid palette color"))?;
+            } else if let Some(tag) = key.strip_prefix("color.") {
+                let tag = tag
+                    .to_ascii_uppercase()
+                    .parse()
+                    .map_err(|_| invalid("unknown color tag"))?;
+                let color = value.parse().map_err(|_| invalid("invalid color"))?;
+                theme.colors.insert(tag, color);
+            } else if let Some(tag) = key.strip_prefix("font.") {
+                let tag = tag
+                    .to_ascii_uppercase()
+                    .parse()
+                    .map_err(|_| invalid("unknown font tag"))?;
+                let (size, font) = match value.split_once(':') {
+                    Some((size, font)) => (size, Some(font.trim().to_string())),
+                    None => (value, None),
+                };
+                let size = size
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid("invalid font size"))?;
+                theme.fonts.insert(tag, FontParams { size, font });
+            } else {
+                return Err(invalid("unknown setting"));
+            }
+        }
+        Ok(theme)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_themes_set_every_color() {
+        for theme in [Theme::light(), Theme::dark(), Theme::high_contrast()] {
+            assert_eq!(10, theme.colors.len());
+            assert!(!theme.palette.is_empty());
+        }
+    }
+
+    #[test]
+    fn apply_replaces_only_themed_tags() {
+        let mut props = GraphProps::default();
+        props
+            .misc
+            .colors
+            .insert(ColorTag::Back, "#123456".parse().unwrap());
+        props.misc.fonts.insert(
+            FontTag::Legend,
+            FontParams {
+                size: 7,
+                font: None,
+            },
+        );
+
+        let theme = Theme::high_contrast();
+        theme.apply(&mut props);
+        assert_eq!(theme.colors, props.misc.colors);
+        assert_eq!(
+            Some(7),
+            props.misc.fonts.get(&FontTag::Legend).map(|f| f.size)
+        );
+        assert_eq!(
+            Some(10),
+            props.misc.fonts.get(&FontTag::Default).map(|f| f.size)
+        );
+    }
+
+    #[test]
+    fn series_colors_cycle() {
+        let theme = Theme::dark();
+        let len = theme.palette.len();
+        assert_eq!(theme.series_color(1), theme.series_color(len + 1));
+        assert_ne!(theme.series_color(0), theme.series_color(1));
+        assert_eq!(None, Theme::default().series_color(0));
+    }
+
+    #[test]
+    fn parse_theme_file() {
+        let theme: Theme = "
+            # comment
+            base = dark
+
+            color.back = #101010
+            color.MGRID = #FF000080
+            font.DEFAULT = 9
+            font.TITLE = 12:DejaVu Sans
+            palette = #FF0000, #00FF00
+        "
+        .parse()
+        .unwrap();
+
+        let mut expected = Theme::dark();
+        expected
+            .colors
+            .insert(ColorTag::Back, "#101010".parse().unwrap());
+        expected
+            .colors
+            .insert(ColorTag::MGrid, "#FF000080".parse().unwrap());
+        expected.fonts.insert(
+            FontTag::Default,
+            FontParams {
+                size: 9,
+                font: None,
+            },
+        );
+        expected.fonts.insert(
+            FontTag::Title,
+            FontParams {
+                size: 12,
+                font: Some("DejaVu Sans".to_string()),
+            },
+        );
+        expected.palette = vec!["#FF0000".parse().unwrap(), "#00FF00".parse().unwrap()];
+        assert_eq!(expected, theme);
+    }
+
+    #[test]
+    fn parse_theme_file_errors() {
+        for (input, line) in [
+            ("color.BACK #000000", 1),
+            ("\n\ncolor.NOPE = #000000", 3),
+            ("color.BACK = black", 1),
+            ("font.TITLE = big", 1),
+            ("palette = #FF0000, red", 1),
+            ("base = sepia", 1),
+            ("color.BACK = #000000\nbase = dark", 2),
+            ("colour.BACK = #000000", 1),
+        ] {
+            match input.parse::<Theme>() {
+                Err(RrdError::InvalidArgument(message)) => assert!(
+                    message.starts_with(&format!("Theme line {line}:")),
+                    "{input:?}: {message}"
+                ),
+                other => panic!("{input:?}: {other:?}"),
+            }
+        }
+    }
+}
@@ -1,7 +1,8 @@
 //! Get info about an RRD.
 
 use crate::{
-    error::{get_rrd_error, RrdError, RrdResult},
+    context,
+    error::{get_rrd_error, Operation, RrdError, RrdResult},
     util::path_to_str,
 };
 use std::{
@@ -17,23 +18,28 @@ use std::{
 ///
 /// See <https://oss.oetiker.ch/rrdtool/doc/rrdinfo.en.html>.
 pub fn info(filename: &Path) -> RrdResult<HashMap<String, InfoValue>> {
-    let filename = CString::new(path_to_str(filename)?)?;
+    let c_filename = CString::new(path_to_str(filename)?)?;
 
-    let result_ptr = unsafe { rrd_sys::rrd_info_r(filename.as_ptr()) };
+    context::prepare("rrd_info_r")?;
+    let result_ptr = unsafe { rrd_sys::rrd_info_r(c_filename.as_ptr()) };
     if result_ptr.is_null() {
-        return Err(get_rrd_error().unwrap_or_else(|| {
-            RrdError::Internal("No info data, but no librrd error".to_string())
-        }));
+        return Err(
+            get_rrd_error(Operation::Info, Some(filename)).unwrap_or_else(|| {
+                RrdError::Internal("No info data, but no librrd error".to_string())
+            }),
+        );
     }
 
-    Ok(build_info_map(result_ptr))
+    build_info_map(result_ptr)
 }
 
 /// Value in the map returned from [`info()`], and other places that use the same info map.
 #[derive(Clone, Debug, PartialEq, PartialOrd)]
 #[allow(missing_docs)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum InfoValue {
-    Value(f64),
+    /// Unknown values (`NaN`) are serialized as `null`.
+    Value(#[cfg_attr(feature = "serde", serde(with = "crate::util::nan_as_null"))] f64),
     Count(u64),
     String(String),
     Int(i32),
@@ -125,14 +131,23 @@ impl From<Vec<u8>> for InfoValue {
     }
 }
 
-/// Must only be called on a non-null pointer.
+/// Converts the `rrd_info_t` list from `librrd` into a map, and frees the list.
 ///
-/// # Panics
-///
-/// Will panic if `info` is null.
-pub(crate) fn build_info_map(info: *mut rrd_sys::rrd_info_t) -> HashMap<String, InfoValue> {
-    assert!(!info.is_null());
+/// Returns an error if `info` is null or contains something this library doesn't understand,
+/// which can happen if the linked `librrd` differs from the one the bindings were generated from.
+pub(crate) fn build_info_map(
+    info: *mut rrd_sys::rrd_info_t,
+) -> RrdResult<HashMap<String, InfoValue>> {
+    if info.is_null() {
+        return Err(RrdError::Internal("Null info".to_string()));
+    }
+
+    let map = read_info_map(info);
+    unsafe { rrd_sys::rrd_info_free(info) }
+    map
+}
 
+fn read_info_map(info: *mut rrd_sys::rrd_info_t) -> RrdResult<HashMap<String, InfoValue>> {
     let mut map = HashMap::new();
     let mut current = info;
     while !current.is_null() {
@@ -154,18 +169,18 @@ pub(crate) fn build_info_map(info: *mut rrd_sys::rrd_info_t) -> HashMap<String,
             }
             rrd_sys::rrd_info_type_RD_I_INT => (unsafe { (*current).value.u_int }).into(),
             rrd_sys::rrd_info_type_RD_I_BLO => {
-                let slice = unsafe {
-                    let blob = (*current).value.u_blo;
-                    std::slice::from_raw_parts(
-                        blob.ptr.cast_const(),
-                        blob.size.try_into().expect("Implausibly huge blob"),
-                    )
-                };
+                let blob = unsafe { (*current).value.u_blo };
+                let size = blob.size.try_into().map_err(|_| {
+                    RrdError::Internal(format!("Implausibly huge blob for info key {key}"))
+                })?;
+                let slice = unsafe { std::slice::from_raw_parts(blob.ptr.cast_const(), size) };
 
                 slice.to_vec().into()
             }
             t => {
-                panic!("Unexpected info type {t} - version mismatch, or memory corruption?")
+                return Err(RrdError::Internal(format!(
+                    "Unexpected info type {t} for key {key} - version mismatch?"
+                )))
             }
         };
 
@@ -174,7 +189,21 @@ pub(crate) fn build_info_map(info: *mut rrd_sys::rrd_info_t) -> HashMap<String,
         current = unsafe { (*current).next };
     }
 
-    unsafe { rrd_sys::rrd_info_free(info) }
+    Ok(map)
+}
 
-    map
+#[cfg(all(test, feature = "serde"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serde_nan_as_null() {
+        let json = serde_json::to_value(InfoValue::Value(f64::NAN)).unwrap();
+        assert_eq!(serde_json::json!({ "Value": null }), json);
+        let value: InfoValue = serde_json::from_value(json).unwrap();
+        assert!(value.into_value().unwrap().is_nan());
+
+        let json = serde_json::to_value(InfoValue::Count(3)).unwrap();
+        assert_eq!(InfoValue::Count(3), serde_json::from_value(json).unwrap());
+    }
 }
@@ -0,0 +1,357 @@
+//! Async versions of the ops in this crate, for use with [`tokio`](https://tokio.rs).
+//!
+//! Every `librrd` call blocks the calling thread, so these functions run the corresponding
+//! blocking op on a dedicated thread pool, separate from tokio's default blocking pool, which is
+//! started the first time it's needed. They can be awaited from any tokio runtime, and the
+//! returned futures are `Send`.
+//!
+//! Calls that take a filename are serialized per file: a second call for the same file waits
+//! until the first has finished, so e.g. a file is never updated concurrently. This applies even
+//! if the future for the first call is dropped, since the blocking op can't be cancelled once it
+//! has started. The graph functions may read many files, and those reads are not serialized;
+//! `graph_to_file()` is serialized for the file it writes.
+//!
+//! Arguments are taken by value, since they must outlive the calling task.
+//!
+//! Requires the `async` feature.
+
+#[cfg(feature = "graph")]
+use crate::ops::graph::{
+    self,
+    elements::GraphElement,
+    props::{DataFormat, GraphProps, ImageFormat},
+    GraphFileOutcome, GraphMetadata,
+};
+use crate::{
+    data::Data,
+    error::{RrdError, RrdResult},
+    ops::{
+        create::{self, Archive, DataSource},
+        fetch::{self, Array, FetchOptions},
+        info::{self, InfoValue},
+        update::{self, BatchTime, Datum, ExtraFlags},
+    },
+    ConsolidationFn, Timestamp,
+};
+use rrd_sys::rrd_double;
+use std::{
+    borrow,
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{self, Arc, OnceLock, Weak},
+    time::Duration,
+};
+use tokio::{runtime, sync::Mutex};
+
+/// The maximum number of threads that will be used to run blocking ops concurrently.
+pub const MAX_BLOCKING_THREADS: usize = 16;
+
+/// Async version of [`create::create`].
+#[allow(clippy::too_many_arguments)]
+pub async fn create(
+    filename: PathBuf,
+    start: Timestamp,
+    step: Duration,
+    no_overwrite: bool,
+    template: Option<PathBuf>,
+    sources: Vec<PathBuf>,
+    data_sources: Vec<DataSource>,
+    round_robin_archives: Vec<Archive>,
+) -> RrdResult<()> {
+    run_for_file(filename, move |filename| {
+        let sources = sources.iter().map(PathBuf::as_path).collect::<Vec<_>>();
+        create::create(
+            filename,
+            start,
+            step,
+            no_overwrite,
+            template.as_deref(),
+            &sources,
+            &data_sources,
+            &round_robin_archives,
+        )
+    })
+    .await
+}
+
+/// Async version of [`update::update_all`].
+pub async fn update_all<D, B, I, O>(filename: PathBuf, update_options: O, data: I) -> RrdResult<()>
+where
+    D: AsRef<[Datum]>,
+    B: borrow::Borrow<(BatchTime, D)>,
+    I: IntoIterator<Item = B> + Send + 'static,
+    O: Into<update::Options> + Send + 'static,
+{
+    run_for_file(filename, move |filename| {
+        update::update_all(filename, update_options, data)
+    })
+    .await
+}
+
+/// Async version of [`update::update`].
+pub async fn update<D, B, I>(
+    filename: PathBuf,
+    ds_names: Vec<String>,
+    extra_flags: ExtraFlags,
+    data: I,
+) -> RrdResult<()>
+where
+    D: AsRef<[Datum]>,
+    B: borrow::Borrow<(BatchTime, D)>,
+    I: IntoIterator<Item = B> + Send + 'static,
+{
+    run_for_file(filename, move |filename| {
+        let ds_names = ds_names.iter().map(String::as_str).collect::<Vec<_>>();
+        update::update(filename, &ds_names, extra_flags, data)
+    })
+    .await
+}
+
+/// Async version of [`fetch::fetch`].
+pub async fn fetch(
+    filename: PathBuf,
+    cf: ConsolidationFn,
+    start: Timestamp,
+    end: Timestamp,
+    resolution: Duration,
+) -> RrdResult<Data<Array>> {
+    fetch_with_options(
+        filename,
+        cf,
+        start,
+        end,
+        resolution,
+        FetchOptions::default(),
+    )
+    .await
+}
+
+/// Async version of [`fetch::fetch_with_options`].
+pub async fn fetch_with_options(
+    filename: PathBuf,
+    cf: ConsolidationFn,
+    start: Timestamp,
+    end: Timestamp,
+    resolution: Duration,
+    options: FetchOptions,
+) -> RrdResult<Data<Array>> {
+    run_for_file(filename, move |filename| {
+        fetch::fetch_with_options(filename, cf, start, end, resolution, options)
+    })
+    .await
+}
+
+/// Async version of [`fetch::fetch_multi`].
+pub async fn fetch_multi(
+    filename: PathBuf,
+    cfs: Vec<ConsolidationFn>,
+    start: Timestamp,
+    end: Timestamp,
+    resolution: Duration,
+    options: FetchOptions,
+) -> RrdResult<Data<Vec<rrd_double>>> {
+    run_for_file(filename, move |filename| {
+        fetch::fetch_multi(filename, &cfs, start, end, resolution, options)
+    })
+    .await
+}
+
+/// Async version of [`info::info`].
+pub async fn info(filename: PathBuf) -> RrdResult<HashMap<String, InfoValue>> {
+    run_for_file(filename, info::info).await
+}
+
+/// Async version of [`graph::graph`].
+///
+/// Unlike the other functions in this module, this is not serialized with other calls for the
+/// files it reads.
+#[cfg(feature = "graph")]
+pub async fn graph(
+    image_format: ImageFormat,
+    props: GraphProps,
+    elements: Vec<GraphElement>,
+) -> RrdResult<(Vec<u8>, GraphMetadata)> {
+    run_blocking(move || graph::graph(image_format, props, &elements)).await
+}
+
+/// Async version of [`graph::graph_to_file`].
+///
+/// This is serialized with other calls for `path`, but not for the files it reads.
+#[cfg(feature = "graph")]
+pub async fn graph_to_file(
+    path: PathBuf,
+    image_format: ImageFormat,
+    props: GraphProps,
+    elements: Vec<GraphElement>,
+    lazy: bool,
+) -> RrdResult<GraphFileOutcome> {
+    run_for_file(path, move |path| {
+        graph::graph_to_file(path, image_format, props, &elements, lazy)
+    })
+    .await
+}
+
+/// Async version of [`graph::graph_data`].
+///
+/// Like [`graph()`], this is not serialized with other calls for the files it reads.
+#[cfg(feature = "graph")]
+pub async fn graph_data(
+    format: DataFormat,
+    props: GraphProps,
+    elements: Vec<GraphElement>,
+) -> RrdResult<Data<Vec<rrd_double>>> {
+    run_blocking(move || graph::graph_data(format, props, &elements)).await
+}
+
+/// Run `op` on the blocking pool once any other ops for `filename` have finished.
+async fn run_for_file<T, F>(filename: PathBuf, op: F) -> RrdResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&Path) -> RrdResult<T> + Send + 'static,
+{
+    let guard = file_lock(&filename).lock_owned().await;
+    run_blocking(move || {
+        // Held until the op finishes, even if the calling future is dropped
+        let _guard = guard;
+        op(&filename)
+    })
+    .await
+}
+
+async fn run_blocking<T, F>(op: F) -> RrdResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> RrdResult<T> + Send + 'static,
+{
+    blocking_pool()
+        .spawn_blocking(op)
+        .await
+        .map_err(|e| RrdError::Internal(format!("Blocking op failed: {e}")))?
+}
+
+fn blocking_pool() -> &'static runtime::Handle {
+    static POOL: OnceLock<runtime::Runtime> = OnceLock::new();
+    POOL.get_or_init(|| {
+        // Only the blocking threads are used; the single worker thread just idles
+        runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .max_blocking_threads(MAX_BLOCKING_THREADS)
+            .thread_name("rrd-blocking")
+            .build()
+            .expect("Could not start rrd blocking pool")
+    })
+    .handle()
+}
+
+/// Returns the lock for `filename`, shared by all calls currently using that file.
+///
+/// Paths are made absolute (and canonical, if the file exists) so that different spellings of the
+/// same path share a lock.
+fn file_lock(filename: &Path) -> Arc<Mutex<()>> {
+    static LOCKS: OnceLock<sync::Mutex<HashMap<PathBuf, Weak<Mutex<()>>>>> = OnceLock::new();
+
+    let key = crate::util::file_key(filename);
+
+    let mut locks = LOCKS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner());
+    // Forget files that are no longer in use
+    locks.retain(|_, lock| lock.strong_count() > 0);
+    if let Some(lock) = locks.get(&key).and_then(Weak::upgrade) {
+        return lock;
+    }
+    let lock = Arc::new(Mutex::new(()));
+    locks.insert(key, Arc::downgrade(&lock));
+    lock
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn futures_are_send() {
+        let path = PathBuf::from("unused.rrd");
+        let now = Timestamp::default();
+        let step = Duration::from_secs(1);
+
+        // Not polled, so nothing runs
+        assert_send(&create(
+            path.clone(),
+            now,
+            step,
+            true,
+            None,
+            vec![],
+            vec![],
+            vec![],
+        ));
+        assert_send(&update_all(
+            path.clone(),
+            update::Options::default(),
+            vec![(BatchTime::Now, vec![Datum::Unspecified])],
+        ));
+        assert_send(&fetch(path.clone(), ConsolidationFn::Avg, now, now, step));
+        assert_send(&info(path));
+        #[cfg(feature = "graph")]
+        assert_send(&graph(ImageFormat::Png, GraphProps::default(), vec![]));
+        #[cfg(feature = "graph")]
+        assert_send(&graph_to_file(
+            PathBuf::new(),
+            ImageFormat::Png,
+            GraphProps::default(),
+            vec![],
+            true,
+        ));
+        #[cfg(feature = "graph")]
+        assert_send(&graph_data(DataFormat::Csv, GraphProps::default(), vec![]));
+    }
+
+    #[test]
+    fn data_is_send_and_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Data<Array>>();
+    }
+
+    #[tokio::test]
+    async fn same_file_is_serialized() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let path = tempdir.path().join("data.rrd");
+        // A different spelling of the same path
+        let other_spelling = tempdir.path().join(".").join("data.rrd");
+
+        let first = file_lock(&path).lock_owned().await;
+        assert!(file_lock(&other_spelling).try_lock().is_err());
+        assert!(file_lock(&tempdir.path().join("other.rrd"))
+            .try_lock()
+            .is_ok());
+        drop(first);
+        assert!(file_lock(&other_spelling).try_lock().is_ok());
+    }
+
+    #[tokio::test]
+    async fn ops_for_same_file_do_not_overlap() {
+        let path = PathBuf::from("serialized.rrd");
+        let running = Arc::new(sync::atomic::AtomicUsize::new(0));
+
+        let tasks = (0..4)
+            .map(|_| {
+                let running = running.clone();
+                run_for_file(path.clone(), move |_| {
+                    let concurrent = running.fetch_add(1, sync::atomic::Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_millis(20));
+                    running.fetch_sub(1, sync::atomic::Ordering::SeqCst);
+                    Ok(concurrent)
+                })
+            })
+            .map(tokio::spawn)
+            .collect::<Vec<_>>();
+
+        for task in tasks {
+            assert_eq!(0, task.await.unwrap().unwrap());
+        }
+    }
+}
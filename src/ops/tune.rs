@@ -0,0 +1,142 @@
+//! Change the settings of an existing RRD.
+//!
+//! So far this only builds `rrdtool tune` command lines; see [`TuneCommand`].
+//!
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrdtune.en.html>.
+
+use crate::{
+    error::{InvalidArgument, RrdResult},
+    util::{self, path_to_str},
+};
+use std::path::PathBuf;
+
+/// The inputs to `rrdtool tune`.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::tune::{DataSourceType, Tune, TuneCommand};
+///
+/// let command = TuneCommand {
+///     filename: "data.rrd".into(),
+///     changes: vec![
+///         Tune::Heartbeat { ds_name: "speed".to_string(), heartbeat: 1200 },
+///         Tune::Minimum { ds_name: "speed".to_string(), min: None },
+///         Tune::DataSourceType { ds_name: "speed".to_string(), ds_type: DataSourceType::Derive },
+///     ],
+/// };
+/// assert_eq!(
+///     "rrdtool tune data.rrd --heartbeat speed:1200 --minimum speed:U \
+///      --data-source-type speed:DERIVE",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TuneCommand {
+    /// The RRD to change
+    pub filename: PathBuf,
+    /// The changes to apply, in order
+    pub changes: Vec<Tune>,
+}
+
+impl TuneCommand {
+    /// Returns the `rrdtool` arguments, starting with `tune`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec!["tune".to_string(), path_to_str(&self.filename)?.to_string()];
+        for change in &self.changes {
+            change.check_ds_names()?;
+            let (option, value) = match change {
+                Tune::Heartbeat { ds_name, heartbeat } => {
+                    ("--heartbeat", format!("{ds_name}:{heartbeat}"))
+                }
+                Tune::Minimum { ds_name, min } => ("--minimum", limit_arg(ds_name, *min)),
+                Tune::Maximum { ds_name, max } => ("--maximum", limit_arg(ds_name, *max)),
+                Tune::DataSourceType { ds_name, ds_type } => (
+                    "--data-source-type",
+                    format!("{ds_name}:{}", ds_type.as_arg_str()),
+                ),
+                Tune::Rename { old, new } => ("--data-source-rename", format!("{old}:{new}")),
+            };
+            args.push(option.to_string());
+            args.push(value);
+        }
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool tune` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
+}
+
+fn limit_arg(ds_name: &str, limit: Option<f64>) -> String {
+    match limit {
+        Some(limit) => format!("{ds_name}:{limit}"),
+        None => format!("{ds_name}:U"),
+    }
+}
+
+/// A change to make to an RRD.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tune {
+    /// Set the heartbeat of a DS, in seconds
+    #[allow(missing_docs)]
+    Heartbeat { ds_name: String, heartbeat: u32 },
+    /// Set the minimum value of a DS, or remove it if `None`
+    #[allow(missing_docs)]
+    Minimum { ds_name: String, min: Option<f64> },
+    /// Set the maximum value of a DS, or remove it if `None`
+    #[allow(missing_docs)]
+    Maximum { ds_name: String, max: Option<f64> },
+    /// Change the type of a DS
+    #[allow(missing_docs)]
+    DataSourceType {
+        ds_name: String,
+        ds_type: DataSourceType,
+    },
+    /// Rename a DS
+    #[allow(missing_docs)]
+    Rename { old: String, new: String },
+}
+
+impl Tune {
+    fn check_ds_names(&self) -> Result<(), InvalidArgument> {
+        match self {
+            Tune::Heartbeat { ds_name, .. }
+            | Tune::Minimum { ds_name, .. }
+            | Tune::Maximum { ds_name, .. }
+            | Tune::DataSourceType { ds_name, .. } => util::check_ds_name(ds_name),
+            Tune::Rename { old, new } => {
+                util::check_ds_name(old)?;
+                util::check_ds_name(new)
+            }
+        }
+    }
+}
+
+/// The type of a (non-`COMPUTE`) DS.
+///
+/// See [`crate::ops::create::DataSource`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub enum DataSourceType {
+    Gauge,
+    Counter,
+    DCounter,
+    Derive,
+    DDerive,
+    Absolute,
+}
+
+impl DataSourceType {
+    fn as_arg_str(&self) -> &'static str {
+        match self {
+            DataSourceType::Gauge => "GAUGE",
+            DataSourceType::Counter => "COUNTER",
+            DataSourceType::DCounter => "DCOUNTER",
+            DataSourceType::Derive => "DERIVE",
+            DataSourceType::DDerive => "DDERIVE",
+            DataSourceType::Absolute => "ABSOLUTE",
+        }
+    }
+}
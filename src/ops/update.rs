@@ -2,15 +2,23 @@
 
 use crate::error::RrdError;
 use crate::{
-    error::{return_code_to_result, RrdResult},
-    util::{path_to_str, ArrayOfStrings},
+    context,
+    error::{return_code_to_result, Operation, RrdResult},
+    ops::version::{self, Capabilities},
+    util::{self, path_to_str, ArrayOfStrings},
     Timestamp,
 };
 use bitflags::bitflags;
 use itertools::Itertools;
 use log::debug;
 use rrd_sys::rrd_int;
-use std::{borrow, ffi::CString, fmt::Write, path::Path, ptr::null};
+use std::{
+    borrow,
+    ffi::CString,
+    fmt::Write,
+    path::{Path, PathBuf},
+    ptr::null,
+};
 
 bitflags! {
     /// Flags to alter update behavior.
@@ -33,12 +41,13 @@ bitflags! {
 /// Options to alter update behavior.
 ///
 /// This is an alternative to using `ExtraFlags`.
-#[derive(Debug, Default, Clone, Copy)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub struct Options {
     /// Silently skip updates older than the last update already present rather than returning an error.
     pub skip_past_updates: bool,
     /// Locking behavior when updating the RRD.
-    #[cfg(feature = "locking_mode")]
+    ///
+    /// Anything other than [`LockingMode::DEFAULT`] requires librrd 1.9.0 or newer.
     pub locking_mode: LockingMode,
 }
 
@@ -49,15 +58,12 @@ impl Options {
         if self.skip_past_updates {
             bits |= 1;
         }
-        #[cfg(feature = "locking_mode")]
-        {
-            bits |= match self.locking_mode {
-                LockingMode::DEFAULT => 0,
-                LockingMode::NONE => 1 << 7,
-                LockingMode::BLOCK => 2 << 7,
-                LockingMode::TRY => 3 << 7,
-            };
-        }
+        bits |= match self.locking_mode {
+            LockingMode::DEFAULT => 0,
+            LockingMode::NONE => 1 << 7,
+            LockingMode::BLOCK => 2 << 7,
+            LockingMode::TRY => 3 << 7,
+        };
         bits
     }
 }
@@ -66,14 +72,12 @@ impl From<ExtraFlags> for Options {
     fn from(flags: ExtraFlags) -> Self {
         Self {
             skip_past_updates: flags.contains(ExtraFlags::SKIP_PAST_UPDATES),
-            #[cfg(feature = "locking_mode")]
             locking_mode: LockingMode::default(),
         }
     }
 }
 
 /// Locking behavior when updating the RRD.
-#[cfg(feature = "locking_mode")]
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
 pub enum LockingMode {
     /// Read $RRD_LOCKING environment or fall back to TRY.
@@ -119,26 +123,33 @@ where
     I: IntoIterator<Item = B>,
     O: Into<Options>,
 {
-    let filename = CString::new(path_to_str(filename)?)?;
+    let c_filename = CString::new(path_to_str(filename)?)?;
     let args = build_datum_args(data, None)?;
-    let extra_flags = update_options.into().bits();
+    let update_options = update_options.into();
+    if update_options.locking_mode != LockingMode::DEFAULT {
+        version::require("Update locking mode", Capabilities::LOCKING_MODE)?;
+    }
+    let extra_flags = update_options.bits();
 
     debug!("Update: file={filename:?} extra_flags=0x{extra_flags:02x} args={args:?}",);
 
+    context::prepare("rrd_updatex_r")?;
     let rc = unsafe {
         rrd_sys::rrd_updatex_r(
-            filename.as_ptr(),
+            c_filename.as_ptr(),
             null(),
             extra_flags,
             args.len() as rrd_int,
             args.as_ptr(),
         )
     };
-    return_code_to_result(rc)
+    return_code_to_result(rc, Operation::Update, Some(filename))
 }
 
 /// Update only the DS names specified in `ds_names`.
 ///
+/// DS names must be 1 to 19 ASCII letters, digits or `_`.
+///
 /// No `COMPUTE` DS names should be included, as those do not have values directly provided. DS
 /// names not specified (other than `COMPUTE` DSs) will have `unknown` values applied for the
 /// given timestamps.
@@ -179,24 +190,101 @@ where
     B: borrow::Borrow<(BatchTime, D)>,
     I: IntoIterator<Item = B>,
 {
-    let filename = CString::new(path_to_str(filename)?)?;
-    let template = CString::new(ds_names.iter().join(":"))?;
+    let c_filename = CString::new(path_to_str(filename)?)?;
+    let template = CString::new(template_arg(ds_names)?)?;
     let args = build_datum_args(data, Some(ds_names.len()))?;
 
     debug!(
         "Update: file={filename:?} template={template:?} extra_flags=0x{extra_flags:02x} args={args:?}",
     );
 
+    context::prepare("rrd_updatex_r")?;
     let rc = unsafe {
         rrd_sys::rrd_updatex_r(
-            filename.as_ptr(),
+            c_filename.as_ptr(),
             template.as_ptr(),
             extra_flags.bits(),
             args.len() as rrd_int,
             args.as_ptr(),
         )
     };
-    return_code_to_result(rc)
+    return_code_to_result(rc, Operation::Update, Some(filename))
+}
+
+/// Returns the `:`-separated template for `ds_names`, after checking they're valid DS names.
+fn template_arg<S: AsRef<str>>(ds_names: &[S]) -> RrdResult<String> {
+    for name in ds_names {
+        util::check_ds_name(name.as_ref())?;
+    }
+    Ok(ds_names.iter().map(AsRef::as_ref).join(":"))
+}
+
+/// The inputs to [`update_all`] or [`update`], for building the equivalent `rrdtool update` command
+/// line.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::update::{BatchTime, Datum, UpdateCommand};
+///
+/// let command = UpdateCommand {
+///     filename: "data.rrd".into(),
+///     options: Default::default(),
+///     ds_names: Some(vec!["speed".to_string()]),
+///     data: vec![(BatchTime::Now, vec![Datum::Int(12)])],
+/// };
+/// assert_eq!(
+///     "rrdtool update data.rrd --template speed N:12",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateCommand {
+    /// The RRD to update
+    pub filename: PathBuf,
+    /// Update options
+    pub options: Options,
+    /// The DS names to update, as with [`update`], or `None` to update all, as with [`update_all`]
+    pub ds_names: Option<Vec<String>>,
+    /// The data to add
+    pub data: Vec<(BatchTime, Vec<Datum>)>,
+}
+
+impl UpdateCommand {
+    /// Returns the `rrdtool` arguments, starting with `update`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec![
+            "update".to_string(),
+            path_to_str(&self.filename)?.to_string(),
+        ];
+        if let Some(ds_names) = &self.ds_names {
+            args.push("--template".to_string());
+            args.push(template_arg(ds_names)?);
+        }
+        if self.options.skip_past_updates {
+            args.push("--skip-past-updates".to_string());
+        }
+        let locking = match self.options.locking_mode {
+            LockingMode::DEFAULT => None,
+            LockingMode::NONE => Some("none"),
+            LockingMode::BLOCK => Some("block"),
+            LockingMode::TRY => Some("try"),
+        };
+        if let Some(locking) = locking {
+            args.push("--locking".to_string());
+            args.push(locking.to_string());
+        }
+        args.extend(datum_arg_strings(
+            &self.data,
+            self.ds_names.as_ref().map(Vec::len),
+        )?);
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool update` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
 }
 
 /// The value to set for an individual DS at a particular timestamp.
@@ -221,6 +309,7 @@ impl From<f64> for Datum {
 }
 
 /// Timestamp to use for a batch of [`Datum`] values in an update call.
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum BatchTime {
     /// Let `librrd` determine the time from the system clock.
     Now,
@@ -238,8 +327,24 @@ impl From<Timestamp> for BatchTime {
 /// len.
 fn build_datum_args<'a, D, B, I>(
     batches: I,
-    mut expected_len: Option<usize>,
+    expected_len: Option<usize>,
 ) -> RrdResult<ArrayOfStrings>
+where
+    D: AsRef<[Datum]> + 'a,
+    B: borrow::Borrow<(BatchTime, D)>,
+    I: IntoIterator<Item = B>,
+{
+    datum_arg_strings(batches, expected_len)?
+        .into_iter()
+        .map(|arg| CString::new(arg).map_err(|e| e.into()))
+        .collect()
+}
+
+/// Returns a `timestamp:value:...` arg for each batch, checking lengths as in [`build_datum_args`].
+fn datum_arg_strings<'a, D, B, I>(
+    batches: I,
+    mut expected_len: Option<usize>,
+) -> RrdResult<Vec<String>>
 where
     D: AsRef<[Datum]> + 'a,
     B: borrow::Borrow<(BatchTime, D)>,
@@ -285,9 +390,9 @@ where
                 }
             }
 
-            CString::new(timestamp_arg).map_err(|e| e.into())
+            Ok(timestamp_arg)
         })
-        .collect::<Result<ArrayOfStrings, _>>()
+        .collect()
 }
 
 #[cfg(test)]
@@ -363,6 +468,57 @@ mod tests {
         update_all(rrd_path, Options::default(), data)
     }
 
+    #[test]
+    fn command_args() {
+        let command = UpdateCommand {
+            filename: "data.rrd".into(),
+            options: Options {
+                skip_past_updates: true,
+                locking_mode: LockingMode::BLOCK,
+            },
+            ds_names: None,
+            data: vec![
+                (
+                    Timestamp::from_timestamp(920804460, 0).unwrap().into(),
+                    vec![Datum::Float(1.5), Datum::Unspecified],
+                ),
+                (BatchTime::Now, vec![Datum::Int(2), Datum::Int(3)]),
+            ],
+        };
+        assert_eq!(
+            vec![
+                "update",
+                "data.rrd",
+                "--skip-past-updates",
+                "--locking",
+                "block",
+                "920804460:1.5:U",
+                "N:2:3"
+            ],
+            command.to_args().unwrap()
+        );
+
+        let mismatched = UpdateCommand {
+            ds_names: Some(vec!["a".to_string(), "b".to_string()]),
+            data: vec![(BatchTime::Now, vec![Datum::Int(1)])],
+            ..command
+        };
+        assert!(matches!(
+            mismatched.to_args(),
+            Err(RrdError::InvalidArgument(_))
+        ));
+
+        let injected = UpdateCommand {
+            ds_names: Some(vec!["a:b".to_string()]),
+            data: vec![(BatchTime::Now, vec![Datum::Int(1)])],
+            ..mismatched
+        };
+        assert!(matches!(
+            injected.to_args(),
+            Err(RrdError::InvalidArgument(_))
+        ));
+    }
+
     fn call_update_with_tuple_vals(
         rrd_path: &Path,
         data: impl IntoIterator<Item = (BatchTime, [Datum; 1])>,
@@ -378,13 +534,11 @@ mod tests {
                 options.skip_past_updates,
                 flags.contains(ExtraFlags::SKIP_PAST_UPDATES)
             );
-            #[cfg(feature = "locking_mode")]
             assert_eq!(options.locking_mode, LockingMode::default());
             assert_eq!(options.bits(), flags.bits());
         }
     }
 
-    #[cfg(feature = "locking_mode")]
     #[test]
     fn options_locking_mode_bits() {
         use LockingMode::*;
@@ -1,10 +1,218 @@
-//! Get the `librrd` version
+//! Get the `librrd` version, and what it supports.
+//!
+//! The linked `librrd` can be older than the one these bindings were built against, e.g. when one
+//! binary is deployed to hosts with different `rrdtool` packages. Ops check [`Capabilities`] at
+//! runtime, and return [`RrdError::Unsupported`] when a requested option needs a newer `librrd`.
+//!
+//! With the `dynamic-loading` feature, `librrd` isn't linked at all, but loaded when first used,
+//! so programs still start on hosts without it. Ops then return [`RrdError::LibraryUnavailable`]
+//! if it can't be loaded. The default library name for the platform (e.g. `librrd.so.8`) is
+//! loaded, unless the `RRD_SYS_LIBRARY` environment variable or `load_librrd` specifies a path.
 
-use std::ffi::CStr;
+use crate::{
+    context,
+    error::{RrdError, RrdResult},
+};
+use std::{ffi::CStr, fmt, str::FromStr, sync::OnceLock};
 
 /// Returns the version of `librrd` this library is linked to, e.g. `"1.9.0"`.
+///
+/// # Panics
+///
+/// With the `dynamic-loading` feature, if `librrd` can't be loaded. Use [`linked_version`] or
+/// check [`is_available`] first to avoid that.
 pub fn librrd_version() -> String {
     (unsafe { CStr::from_ptr(rrd_sys::rrd_strversion()) })
         .to_string_lossy()
         .into_owned()
 }
+
+/// Returns the parsed version of `librrd` this library is linked to.
+pub fn linked_version() -> RrdResult<LibrrdVersion> {
+    static VERSION: OnceLock<LibrrdVersion> = OnceLock::new();
+    if let Some(version) = VERSION.get() {
+        return Ok(*version);
+    }
+
+    // Not cached on failure, so a library loaded later is still picked up
+    context::ensure_available("rrd_strversion")?;
+    let version = librrd_version();
+    let parsed = version
+        .parse()
+        .map_err(|_| RrdError::Internal(format!("Could not parse librrd version {version:?}")))?;
+    Ok(*VERSION.get_or_init(|| parsed))
+}
+
+/// Returns `true` if `librrd` is available.
+///
+/// This is always `true` unless the `dynamic-loading` feature is enabled, in which case this loads
+/// `librrd` if it hasn't been loaded yet.
+pub fn is_available() -> bool {
+    context::ensure_available("rrd_strversion").is_ok()
+}
+
+/// Load `librrd` from `path`, instead of the default library for the platform.
+///
+/// This must be called before any other use of `librrd`, and only once.
+///
+/// Requires the `dynamic-loading` feature.
+#[cfg(feature = "dynamic-loading")]
+pub fn load_librrd(path: impl AsRef<std::ffi::OsStr>) -> RrdResult<()> {
+    rrd_sys::load_library(path).map_err(|e| RrdError::LibraryUnavailable(e.to_string()))
+}
+
+/// A `librrd` version number.
+///
+/// Versions are ordered, so they can be compared to find out whether a feature is available:
+///
+/// ```
+/// use rrd::ops::version::LibrrdVersion;
+///
+/// let version: LibrrdVersion = "1.7.2".parse().unwrap();
+/// assert!(version < LibrrdVersion::new(1, 9, 0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LibrrdVersion {
+    /// Major version
+    pub major: u32,
+    /// Minor version
+    pub minor: u32,
+    /// Patch version, `0` if not present
+    pub patch: u32,
+}
+
+impl LibrrdVersion {
+    /// Construct a version from its components.
+    pub const fn new(major: u32, minor: u32, patch: u32) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+/// Parses `major.minor[.patch]`, ignoring any suffix on the last component (e.g. `1.8.0rc1`).
+impl FromStr for LibrrdVersion {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().splitn(3, '.');
+        let mut next = |required: bool| -> Result<u32, ParseVersionError> {
+            match parts.next() {
+                Some(part) => {
+                    let digits = part
+                        .find(|c: char| !c.is_ascii_digit())
+                        .map_or(part, |end| &part[..end]);
+                    digits.parse().map_err(|_| ParseVersionError)
+                }
+                None if required => Err(ParseVersionError),
+                None => Ok(0),
+            }
+        };
+        Ok(Self::new(next(true)?, next(true)?, next(false)?))
+    }
+}
+
+impl fmt::Display for LibrrdVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// A string could not be parsed as a [`LibrrdVersion`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid librrd version")]
+pub struct ParseVersionError;
+
+/// Features that are only available in some `librrd` versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Locking modes for updates, i.e. `update::Options::locking_mode` (1.9.0)
+    pub locking_mode: bool,
+    /// `rrd_create_r2`, used by `create::create` (1.5.0)
+    pub create_r2: bool,
+    /// `DCOUNTER` and `DDERIVE` data sources (1.5.0)
+    pub dcounter_dderive: bool,
+    /// Durations with units (e.g. `5m`) in `create`, `tune` and `graph` arguments (1.5.0).
+    ///
+    /// Reported for callers building their own arguments; the ops here always pass seconds.
+    pub scaled_durations: bool,
+    /// Gradients in graph `AREA`s (1.6.0)
+    pub gradient_areas: bool,
+    /// Graph data export formats, used by `graph::graph_data` (1.5.0)
+    pub graph_data_formats: bool,
+}
+
+impl Capabilities {
+    pub(crate) const LOCKING_MODE: LibrrdVersion = LibrrdVersion::new(1, 9, 0);
+    pub(crate) const CREATE_R2: LibrrdVersion = LibrrdVersion::new(1, 5, 0);
+    pub(crate) const DCOUNTER_DDERIVE: LibrrdVersion = LibrrdVersion::new(1, 5, 0);
+    pub(crate) const SCALED_DURATIONS: LibrrdVersion = LibrrdVersion::new(1, 5, 0);
+    pub(crate) const GRADIENT_AREAS: LibrrdVersion = LibrrdVersion::new(1, 6, 0);
+    pub(crate) const GRAPH_DATA_FORMATS: LibrrdVersion = LibrrdVersion::new(1, 5, 0);
+
+    /// The capabilities of `librrd` at `version`.
+    pub fn of(version: LibrrdVersion) -> Self {
+        Self {
+            locking_mode: version >= Self::LOCKING_MODE,
+            create_r2: version >= Self::CREATE_R2,
+            dcounter_dderive: version >= Self::DCOUNTER_DDERIVE,
+            scaled_durations: version >= Self::SCALED_DURATIONS,
+            gradient_areas: version >= Self::GRADIENT_AREAS,
+            graph_data_formats: version >= Self::GRAPH_DATA_FORMATS,
+        }
+    }
+
+    /// The capabilities of the linked `librrd`.
+    pub fn detect() -> RrdResult<Self> {
+        linked_version().map(Self::of)
+    }
+}
+
+/// Returns [`RrdError::Unsupported`] if the linked `librrd` is older than `required`, which is
+/// needed for `feature`.
+pub(crate) fn require(feature: &'static str, required: LibrrdVersion) -> RrdResult<()> {
+    let linked = linked_version()?;
+    if linked >= required {
+        Ok(())
+    } else {
+        Err(RrdError::Unsupported {
+            feature,
+            required,
+            linked,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_versions() {
+        assert_eq!(Ok(LibrrdVersion::new(1, 7, 2)), "1.7.2".parse());
+        assert_eq!(Ok(LibrrdVersion::new(1, 9, 0)), "1.9".parse());
+        assert_eq!(Ok(LibrrdVersion::new(1, 8, 0)), "1.8.0rc1\n".parse());
+        assert_eq!(Err(ParseVersionError), "".parse::<LibrrdVersion>());
+        assert_eq!(Err(ParseVersionError), "1".parse::<LibrrdVersion>());
+        assert_eq!(Err(ParseVersionError), "x.y.z".parse::<LibrrdVersion>());
+    }
+
+    #[test]
+    fn capabilities_by_version() {
+        let old = Capabilities::of(LibrrdVersion::new(1, 5, 5));
+        assert!(!old.locking_mode);
+        assert!(old.create_r2);
+        assert!(old.dcounter_dderive);
+        assert!(old.scaled_durations);
+        assert!(!old.gradient_areas);
+
+        assert!(!Capabilities::of(LibrrdVersion::new(1, 4, 9)).scaled_durations);
+
+        let new = Capabilities::of(LibrrdVersion::new(1, 9, 0));
+        assert!(new.locking_mode);
+        assert!(new.gradient_areas);
+    }
+}
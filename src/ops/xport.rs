@@ -0,0 +1,159 @@
+//! Export data from RRDs, using the same data definitions as graphs.
+//!
+//! So far this only builds `rrdtool xport` command lines; see [`XportCommand`].
+//!
+//! See <https://oss.oetiker.ch/rrdtool/doc/rrdxport.en.html>.
+
+use crate::{
+    error::RrdResult,
+    ops::graph::{
+        elements::{CDef, Def, Legend, VDef, VarName},
+        AppendArgs,
+    },
+    util, Timestamp,
+};
+use std::time::Duration;
+
+/// The inputs to `rrdtool xport`.
+///
+/// # Examples
+///
+/// ```
+/// use rrd::ops::{graph::elements::{Def, Legend, VarName}, xport::{Xport, XportCommand}};
+/// use rrd::ConsolidationFn;
+///
+/// let value = VarName::new("v").unwrap();
+/// let command = XportCommand {
+///     start: None,
+///     end: None,
+///     step: None,
+///     max_rows: Some(100),
+///     elements: vec![
+///         Def {
+///             var_name: value.clone(),
+///             rrd: "data.rrd".into(),
+///             ds_name: "speed".to_string(),
+///             consolidation_fn: ConsolidationFn::Avg,
+///             step: None,
+///             start: None,
+///             end: None,
+///             reduce: None,
+///         }
+///         .into(),
+///         Xport { value, legend: Some(Legend::new("Speed (m/s)")) }.into(),
+///     ],
+/// };
+/// assert_eq!(
+///     "rrdtool xport --maxrows 100 DEF:v=data.rrd:speed:AVERAGE 'XPORT:v:Speed (m/s)'",
+///     command.to_rrdtool_command().unwrap(),
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct XportCommand {
+    /// Start of the exported time range, if not the default of 1 day before `end`
+    pub start: Option<Timestamp>,
+    /// End of the exported time range, if not the default of now
+    pub end: Option<Timestamp>,
+    /// The resolution to export at, if not the finest available
+    pub step: Option<Duration>,
+    /// The maximum number of rows to export
+    pub max_rows: Option<u32>,
+    /// Data definitions, and which of them to export
+    pub elements: Vec<XportElement>,
+}
+
+impl XportCommand {
+    /// Returns the `rrdtool` arguments, starting with `xport`.
+    pub fn to_args(&self) -> RrdResult<Vec<String>> {
+        let mut args = vec!["xport".to_string()];
+        if let Some(start) = self.start {
+            args.push("--start".to_string());
+            args.push(start.timestamp().to_string());
+        }
+        if let Some(end) = self.end {
+            args.push("--end".to_string());
+            args.push(end.timestamp().to_string());
+        }
+        if let Some(step) = self.step {
+            args.push("--step".to_string());
+            args.push(step.as_secs().to_string());
+        }
+        if let Some(max_rows) = self.max_rows {
+            args.push("--maxrows".to_string());
+            args.push(max_rows.to_string());
+        }
+        for element in &self.elements {
+            element.append_to(&mut args)?;
+        }
+        Ok(args)
+    }
+
+    /// Returns a shell-quoted `rrdtool xport` command line.
+    pub fn to_rrdtool_command(&self) -> RrdResult<String> {
+        Ok(util::rrdtool_command(&self.to_args()?))
+    }
+}
+
+/// The elements allowed in an export.
+#[derive(Debug, Clone, PartialEq)]
+#[allow(missing_docs)]
+pub enum XportElement {
+    Def(Def),
+    CDef(CDef),
+    VDef(VDef),
+    Xport(Xport),
+}
+
+impl AppendArgs for XportElement {
+    fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        match self {
+            XportElement::Def(e) => e.append_to(args),
+            XportElement::CDef(e) => e.append_to(args),
+            XportElement::VDef(e) => e.append_to(args),
+            XportElement::Xport(e) => e.append_to(args),
+        }
+    }
+}
+
+impl From<Def> for XportElement {
+    fn from(value: Def) -> Self {
+        Self::Def(value)
+    }
+}
+
+impl From<CDef> for XportElement {
+    fn from(value: CDef) -> Self {
+        Self::CDef(value)
+    }
+}
+
+impl From<VDef> for XportElement {
+    fn from(value: VDef) -> Self {
+        Self::VDef(value)
+    }
+}
+
+impl From<Xport> for XportElement {
+    fn from(value: Xport) -> Self {
+        Self::Xport(value)
+    }
+}
+
+/// Include a variable in the export, as a column labelled with `legend`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[allow(missing_docs)]
+pub struct Xport {
+    pub value: VarName,
+    pub legend: Option<Legend>,
+}
+
+impl AppendArgs for Xport {
+    fn append_to(&self, args: &mut Vec<String>) -> RrdResult<()> {
+        let mut s = format!("XPORT:{}", self.value.name());
+        if let Some(legend) = &self.legend {
+            legend.append_to(&mut s)?;
+        }
+        args.push(s);
+        Ok(())
+    }
+}
@@ -0,0 +1,710 @@
+//! An HTTP server for RRD info, data and graphs.
+//!
+//! Endpoints:
+//!
+//! - `GET /rrd/{path}/info` returns the RRD's [`info`](info::info), as a JSON object of
+//!   [`InfoValue`]s.
+//! - `GET /rrd/{path}/fetch?cf=AVERAGE&start=...&end=...&resolution=...&format=json` returns data
+//!   from [`fetch`](fetch::fetch). `cf` is required. `start` and `end` are Unix timestamps or
+//!   RFC 3339 times, and default to one day ago and now. `resolution` is in seconds, and defaults
+//!   to the finest available. `format` is `json` (the default, as described in
+//!   [`data`](crate::data)) or `csv`. Time spans over the policy's
+//!   [`max_time_span`](GraphPolicy::max_time_span) are rejected.
+//! - `GET /rrd/{path}/lastupdate` returns the time of the last update, and the last value given
+//!   for each data source, like `rrdtool lastupdate`:
+//!   `{"last_update": "2025-01-01T00:00:00Z", "values": {"in": 1.0, "out": null}}`.
+//! - `POST /graph` renders the [`GraphRequest`] in the JSON body with [`GraphPolicy::graph`], and
+//!   returns the image.
+//!
+//! `{path}` is percent-decoded, and is relative to the root directory of the config's
+//! [`GraphPolicy`]. RRDs outside the root, including via symlinks, are reported as not found.
+//!
+//! Errors are returned as a JSON object with an `error` message, and a status code for the kind of
+//! error, e.g. 400 for invalid params, 403 for requests rejected by the policy, and 404 for RRDs that
+//! don't exist.
+//!
+//! Requests are handled by [`ServerConfig::max_concurrent`] threads. Further requests wait until a
+//! thread is free.
+//!
+//! Requires the `server` feature. The `rrd-server` binary runs a server from the command line.
+
+use crate::{
+    data::Data,
+    error::{LibRrdErrorKind, RrdError, RrdResult},
+    ops::{
+        fetch,
+        graph::{
+            elements::GraphElement,
+            policy::GraphPolicy,
+            props::{GraphProps, ImageFormat},
+        },
+        info::{self, InfoValue},
+    },
+    ConsolidationFn, Timestamp,
+};
+use rrd_sys::rrd_double;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Write as _},
+    io::Read as _,
+    net::{SocketAddr, ToSocketAddrs},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+/// Configuration for a [`Server`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerConfig {
+    /// Limits for `/graph`. Its root is also the directory that `/rrd/` paths are relative to.
+    pub policy: GraphPolicy,
+    /// Number of requests handled at once
+    pub max_concurrent: usize,
+    /// Max size of a request body, in bytes
+    pub max_body_size: usize,
+}
+
+impl ServerConfig {
+    /// A config serving RRDs in `root`, with the default [`GraphPolicy`] limits.
+    ///
+    /// Returns an error if `root` can't be canonicalized, e.g. because it doesn't exist.
+    pub fn new(root: impl AsRef<Path>) -> RrdResult<Self> {
+        Ok(Self {
+            policy: GraphPolicy::new(root)?,
+            max_concurrent: 4,
+            max_body_size: 1024 * 1024,
+        })
+    }
+}
+
+/// The JSON body of a `POST /graph` request.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct GraphRequest {
+    /// Defaults to PNG
+    #[serde(default = "default_image_format")]
+    pub image_format: ImageFormat,
+    #[allow(missing_docs)]
+    #[serde(default)]
+    pub props: GraphProps,
+    #[allow(missing_docs)]
+    pub elements: Vec<GraphElement>,
+}
+
+fn default_image_format() -> ImageFormat {
+    ImageFormat::Png
+}
+
+/// An HTTP server for RRDs. See the [module docs](self) for its endpoints.
+///
+/// # Examples
+///
+/// ```no_run
+/// use rrd::server::{Server, ServerConfig};
+/// use std::{sync::Arc, thread};
+///
+/// let server = Arc::new(Server::bind("127.0.0.1:8080", ServerConfig::new("/var/lib/rrd")?)?);
+/// let running = thread::spawn({
+///     let server = server.clone();
+///     move || server.run()
+/// });
+/// // ...
+/// server.shutdown();
+/// running.join().unwrap();
+/// # Ok::<(), rrd::error::RrdError>(())
+/// ```
+pub struct Server {
+    http: tiny_http::Server,
+    config: ServerConfig,
+    shutting_down: AtomicBool,
+}
+
+impl Server {
+    /// Listen on `addr`. Requests aren't handled until [`Server::run`] is called.
+    pub fn bind(addr: impl ToSocketAddrs, config: ServerConfig) -> RrdResult<Self> {
+        if config.max_concurrent == 0 {
+            return Err(RrdError::InvalidArgument(
+                "Server max_concurrent must be positive".to_string(),
+            ));
+        }
+        let http = tiny_http::Server::http(addr)
+            .map_err(|e| RrdError::InvalidArgument(format!("Server can't listen: {e}")))?;
+        Ok(Self {
+            http,
+            config,
+            shutting_down: AtomicBool::new(false),
+        })
+    }
+
+    /// The address the server is listening on, e.g. to find the port chosen for port 0.
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.http.server_addr().to_ip()
+    }
+
+    #[allow(missing_docs)]
+    pub fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+
+    /// Handle requests until [`Server::shutdown`] is called.
+    pub fn run(&self) {
+        thread::scope(|scope| {
+            for _ in 0..self.config.max_concurrent {
+                scope.spawn(|| self.serve());
+            }
+        });
+    }
+
+    /// Make [`Server::run`] return, once the requests being handled are finished.
+    pub fn shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+        // Each unblock is queued until a thread receives it, so every thread stops
+        for _ in 0..self.config.max_concurrent {
+            self.http.unblock();
+        }
+    }
+
+    /// Handle requests on the current thread until shut down.
+    fn serve(&self) {
+        loop {
+            match self.http.recv() {
+                Ok(request) => self.respond(request),
+                Err(_) if self.shutting_down.load(Ordering::SeqCst) => return,
+                Err(e) => log::warn!("Server: failed to receive a request: {e}"),
+            }
+        }
+    }
+
+    fn respond(&self, mut request: tiny_http::Request) {
+        let reply = match read_body(&mut request, self.config.max_body_size) {
+            Ok(body) => route(
+                &self.config,
+                request.method().as_str(),
+                request.url(),
+                &body,
+            ),
+            Err(e) => e.into(),
+        };
+        log::debug!(
+            "Server: {} {} -> {}",
+            request.method(),
+            request.url(),
+            reply.status
+        );
+        let content_type = tiny_http::Header::from_bytes("Content-Type", reply.content_type)
+            .expect("Content types are valid header values");
+        let response = tiny_http::Response::from_data(reply.body)
+            .with_status_code(reply.status)
+            .with_header(content_type);
+        if let Err(e) = request.respond(response) {
+            log::debug!("Server: failed to send a response: {e}");
+        }
+    }
+}
+
+impl fmt::Debug for Server {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Server")
+            .field("addr", &self.local_addr())
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+/// A response, before it's sent.
+#[derive(Debug)]
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn ok(content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    fn json(value: &impl serde::Serialize) -> Result<Self, HttpError> {
+        let body = serde_json::to_vec(value)
+            .map_err(|e| RrdError::Internal(format!("Server: can't serialize response: {e}")))?;
+        Ok(Self::ok("application/json", body))
+    }
+}
+
+/// A failed request, sent as a JSON error.
+#[derive(Debug)]
+struct HttpError {
+    status: u16,
+    message: String,
+}
+
+impl HttpError {
+    fn new(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+
+    fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(400, message)
+    }
+}
+
+impl From<RrdError> for HttpError {
+    fn from(e: RrdError) -> Self {
+        let status = match &e {
+            RrdError::InvalidArgument(_) | RrdError::NulError(_) | RrdError::PathEncodingError => {
+                400
+            }
+            RrdError::PolicyViolation(_) => 403,
+            RrdError::LibRrdError(_) => match e.librrd_kind() {
+                Some(LibRrdErrorKind::FileNotFound) => 404,
+                Some(
+                    LibRrdErrorKind::UnknownDataSource
+                    | LibRrdErrorKind::NoMatchingArchive
+                    | LibRrdErrorKind::InvalidArgument,
+                ) => 400,
+                Some(LibRrdErrorKind::LockFailed) => 503,
+                _ => 500,
+            },
+            RrdError::Unsupported { .. } => 501,
+            RrdError::LibraryUnavailable(_) => 503,
+            RrdError::Internal(_) => 500,
+        };
+        if status >= 500 {
+            log::warn!("Server: request failed: {e}");
+        }
+        Self::new(status, e.to_string())
+    }
+}
+
+impl From<HttpError> for Reply {
+    fn from(e: HttpError) -> Self {
+        let body = serde_json::json!({ "error": e.message }).to_string();
+        Self {
+            status: e.status,
+            content_type: "application/json",
+            body: body.into_bytes(),
+        }
+    }
+}
+
+fn read_body(request: &mut tiny_http::Request, max_size: usize) -> Result<Vec<u8>, HttpError> {
+    let mut body = vec![];
+    request
+        .as_reader()
+        .take(max_size as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| HttpError::bad_request(format!("Can't read request body: {e}")))?;
+    if body.len() > max_size {
+        return Err(HttpError::new(
+            413,
+            format!("Request body is more than the maximum {max_size} bytes"),
+        ));
+    }
+    Ok(body)
+}
+
+fn route(config: &ServerConfig, method: &str, url: &str, body: &[u8]) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let result = if path == "/graph" {
+        match method {
+            "POST" => graph(config, body),
+            _ => Err(method_not_allowed()),
+        }
+    } else {
+        match path.strip_prefix("/rrd/").and_then(|p| p.rsplit_once('/')) {
+            Some((rrd, action @ ("info" | "fetch" | "lastupdate"))) => {
+                if method == "GET" {
+                    rrd_request(config, rrd, action, query)
+                } else {
+                    Err(method_not_allowed())
+                }
+            }
+            _ => Err(HttpError::new(404, format!("No such endpoint {path:?}"))),
+        }
+    };
+    result.unwrap_or_else(Reply::from)
+}
+
+fn method_not_allowed() -> HttpError {
+    HttpError::new(405, "Method not allowed")
+}
+
+fn rrd_request(
+    config: &ServerConfig,
+    rrd: &str,
+    action: &str,
+    query: &str,
+) -> Result<Reply, HttpError> {
+    let rrd = percent_decode(rrd).ok_or_else(|| HttpError::bad_request("Invalid RRD path"))?;
+    let query = parse_query(query)?;
+    let path = confine(config, &rrd)?;
+    match action {
+        "info" => {
+            let info: BTreeMap<_, _> = info::info(&path)?.into_iter().collect();
+            Reply::json(&info)
+        }
+        "fetch" => fetch(&config.policy, &path, &query),
+        "lastupdate" => Reply::json(&last_update(&info::info(&path)?)?),
+        _ => unreachable!("Unrouted action {action}"),
+    }
+}
+
+/// The canonical path of `rrd` in the root directory.
+fn confine(config: &ServerConfig, rrd: &str) -> Result<PathBuf, HttpError> {
+    // Don't reveal whether a path outside the root exists
+    config
+        .policy
+        .confine(Path::new(rrd))
+        .map_err(|_| HttpError::new(404, format!("RRD {rrd:?} not found")))
+}
+
+fn fetch(
+    policy: &GraphPolicy,
+    path: &Path,
+    query: &HashMap<String, String>,
+) -> Result<Reply, HttpError> {
+    let cf = match query.get("cf").map(String::as_str) {
+        Some("AVERAGE") => ConsolidationFn::Avg,
+        Some("MIN") => ConsolidationFn::Min,
+        Some("MAX") => ConsolidationFn::Max,
+        Some("LAST") => ConsolidationFn::Last,
+        Some(other) => {
+            return Err(HttpError::bad_request(format!(
+                "Unknown consolidation function {other:?}"
+            )))
+        }
+        None => return Err(HttpError::bad_request("Missing `cf` param")),
+    };
+    let end = match query.get("end") {
+        Some(end) => parse_timestamp("end", end)?,
+        None => chrono::Utc::now(),
+    };
+    let start = match query.get("start") {
+        Some(start) => parse_timestamp("start", start)?,
+        None => end - chrono::TimeDelta::days(1),
+    };
+    // The data is held in memory, so limit it like a graph's
+    policy.check_time_span(start, end)?;
+    let resolution = match query.get("resolution") {
+        Some(resolution) => resolution
+            .parse()
+            .ok()
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .ok_or_else(|| {
+                HttpError::bad_request("`resolution` must be a positive number of seconds")
+            })?,
+        // librrd picks the finest resolution at least this long
+        None => Duration::from_secs(1),
+    };
+    let csv = match query.get("format").map(String::as_str) {
+        None | Some("json") => false,
+        Some("csv") => true,
+        Some(other) => return Err(HttpError::bad_request(format!("Unknown format {other:?}"))),
+    };
+
+    let data = fetch::fetch(path, cf, start, end, resolution)?;
+    if csv {
+        Ok(Reply::ok("text/csv", to_csv(&data).into_bytes()))
+    } else {
+        Reply::json(&data)
+    }
+}
+
+fn parse_timestamp(param: &str, value: &str) -> Result<Timestamp, HttpError> {
+    let timestamp = match value.parse() {
+        Ok(secs) => Timestamp::from_timestamp(secs, 0),
+        Err(_) => chrono::DateTime::parse_from_rfc3339(value)
+            .ok()
+            .map(|t| t.to_utc()),
+    };
+    timestamp.ok_or_else(|| {
+        HttpError::bad_request(format!(
+            "`{param}` must be a Unix timestamp or an RFC 3339 time"
+        ))
+    })
+}
+
+/// A header row of `timestamp` and the DS names, then a row for each timestamp. Unknown values
+/// are empty.
+fn to_csv<T>(data: &Data<T>) -> String
+where
+    T: Deref<Target = [rrd_double]>,
+{
+    let mut csv = "timestamp".to_string();
+    // DS names are only letters, digits and `_`, so they don't need quoting
+    for name in data.ds_names() {
+        csv.push(',');
+        csv.push_str(name);
+    }
+    csv.push('\n');
+    for row in data.rows() {
+        csv.push_str(
+            &row.timestamp()
+                .to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        );
+        for value in row.iter_known() {
+            csv.push(',');
+            if let Some(value) = value {
+                write!(csv, "{value}").unwrap();
+            }
+        }
+        csv.push('\n');
+    }
+    csv
+}
+
+/// The response to `lastupdate`.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct LastUpdate {
+    last_update: Timestamp,
+    /// `None` for unknown values
+    values: BTreeMap<String, Option<f64>>,
+}
+
+/// The equivalent of `rrdtool lastupdate`, from an RRD's info.
+fn last_update(info: &HashMap<String, InfoValue>) -> RrdResult<LastUpdate> {
+    let last_update = match info.get("last_update") {
+        Some(InfoValue::Count(secs)) => i64::try_from(*secs).ok(),
+        Some(InfoValue::Int(secs)) => Some(i64::from(*secs)),
+        _ => None,
+    }
+    .and_then(|secs| Timestamp::from_timestamp(secs, 0))
+    .ok_or_else(|| RrdError::Internal("Info: missing or invalid last_update".to_string()))?;
+    let values = info
+        .iter()
+        .filter_map(|(key, value)| {
+            let name = key.strip_prefix("ds[")?.strip_suffix("].last_ds")?;
+            // The value as given to update, or `U`
+            let value = match value {
+                InfoValue::String(s) => s.parse().ok().filter(|v: &f64| !v.is_nan()),
+                _ => None,
+            };
+            Some((name.to_string(), value))
+        })
+        .collect();
+    Ok(LastUpdate {
+        last_update,
+        values,
+    })
+}
+
+fn parse_query(query: &str) -> Result<HashMap<String, String>, HttpError> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |s: &str| {
+                percent_decode(&s.replace('+', " "))
+                    .ok_or_else(|| HttpError::bad_request(format!("Invalid query param {pair:?}")))
+            };
+            Ok((decode(key)?, decode(value)?))
+        })
+        .collect()
+}
+
+/// Decodes `%XX` escapes. Returns `None` if an escape is invalid, or the result isn't UTF-8.
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = tail
+                .get(..2)
+                .filter(|h| h.iter().all(u8::is_ascii_hexdigit))?;
+            bytes.push(u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn graph(config: &ServerConfig, body: &[u8]) -> Result<Reply, HttpError> {
+    let request: GraphRequest = serde_json::from_slice(body)
+        .map_err(|e| HttpError::bad_request(format!("Invalid graph request: {e}")))?;
+    let content_type = match request.image_format {
+        ImageFormat::Png => "image/png",
+        ImageFormat::Svg => "image/svg+xml",
+        ImageFormat::Eps => "application/postscript",
+        ImageFormat::Pdf => "application/pdf",
+    };
+    let (image, _metadata) =
+        config
+            .policy
+            .graph(request.image_format, request.props, &request.elements)?;
+    Ok(Reply::ok(content_type, image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn config() -> (tempfile::TempDir, ServerConfig) {
+        let tempdir = tempfile::tempdir().unwrap();
+        let root = tempdir.path().join("root");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("sub/ok.rrd"), b"").unwrap();
+        fs::write(tempdir.path().join("secret.rrd"), b"").unwrap();
+        let config = ServerConfig::new(&root).unwrap();
+        (tempdir, config)
+    }
+
+    fn get(config: &ServerConfig, url: &str) -> (u16, String) {
+        let reply = route(config, "GET", url, b"");
+        (reply.status, String::from_utf8(reply.body).unwrap())
+    }
+
+    #[test]
+    fn rejects_unknown_routes_and_methods() {
+        let (_tempdir, config) = config();
+        assert_eq!(404, get(&config, "/").0);
+        assert_eq!(404, get(&config, "/rrd/sub/ok.rrd/dump").0);
+        assert_eq!(405, get(&config, "/graph").0);
+        assert_eq!(
+            405,
+            route(&config, "POST", "/rrd/sub/ok.rrd/info", b"").status
+        );
+    }
+
+    #[test]
+    fn confines_rrd_paths() {
+        let (_tempdir, config) = config();
+        for url in [
+            "/rrd/../secret.rrd/info",
+            "/rrd/%2E%2E/secret.rrd/lastupdate",
+            "/rrd/sub/missing.rrd/info",
+            "/rrd/sub/info",
+        ] {
+            let (status, body) = get(&config, url);
+            assert_eq!(404, status, "{url}");
+            assert!(body.starts_with(r#"{"error":"RRD "#), "{body}");
+        }
+        let secret = config.policy.root().join("../secret.rrd");
+        let (status, _) = get(&config, &format!("/rrd/{}/info", secret.display()));
+        assert_eq!(404, status);
+    }
+
+    #[test]
+    fn rejects_invalid_fetch_params() {
+        let (_tempdir, config) = config();
+        for query in [
+            "",
+            "cf=MEDIAN",
+            "cf=AVERAGE&start=yesterday",
+            "cf=AVERAGE&end=2025-01-01",
+            "cf=AVERAGE&resolution=0",
+            "cf=AVERAGE&format=xml",
+            "cf=%ZZ",
+        ] {
+            let (status, body) = get(&config, &format!("/rrd/sub/ok.rrd/fetch?{query}"));
+            assert_eq!(400, status, "{query}: {body}");
+        }
+
+        // rejected before fetching, by the policy's max time span
+        let (status, body) = get(
+            &config,
+            "/rrd/sub/ok.rrd/fetch?cf=AVERAGE&start=0&end=4000000000",
+        );
+        assert_eq!(403, status, "{body}");
+    }
+
+    #[test]
+    fn rejects_invalid_graph_requests() {
+        let (_tempdir, config) = config();
+        assert_eq!(400, route(&config, "POST", "/graph", b"{").status);
+        assert_eq!(400, route(&config, "POST", "/graph", b"{}").status);
+    }
+
+    #[test]
+    fn maps_errors_to_statuses() {
+        let reply = Reply::from(HttpError::from(RrdError::PolicyViolation("no".to_string())));
+        assert_eq!(403, reply.status);
+        assert_eq!(
+            r#"{"error":"Graph policy violation: no"}"#,
+            String::from_utf8(reply.body).unwrap()
+        );
+        let reply = Reply::from(HttpError::from(RrdError::Internal("oops".to_string())));
+        assert_eq!(500, reply.status);
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        let expected = Timestamp::from_timestamp(1_735_689_600, 0).unwrap();
+        assert_eq!(expected, parse_timestamp("t", "1735689600").unwrap());
+        assert_eq!(
+            expected,
+            parse_timestamp("t", "2025-01-01T00:00:00Z").unwrap()
+        );
+        assert_eq!(
+            expected,
+            parse_timestamp("t", "2025-01-01T01:00:00+01:00").unwrap()
+        );
+        assert!(parse_timestamp("t", "now").is_err());
+    }
+
+    #[test]
+    fn parses_queries() {
+        let query = parse_query("cf=AVERAGE&a+b=%2Fc%20d&flag&").unwrap();
+        assert_eq!("AVERAGE", query["cf"]);
+        assert_eq!("/c d", query["a b"]);
+        assert_eq!("", query["flag"]);
+        assert!(parse_query("a=%2").is_err());
+        assert!(parse_query("a=%FF").is_err());
+    }
+
+    #[test]
+    fn csv() {
+        let data = Data::from_columns(
+            Timestamp::from_timestamp(1_735_689_600, 0).unwrap(),
+            Duration::from_secs(300),
+            vec![
+                ("in".to_string(), vec![1.5, f64::NAN]),
+                ("out".to_string(), vec![-2.0, 3.0]),
+            ],
+        )
+        .unwrap();
+        assert_eq!(
+            "timestamp,in,out\n\
+             2025-01-01T00:00:00Z,1.5,-2\n\
+             2025-01-01T00:05:00Z,,3\n",
+            to_csv(&data)
+        );
+    }
+
+    #[test]
+    fn last_update_from_info() {
+        let info = HashMap::from([
+            ("last_update".to_string(), InfoValue::Count(1_735_689_600)),
+            (
+                "ds[in].last_ds".to_string(),
+                InfoValue::String("12".to_string()),
+            ),
+            (
+                "ds[out].last_ds".to_string(),
+                InfoValue::String("U".to_string()),
+            ),
+            ("ds[in].index".to_string(), InfoValue::Count(0)),
+        ]);
+        assert_eq!(
+            LastUpdate {
+                last_update: Timestamp::from_timestamp(1_735_689_600, 0).unwrap(),
+                values: BTreeMap::from([("in".to_string(), Some(12.0)), ("out".to_string(), None)]),
+            },
+            last_update(&info).unwrap()
+        );
+        assert!(last_update(&HashMap::new()).is_err());
+    }
+}
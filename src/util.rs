@@ -1,9 +1,16 @@
 //! Miscellaneous utilities.
 
-use crate::error::{RrdError, RrdResult};
+use crate::error::{InvalidArgument, RrdError, RrdResult};
 use itertools::Itertools;
 use rrd_sys::rrd_char;
-use std::{ffi::CString, fmt, path::Path, ptr};
+use std::{
+    borrow::Cow,
+    ffi::CString,
+    fmt,
+    path::{Path, PathBuf},
+    ptr,
+    sync::LazyLock,
+};
 
 /// Conveniently convert a `Path` to a `&str`, mapping non-UTF-8 paths to `RrdError`.
 ///
@@ -46,6 +53,141 @@ pub fn path_to_str(path: &Path) -> RrdResult<&str> {
     path.to_str().ok_or(RrdError::PathEncodingError)
 }
 
+/// A key that identifies the file at `path`, so that different spellings of the same path, or
+/// symlinks to the same file, get the same key.
+///
+/// The path is made canonical if the file exists, otherwise absolute.
+pub(crate) fn file_key(path: &Path) -> PathBuf {
+    path.canonicalize()
+        .or_else(|_| std::path::absolute(path))
+        .unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Escape `field`, which will be one of the `:`-separated fields in an arg, so that `librrd` reads
+/// it back exactly.
+///
+/// `librrd` (since 1.5) only unescapes `\:`, leaving other backslashes alone so that text can end
+/// in control sequences like `\l`. A trailing backslash would escape the following separator, so
+/// it's rejected.
+#[cfg(feature = "graph")]
+pub(crate) fn escape_field(field: &str) -> Result<String, InvalidArgument> {
+    if field.ends_with('\\') {
+        return Err(InvalidArgument("Text can't end with a backslash"));
+    }
+    Ok(field.replace(':', "\\:"))
+}
+
+/// Returns an error if `name` isn't a valid DS name: 1 to 19 ASCII letters, digits or `_`.
+pub(crate) fn check_ds_name(name: &str) -> Result<(), InvalidArgument> {
+    static VALID_DS_NAME: LazyLock<regex::Regex> =
+        LazyLock::new(|| regex::Regex::new("^[A-Za-z0-9_]{1,19}$").unwrap());
+    if VALID_DS_NAME.is_match(name) {
+        Ok(())
+    } else {
+        Err(InvalidArgument("Invalid DS name"))
+    }
+}
+
+/// Quote `arg` for a POSIX shell, if it contains anything other than characters that are never
+/// special to the shell.
+///
+/// # Examples
+/// ```
+/// use rrd::util::shell_quote;
+///
+/// assert_eq!("DEF:v=data.rrd:ds:AVERAGE", shell_quote("DEF:v=data.rrd:ds:AVERAGE"));
+/// assert_eq!("'COMMENT:It'\\''s 5\\:00'", shell_quote("COMMENT:It's 5\\:00"));
+/// ```
+pub fn shell_quote(arg: &str) -> Cow<'_, str> {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "_-+=:,./@%".contains(c);
+    if !arg.is_empty() && arg.chars().all(is_plain) {
+        Cow::Borrowed(arg)
+    } else {
+        // Nothing is special inside single quotes, so only `'` itself needs handling
+        Cow::Owned(format!("'{}'", arg.replace('\'', r"'\''")))
+    }
+}
+
+/// Split a POSIX shell command line into args, the inverse of joining args quoted with
+/// [`shell_quote`].
+///
+/// Handles single and double quotes, backslash escapes, and backslash-newline line continuations.
+/// Returns an error for unterminated quotes, and for variable or command substitution (`$` or
+/// `` ` `` outside single quotes), since those can't be expanded here.
+///
+/// # Examples
+/// ```
+/// use rrd::util::shell_split;
+///
+/// assert_eq!(
+///     vec!["rrdtool", "graph", "COMMENT:It's 5\\:00"],
+///     shell_split("rrdtool graph \\\n  'COMMENT:It'\\''s 5\\:00'").unwrap()
+/// );
+/// ```
+pub fn shell_split(command: &str) -> Result<Vec<String>, InvalidArgument> {
+    let mut args = vec![];
+    // `None` between args, so that `''` is an empty arg rather than nothing
+    let mut current: Option<String> = None;
+    let mut chars = command.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                args.extend(current.take());
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some(escaped) => current.get_or_insert_with(String::new).push(escaped),
+                None => return Err(InvalidArgument("Command line ends with a backslash")),
+            },
+            '\'' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => arg.push(c),
+                        None => return Err(InvalidArgument("Unterminated single quote")),
+                    }
+                }
+            }
+            '"' => {
+                let arg = current.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('\n') => {}
+                            Some(c @ ('$' | '`' | '"' | '\\')) => arg.push(c),
+                            Some(c) => {
+                                arg.push('\\');
+                                arg.push(c);
+                            }
+                            None => return Err(InvalidArgument("Unterminated double quote")),
+                        },
+                        Some('$' | '`') => {
+                            return Err(InvalidArgument("Shell expansion isn't supported"))
+                        }
+                        Some(c) => arg.push(c),
+                        None => return Err(InvalidArgument("Unterminated double quote")),
+                    }
+                }
+            }
+            '$' | '`' => return Err(InvalidArgument("Shell expansion isn't supported")),
+            c => current.get_or_insert_with(String::new).push(c),
+        }
+    }
+    args.extend(current);
+    Ok(args)
+}
+
+/// Returns an `rrdtool` command line for `args`, quoted with [`shell_quote`], so that it can be
+/// pasted into a shell.
+pub(crate) fn rrdtool_command(args: &[String]) -> String {
+    std::iter::once("rrdtool")
+        .chain(args.iter().map(String::as_str))
+        .map(shell_quote)
+        .join(" ")
+}
+
 pub(crate) struct MaybeNullTerminatedArrayOfStrings<const IS_NULL_TERMINATED: bool> {
     /// Keep the strings so they can be dropped
     cstrings: Vec<CString>,
@@ -113,11 +255,84 @@ pub(crate) type ArrayOfStrings = MaybeNullTerminatedArrayOfStrings<false>;
 /// expect the last pointer in the array to be null.
 pub(crate) type NullTerminatedArrayOfStrings = MaybeNullTerminatedArrayOfStrings<true>;
 
+/// Serialize `NaN`, which `librrd` uses for unknown values, as `null`, since formats like JSON
+/// can't represent it.
+///
+/// For use with `#[serde(with = "...")]`.
+#[cfg(feature = "serde")]
+pub(crate) mod nan_as_null {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(crate) fn serialize<S: Serializer>(value: &f64, serializer: S) -> Result<S::Ok, S::Error> {
+        if value.is_nan() {
+            serializer.serialize_none()
+        } else {
+            serializer.serialize_some(value)
+        }
+    }
+
+    pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+        Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::ptr::null_mut;
 
+    #[test]
+    fn file_key_resolves_aliases() {
+        let tempdir = tempfile::tempdir().unwrap();
+        let file = tempdir.path().join("a.rrd");
+        std::fs::write(&file, b"").unwrap();
+
+        let key = file_key(&file);
+        assert_eq!(key, file_key(&tempdir.path().join("./a.rrd")));
+        #[cfg(unix)]
+        {
+            let link = tempdir.path().join("link.rrd");
+            std::os::unix::fs::symlink(&file, &link).unwrap();
+            assert_eq!(key, file_key(&link));
+        }
+        // missing files still get an absolute key
+        assert!(file_key(Path::new("missing.rrd")).is_absolute());
+    }
+
+    #[test]
+    fn shell_split_inverts_shell_quote() {
+        let args = [
+            "plain",
+            "",
+            "two words",
+            "it's",
+            r"back\slash",
+            "\"double\"",
+            "$HOME",
+            "tab\tand\nnewline",
+        ];
+        let command = args.iter().map(|a| shell_quote(a)).join(" ");
+        assert_eq!(args.to_vec(), shell_split(&command).unwrap());
+    }
+
+    #[test]
+    fn shell_split_double_quotes() {
+        assert_eq!(
+            vec![r#"a "b" \c \d"#, "e"],
+            shell_split(r#""a \"b\" \\c \d" e"#).unwrap()
+        );
+        for invalid in [
+            "'open",
+            "\"open",
+            "trailing\\",
+            "$HOME",
+            "\"$(date)\"",
+            "`date`",
+        ] {
+            assert!(shell_split(invalid).is_err(), "{invalid}");
+        }
+    }
+
     #[test]
     fn array_all_ptrs_non_null() {
         let array = ArrayOfStrings::new(["one", "two"]).unwrap();
@@ -162,4 +377,22 @@ mod tests {
         assert!(array.is_empty());
         assert_eq!(array.as_ptr(), null_mut());
     }
+
+    #[test]
+    fn shell_quoting() {
+        assert_eq!("--start", shell_quote("--start"));
+        assert_eq!("''", shell_quote(""));
+        assert_eq!("'a b'", shell_quote("a b"));
+        assert_eq!("'$HOME'", shell_quote("$HOME"));
+        assert_eq!("'a\nb'", shell_quote("a\nb"));
+        assert_eq!(r"''\'''", shell_quote("'"));
+        assert_eq!(
+            "rrdtool fetch 'my file.rrd' AVERAGE",
+            rrdtool_command(&[
+                "fetch".to_string(),
+                "my file.rrd".to_string(),
+                "AVERAGE".to_string()
+            ])
+        );
+    }
 }
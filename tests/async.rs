@@ -0,0 +1,61 @@
+#![cfg(feature = "async")]
+
+use rrd::{
+    ops::{create, tokio as rrd_tokio, update},
+    ConsolidationFn, Timestamp,
+};
+use std::time;
+
+#[tokio::test]
+async fn concurrent_updates_to_one_file() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let rrd_path = tempdir.path().join("data.rrd");
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+
+    rrd_tokio::create(
+        rrd_path.clone(),
+        start,
+        time::Duration::from_secs(300),
+        true,
+        None,
+        vec![],
+        vec![create::DataSource::gauge(
+            create::DataSourceName::new("temp"),
+            600,
+            None,
+            None,
+        )],
+        vec![create::Archive::new(ConsolidationFn::Avg, 0.5, 1, 24)?],
+    )
+    .await?;
+
+    // Updates must be in time order, so each task waits for the previous one to be queued
+    let mut tasks = Vec::new();
+    for i in 1..=12_i64 {
+        let ts = Timestamp::from_timestamp(920804400 + i * 300, 0).unwrap();
+        tasks.push(tokio::spawn(rrd_tokio::update_all(
+            rrd_path.clone(),
+            update::Options::default(),
+            vec![(update::BatchTime::from(ts), [update::Datum::from(i as f64)])],
+        )));
+        tokio::task::yield_now().await;
+    }
+    for task in tasks {
+        task.await??;
+    }
+
+    let fetched = rrd_tokio::fetch(
+        rrd_path.clone(),
+        ConsolidationFn::Avg,
+        start,
+        Timestamp::from_timestamp(920808000, 0).unwrap(),
+        time::Duration::from_secs(300),
+    )
+    .await?;
+    assert_eq!(vec!["temp".to_string()], fetched.ds_names());
+
+    let info = rrd_tokio::info(rrd_path).await?;
+    assert!(info.contains_key("last_update"));
+
+    Ok(())
+}
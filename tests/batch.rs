@@ -0,0 +1,100 @@
+use rrd::{
+    error::{LibRrdErrorKind, Operation, RrdError},
+    ops::{
+        batch::{Executor, Job, JobOutput},
+        create, update,
+    },
+    ConsolidationFn, Timestamp,
+};
+use std::{num::NonZeroUsize, time};
+
+#[test]
+fn batch_of_updates_fetches_and_infos() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+    let step = time::Duration::from_secs(300);
+    let paths = (0..10)
+        .map(|i| tempdir.path().join(format!("{i}.rrd")))
+        .collect::<Vec<_>>();
+    for path in &paths {
+        create::create(
+            path,
+            start,
+            step,
+            true,
+            None,
+            &[],
+            &[create::DataSource::gauge(
+                create::DataSourceName::new("temp"),
+                600,
+                None,
+                None,
+            )],
+            &[create::Archive::new(ConsolidationFn::Avg, 0.5, 1, 24)?],
+        )?;
+    }
+
+    // Several updates per file, which must be applied in order
+    let mut jobs = Vec::new();
+    for row in 1..=5_u32 {
+        for path in &paths {
+            jobs.push(Job::Update {
+                filename: path.clone(),
+                options: update::Options::default(),
+                data: vec![(
+                    update::BatchTime::from(start + step * row),
+                    vec![update::Datum::from(f64::from(row))],
+                )],
+            });
+        }
+    }
+    for path in &paths {
+        jobs.push(Job::Fetch {
+            filename: path.clone(),
+            cf: ConsolidationFn::Avg,
+            start,
+            end: start + step * 5,
+            resolution: step,
+            options: Default::default(),
+        });
+    }
+    let missing = tempdir.path().join("missing.rrd");
+    jobs.push(Job::Info {
+        filename: missing.clone(),
+    });
+    jobs.push(Job::Info {
+        filename: paths[0].clone(),
+    });
+
+    let report = Executor::new(NonZeroUsize::new(4).unwrap()).run(jobs);
+
+    assert_eq!(1, report.failure_count());
+    assert!(report.total_job_time() >= report.max_job_time());
+    let mut results = report.results.into_iter();
+    for _ in 0..50 {
+        assert!(matches!(
+            results.next().unwrap().output,
+            Ok(JobOutput::Update)
+        ));
+    }
+    for _ in 0..10 {
+        match results.next().unwrap().output {
+            Ok(JobOutput::Fetch(data)) => assert_eq!(vec!["temp".to_string()], data.ds_names()),
+            _ => panic!("Expected fetch output"),
+        }
+    }
+    match results.next().unwrap().output {
+        Err(RrdError::LibRrdError(error)) => {
+            assert_eq!(Operation::Info, error.operation());
+            assert_eq!(Some(missing.as_path()), error.path());
+            assert_eq!(LibRrdErrorKind::FileNotFound, error.kind());
+        }
+        _ => panic!("Expected error for missing file"),
+    }
+    assert!(matches!(
+        results.next().unwrap().output,
+        Ok(JobOutput::Info(_))
+    ));
+
+    Ok(())
+}
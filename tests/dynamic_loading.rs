@@ -0,0 +1,65 @@
+#![cfg(feature = "dynamic-loading")]
+
+use rrd::{
+    data::Data,
+    error::RrdError,
+    ops::{fetch, info, update, version},
+    ConsolidationFn,
+};
+use std::{path::Path, time::Duration};
+
+#[test]
+fn missing_library_is_reported_as_error() {
+    let missing = "/nonexistent/librrd.so.8";
+    // Set before anything loads librrd, and this is the only test in this binary
+    std::env::set_var("RRD_SYS_LIBRARY", missing);
+
+    assert!(!version::is_available());
+    assert_unavailable(version::linked_version().map(|_| ()), missing);
+    assert_unavailable(info::info(Path::new("x.rrd")).map(|_| ()), missing);
+    assert_unavailable(
+        update::update_all(
+            Path::new("x.rrd"),
+            update::ExtraFlags::empty(),
+            &[(update::BatchTime::Now, [update::Datum::from(1_u64)])],
+        ),
+        missing,
+    );
+    let now = rrd::chrono::Utc::now();
+    assert_unavailable(
+        fetch::fetch(
+            Path::new("x.rrd"),
+            ConsolidationFn::Avg,
+            now,
+            now,
+            Duration::from_secs(1),
+        )
+        .map(|_| ()),
+        missing,
+    );
+
+    // Pure Rust parts still work
+    let data = Data::from_rows(
+        now,
+        Duration::from_secs(1),
+        vec!["a".to_string()],
+        vec![1.0, 2.0],
+    )
+    .unwrap();
+    assert_eq!(2, data.row_count());
+
+    // Still missing when loaded explicitly
+    assert!(matches!(
+        version::load_librrd(missing),
+        Err(RrdError::LibraryUnavailable(_))
+    ));
+}
+
+fn assert_unavailable(result: Result<(), RrdError>, path: &str) {
+    match result {
+        Err(RrdError::LibraryUnavailable(message)) => {
+            assert!(message.contains(path), "{message}")
+        }
+        other => panic!("Unexpected {other:?}"),
+    }
+}
@@ -0,0 +1,60 @@
+use rrd::{
+    error::{LibRrdErrorKind, Operation, RrdError},
+    ops::{create, fetch, update},
+    ConsolidationFn, Timestamp,
+};
+use std::time;
+
+#[test]
+fn librrd_errors_are_classified() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let rrd_path = tempdir.path().join("data.rrd");
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+
+    create::create(
+        &rrd_path,
+        start,
+        time::Duration::from_secs(300),
+        true,
+        None,
+        &[],
+        &[create::DataSource::gauge(
+            create::DataSourceName::new("temp"),
+            600,
+            None,
+            None,
+        )],
+        &[create::Archive::new(ConsolidationFn::Avg, 0.5, 1, 24)?],
+    )?;
+
+    // Updating with the start time is not after the last update
+    let error = update::update_all(
+        &rrd_path,
+        update::Options::default(),
+        &[(update::BatchTime::from(start), [update::Datum::from(1.0)])],
+    )
+    .unwrap_err();
+    let RrdError::LibRrdError(error) = error else {
+        panic!("Expected librrd error, got {error:?}");
+    };
+    assert_eq!(LibRrdErrorKind::IllegalUpdateTime, error.kind(), "{error}");
+    assert_eq!(Operation::Update, error.operation());
+    assert_eq!(Some(rrd_path.as_path()), error.path());
+
+    let error = fetch::fetch(
+        &rrd_path,
+        ConsolidationFn::Max,
+        start,
+        start + time::Duration::from_secs(3600),
+        time::Duration::from_secs(300),
+    )
+    .err()
+    .expect("No MAX RRA");
+    assert_eq!(
+        Some(LibRrdErrorKind::NoMatchingArchive),
+        error.librrd_kind(),
+        "{error}"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+use itertools::Itertools;
+use rrd::{
+    ops::{create, fetch, update},
+    ConsolidationFn, Timestamp,
+};
+use std::time;
+
+#[test]
+fn fetch_multi_combines_consolidation_fns() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let rrd_path = tempdir.path().join("data.rrd");
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+
+    create::create(
+        &rrd_path,
+        start,
+        time::Duration::from_secs(300),
+        true,
+        None,
+        &[],
+        &[create::DataSource::gauge(
+            create::DataSourceName::new("temp"),
+            600,
+            None,
+            None,
+        )],
+        &[
+            create::Archive::new(ConsolidationFn::Avg, 0.5, 3, 10)?,
+            create::Archive::new(ConsolidationFn::Min, 0.5, 3, 10)?,
+            create::Archive::new(ConsolidationFn::Max, 0.5, 3, 10)?,
+        ],
+    )?;
+
+    let update_data = (1..=12)
+        .map(|i| {
+            (
+                update::BatchTime::from(Timestamp::from_timestamp(920804400 + i * 300, 0).unwrap()),
+                [update::Datum::from(i as f64)],
+            )
+        })
+        .collect_vec();
+    update::update_all(&rrd_path, update::Options::default(), &update_data)?;
+
+    let fetched = fetch::fetch_multi(
+        &rrd_path,
+        &[
+            ConsolidationFn::Avg,
+            ConsolidationFn::Min,
+            ConsolidationFn::Max,
+        ],
+        start,
+        Timestamp::from_timestamp(920808000, 0).unwrap(),
+        time::Duration::from_secs(900),
+        fetch::FetchOptions { align_start: true },
+    )?;
+
+    assert_eq!(
+        vec!["temp_AVERAGE", "temp_MIN", "temp_MAX"],
+        fetched.ds_names()
+    );
+    assert_eq!(time::Duration::from_secs(900), fetched.step());
+    let known_rows = fetched
+        .rows()
+        .iter()
+        .filter(|row| row.iter().all(|v| !v.is_nan()))
+        .collect_vec();
+    assert!(!known_rows.is_empty());
+    for row in known_rows {
+        let (avg, min, max) = (row[0], row[1], row[2]);
+        assert!(min < avg && avg < max, "{row:?}");
+    }
+
+    Ok(())
+}
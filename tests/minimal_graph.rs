@@ -1,4 +1,7 @@
+#![cfg(feature = "graph")]
+
 use rrd::{
+    data::Data,
     error::RrdResult,
     ops::{
         create, graph,
@@ -101,25 +104,156 @@ fn minimal_graph() -> anyhow::Result<()> {
         );
     }
 
+    // writing to a file, which `lazy` skips while the file is recent
+    {
+        let image_path = tempdir.path().join("graph.png");
+        let render = |lazy| {
+            graph::graph_to_file(
+                &image_path,
+                props::ImageFormat::Png,
+                graph_props(),
+                &graph_elements(rrd_path.clone(), ds_name)?,
+                lazy,
+            )
+        };
+
+        assert!(matches!(
+            render(true)?,
+            graph::GraphFileOutcome::Regenerated(_)
+        ));
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &std::fs::read(&image_path)?[..8]);
+        // 3000s over 400px is 7.5s per pixel, so the file just written is recent enough
+        assert_eq!(graph::GraphFileOutcome::Unchanged(vec![]), render(true)?);
+        assert!(matches!(
+            render(false)?,
+            graph::GraphFileOutcome::Regenerated(_)
+        ));
+    }
+
+    // a generated graph of every DS
+    {
+        let (props, elements) = graph::quick_graph(&rrd_path, graph_props().time_range)?;
+        let (image, metadata) = graph::graph(props::ImageFormat::Png, props, &elements)?;
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &image[..8]);
+        assert_eq!(
+            vec!["gauge"],
+            metadata
+                .legends
+                .iter()
+                .map(|l| l.text.trim())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // several graphs in one image
+    {
+        let mut dashboard = graph::dashboard::Dashboard::new(2);
+        dashboard.title = Some("Dashboard".to_string());
+        dashboard.time_range = graph_props().time_range;
+        for _ in 0..3 {
+            dashboard.add(graph_props(), graph_elements(rrd_path.clone(), ds_name)?);
+        }
+
+        let (image, layout) = dashboard.render(props::ImageFormat::Svg)?;
+        let image = String::from_utf8(image)?;
+        assert_eq!(3, image.matches("<svg").count() - 1);
+        assert_eq!(
+            vec![(10, 40), (501, 40), (10, 191)],
+            layout.cells.iter().map(|c| (c.x, c.y)).collect::<Vec<_>>()
+        );
+        assert_eq!((992, 342), (layout.width, layout.height));
+
+        #[cfg(feature = "png")]
+        {
+            let (image, layout) = dashboard.render(props::ImageFormat::Png)?;
+            assert_eq!(b"\x89PNG\r\n\x1a\n", &image[..8]);
+            assert_eq!(3, layout.cells.len());
+        }
+
+        // zoomed graphs are laid out at their zoomed size
+        let mut zoomed = graph::dashboard::Dashboard::new(2);
+        zoomed.time_range = graph_props().time_range;
+        for _ in 0..3 {
+            let mut props = graph_props();
+            props.misc.zoom = Some(props::Zoom::new(2.0)?);
+            zoomed.add(props, graph_elements(rrd_path.clone(), ds_name)?);
+        }
+        let check_zoomed = |image_format| -> RrdResult<()> {
+            let (_, layout) = zoomed.render(image_format)?;
+            assert_eq!(
+                vec![(10, 10), (982, 10), (10, 302)],
+                layout.cells.iter().map(|c| (c.x, c.y)).collect::<Vec<_>>(),
+                "{image_format:?}"
+            );
+            assert_eq!((1954, 594), (layout.width, layout.height));
+            Ok(())
+        };
+        check_zoomed(props::ImageFormat::Svg)?;
+        #[cfg(feature = "png")]
+        check_zoomed(props::ImageFormat::Png)?;
+    }
+
+    // an rrdcgi template
+    {
+        let mut processor = graph::template::TemplateProcessor::default();
+        processor
+            .vars
+            .insert("rrd".to_string(), rrd_path.to_string_lossy().into_owned());
+        let page = processor.process(
+            "<P><RRD::GRAPH gauge.png --lazy --start 1737316000 --end 1737319000 \
+             DEF:g=<RRD::GETVAR rrd>:gauge:AVERAGE VDEF:max=g,MAXIMUM LINE4:g \
+             PRINT:max:%.0lf></P> max <RRD::PRINT 0>",
+        )?;
+        assert_eq!(
+            r#"<P><IMG SRC="gauge.png" WIDTH="481" HEIGHT="141"></P> max 10"#,
+            page.html
+        );
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &page.graphs[0].image[..8]);
+    }
+
+    // and the data formats, which should all parse to the same data
+    let exported = [
+        props::DataFormat::Csv,
+        props::DataFormat::Tsv,
+        props::DataFormat::Ssv,
+        props::DataFormat::Json,
+        props::DataFormat::JsonTime,
+        props::DataFormat::Xml,
+        props::DataFormat::XmlEnum,
+    ]
+    .into_iter()
+    .map(|format| export_data(rrd_path.clone(), format, ds_name))
+    .collect::<RrdResult<Vec<_>>>()?;
+    for data in &exported {
+        assert_eq!(&["gauge value".to_string()], data.ds_names());
+        let known = data
+            .column("gauge value")
+            .unwrap()
+            .iter_known()
+            .flatten()
+            .collect::<Vec<_>>();
+        assert!(!known.is_empty());
+        assert!(known.iter().all(|v| *v == 10.0), "{known:?}");
+        assert_eq!(exported[0].start(), data.start());
+        assert_eq!(exported[0].step(), data.step());
+        assert_eq!(exported[0].row_count(), data.row_count());
+    }
+
     Ok(())
 }
 
-fn build_graph(
+fn export_data(
     rrd_path: path::PathBuf,
-    img_format: props::ImageFormat,
+    format: props::DataFormat,
     ds_name: &str,
-) -> RrdResult<Vec<u8>> {
+) -> RrdResult<Data<Vec<f64>>> {
     let var_name_g = elements::VarName::new("g".to_string())?;
-    // a little before and a little after the data points in update()
-    let start = Timestamp::from_timestamp(1737316000, 0).unwrap();
-    let end = Timestamp::from_timestamp(1737319000, 0).unwrap();
-
-    let (image, metadata) = graph::graph(
-        img_format,
+    graph::graph_data(
+        format,
         props::GraphProps {
             time_range: props::TimeRange {
-                start: Some(start),
-                end: Some(end),
+                start: Some(Timestamp::from_timestamp(1737316000, 0).unwrap()),
+                end: Some(Timestamp::from_timestamp(1737319000, 0).unwrap()),
                 ..Default::default()
             },
             ..Default::default()
@@ -137,16 +271,34 @@ fn build_graph(
             }
             .into(),
             elements::Line {
-                width: 4.0,
-                value: var_name_g.clone(),
-                color: None,
+                width: 1.0,
+                value: var_name_g,
+                color: Some(elements::ColorWithLegend {
+                    color: "#FF0000".parse()?,
+                    legend: Some(elements::Legend::new("gauge value")),
+                }),
                 stack: false,
                 skip_scale: false,
                 dashes: None,
             }
             .into(),
         ],
+    )
+}
+
+fn build_graph(
+    rrd_path: path::PathBuf,
+    img_format: props::ImageFormat,
+    ds_name: &str,
+) -> RrdResult<Vec<u8>> {
+    let (image, metadata) = graph::graph(
+        img_format,
+        graph_props(),
+        &graph_elements(rrd_path, ds_name)?,
     )?;
+    let props = graph_props();
+    let start = props.time_range.start.unwrap();
+    let end = props.time_range.end.unwrap();
 
     assert_eq!(
         graph::GraphMetadata {
@@ -161,6 +313,8 @@ fn build_graph(
             // not clear why these are 9 and 20, but none of this is documented so...
             value_min: 9.0,
             value_max: 20.0,
+            prints: vec![],
+            legends: vec![],
             extra_info: Default::default(),
         },
         metadata
@@ -168,3 +322,44 @@ fn build_graph(
 
     Ok(image)
 }
+
+fn graph_props() -> props::GraphProps {
+    props::GraphProps {
+        // a little before and a little after the data points in update()
+        time_range: props::TimeRange {
+            start: Some(Timestamp::from_timestamp(1737316000, 0).unwrap()),
+            end: Some(Timestamp::from_timestamp(1737319000, 0).unwrap()),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+fn graph_elements(
+    rrd_path: path::PathBuf,
+    ds_name: &str,
+) -> RrdResult<Vec<elements::GraphElement>> {
+    let var_name_g = elements::VarName::new("g".to_string())?;
+    Ok(vec![
+        elements::Def {
+            var_name: var_name_g.clone(),
+            rrd: rrd_path,
+            ds_name: ds_name.to_string(),
+            consolidation_fn: ConsolidationFn::Avg,
+            step: None,
+            start: None,
+            end: None,
+            reduce: None,
+        }
+        .into(),
+        elements::Line {
+            width: 4.0,
+            value: var_name_g,
+            color: None,
+            stack: false,
+            skip_scale: false,
+            dashes: None,
+        }
+        .into(),
+    ])
+}
@@ -0,0 +1,174 @@
+#![cfg(feature = "server")]
+
+use rrd::{
+    ops::{
+        create,
+        graph::{elements, props},
+        update,
+    },
+    server::{GraphRequest, Server, ServerConfig},
+    ConsolidationFn, Timestamp,
+};
+use std::{
+    io::{Read, Write},
+    net, path,
+    sync::Arc,
+    thread, time,
+};
+
+#[test]
+fn serves_info_fetch_lastupdate_and_graphs() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    std::fs::create_dir(tempdir.path().join("hosts"))?;
+    let rrd_path = tempdir.path().join("hosts/db1.rrd");
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+    create::create(
+        &rrd_path,
+        start,
+        time::Duration::from_secs(300),
+        true,
+        None,
+        &[],
+        &[create::DataSource::gauge(
+            create::DataSourceName::new("load"),
+            600,
+            None,
+            None,
+        )],
+        &[create::Archive::new(ConsolidationFn::Avg, 0.5, 1, 10).unwrap()],
+    )?;
+    update::update(
+        &rrd_path,
+        &["load"],
+        update::ExtraFlags::empty(),
+        &[
+            (
+                (start + time::Duration::from_secs(300)).into(),
+                [1.5.into()],
+            ),
+            (
+                (start + time::Duration::from_secs(600)).into(),
+                [2.5.into()],
+            ),
+        ],
+    )?;
+
+    let mut config = ServerConfig::new(tempdir.path())?;
+    config.max_concurrent = 2;
+    let server = Arc::new(Server::bind("127.0.0.1:0", config)?);
+    let addr = server.local_addr().unwrap();
+    let running = thread::spawn({
+        let server = server.clone();
+        move || server.run()
+    });
+
+    let (status, body) = request(addr, "GET", "/rrd/hosts/db1.rrd/info", "")?;
+    assert_eq!(200, status);
+    let info: serde_json::Value = serde_json::from_slice(&body)?;
+    assert_eq!(serde_json::json!({ "Count": 300 }), info["step"]);
+
+    let (status, body) = request(addr, "GET", "/rrd/hosts%2Fdb1.rrd/lastupdate", "")?;
+    assert_eq!(200, status);
+    assert_eq!(
+        serde_json::json!({
+            "last_update": "1999-03-07T05:10:00Z",
+            "values": { "load": 2.5 },
+        }),
+        serde_json::from_slice::<serde_json::Value>(&body)?
+    );
+
+    let fetch = "/rrd/hosts/db1.rrd/fetch?cf=AVERAGE&start=920804400&end=1999-03-07T05:10:00Z";
+    let (status, body) = request(addr, "GET", &format!("{fetch}&format=csv"), "")?;
+    assert_eq!(200, status);
+    let csv = String::from_utf8(body)?;
+    assert!(
+        csv.starts_with("timestamp,load\n") && csv.contains("1999-03-07T05:05:00Z,1.5\n"),
+        "{csv}"
+    );
+    let (status, body) = request(addr, "GET", fetch, "")?;
+    assert_eq!(200, status);
+    let data: rrd::data::Data<Vec<f64>> = serde_json::from_slice(&body)?;
+    assert_eq!(
+        Some(2.5),
+        data.value_at(start + time::Duration::from_secs(600), "load")
+    );
+
+    let graph = GraphRequest {
+        image_format: props::ImageFormat::Svg,
+        props: props::GraphProps {
+            time_range: props::TimeRange {
+                start: Some(start),
+                end: Some(start + time::Duration::from_secs(3000)),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        elements: vec![
+            elements::Def {
+                var_name: elements::VarName::new("load")?,
+                rrd: path::PathBuf::from("hosts/db1.rrd"),
+                ds_name: "load".to_string(),
+                consolidation_fn: ConsolidationFn::Avg,
+                step: None,
+                start: None,
+                end: None,
+                reduce: None,
+            }
+            .into(),
+            elements::Line {
+                width: 1.0,
+                value: elements::VarName::new("load")?,
+                color: None,
+                stack: false,
+                skip_scale: false,
+                dashes: None,
+            }
+            .into(),
+        ],
+    };
+    let (status, body) = request(addr, "POST", "/graph", &serde_json::to_string(&graph)?)?;
+    assert_eq!(200, status, "{}", String::from_utf8_lossy(&body));
+    assert!(body.starts_with(b"<?xml"));
+
+    // errors
+    let (status, _) = request(addr, "GET", "/rrd/hosts/db2.rrd/info", "")?;
+    assert_eq!(404, status);
+    let (status, _) = request(addr, "GET", "/rrd/../../etc/passwd/info", "")?;
+    assert_eq!(404, status);
+    let (status, _) = request(addr, "GET", "/rrd/hosts/db1.rrd/fetch?cf=MIN", "")?;
+    assert_eq!(400, status);
+
+    server.shutdown();
+    running.join().unwrap();
+    Ok(())
+}
+
+/// Send a request, returning the status and body of the response.
+fn request(
+    addr: net::SocketAddr,
+    method: &str,
+    url: &str,
+    body: &str,
+) -> anyhow::Result<(u16, Vec<u8>)> {
+    let mut stream = net::TcpStream::connect(addr)?;
+    write!(
+        stream,
+        "{method} {url} HTTP/1.1\r\nHost: {addr}\r\nConnection: close\r\n\
+         Content-Length: {}\r\n\r\n{body}",
+        body.len()
+    )?;
+    let mut response = vec![];
+    stream.read_to_end(&mut response)?;
+
+    let header_end = response
+        .windows(4)
+        .position(|w| w == b"\r\n\r\n")
+        .ok_or_else(|| anyhow::anyhow!("No end of headers"))?;
+    let status_line = String::from_utf8_lossy(&response[..header_end]);
+    let status = status_line
+        .split(' ')
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("No status"))?
+        .parse()?;
+    Ok((status, response[header_end + 4..].to_vec()))
+}
@@ -0,0 +1,135 @@
+use rrd::{
+    error::{LibRrdErrorKind, RrdError},
+    ops::{create, fetch, info, update},
+    ConsolidationFn, Timestamp,
+};
+use std::{path::Path, thread, time};
+
+const THREADS: usize = 8;
+const ITERATIONS: usize = 50;
+
+/// Interleave failing and succeeding calls across threads, and check that every failure reports
+/// the error for its own call.
+#[test]
+fn errors_are_attributed_to_the_failing_call() -> anyhow::Result<()> {
+    let tempdir = tempfile::tempdir()?;
+    let start = Timestamp::from_timestamp(920804400, 0).unwrap();
+
+    thread::scope(|scope| {
+        let handles = (0..THREADS)
+            .map(|thread_index| {
+                let dir = tempdir.path();
+                scope.spawn(move || -> anyhow::Result<()> {
+                    let rrd_path = dir.join(format!("thread-{thread_index}.rrd"));
+                    create_rrd(&rrd_path, start)?;
+
+                    for iteration in 0..ITERATIONS {
+                        let missing = dir.join(format!("missing-{thread_index}-{iteration}.rrd"));
+                        let missing_str = missing.to_str().unwrap();
+
+                        assert_error_mentions(info::info(&missing), missing_str);
+                        info::info(&rrd_path)?;
+
+                        assert_error_mentions(
+                            fetch::fetch(
+                                &missing,
+                                ConsolidationFn::Avg,
+                                start,
+                                start + time::Duration::from_secs(3600),
+                                time::Duration::from_secs(300),
+                            ),
+                            missing_str,
+                        );
+                        update::update_all(
+                            &rrd_path,
+                            update::Options::default(),
+                            &[(
+                                update::BatchTime::from(
+                                    start + time::Duration::from_secs(300 * (iteration as u64 + 1)),
+                                ),
+                                [update::Datum::from(iteration as f64)],
+                            )],
+                        )?;
+
+                        // graph is serialized internally, but its errors must still be correct
+                        #[cfg(feature = "graph")]
+                        assert_error_mentions(graph_missing(&missing), missing_str);
+                    }
+                    Ok(())
+                })
+            })
+            .collect::<Vec<_>>();
+
+        handles
+            .into_iter()
+            .try_for_each(|handle| handle.join().expect("Thread panicked"))
+    })
+}
+
+fn create_rrd(rrd_path: &Path, start: Timestamp) -> anyhow::Result<()> {
+    create::create(
+        rrd_path,
+        start,
+        time::Duration::from_secs(300),
+        true,
+        None,
+        &[],
+        &[create::DataSource::gauge(
+            create::DataSourceName::new("speed"),
+            600,
+            None,
+            None,
+        )],
+        &[create::Archive::new(ConsolidationFn::Avg, 0.5, 1, 100)?],
+    )?;
+    Ok(())
+}
+
+/// Graph from `rrd_path`, which doesn't exist
+#[cfg(feature = "graph")]
+fn graph_missing(rrd_path: &Path) -> Result<(), RrdError> {
+    use rrd::ops::graph;
+
+    let var_name = graph::elements::VarName::new("v")?;
+    graph::graph(
+        graph::props::ImageFormat::Png,
+        graph::props::GraphProps::default(),
+        &[
+            graph::elements::Def {
+                var_name: var_name.clone(),
+                rrd: rrd_path.to_path_buf(),
+                ds_name: "speed".to_string(),
+                consolidation_fn: ConsolidationFn::Avg,
+                step: None,
+                start: None,
+                end: None,
+                reduce: None,
+            }
+            .into(),
+            graph::elements::Line {
+                width: 1.0,
+                value: var_name,
+                color: None,
+                stack: false,
+                skip_scale: false,
+                dashes: None,
+            }
+            .into(),
+        ],
+    )
+    .map(|_| ())
+}
+
+fn assert_error_mentions<T>(result: Result<T, RrdError>, expected: &str) {
+    match result {
+        Err(RrdError::LibRrdError(error)) => {
+            assert!(
+                error.message().contains(expected),
+                "Expected error about {expected}, got {error}"
+            );
+            assert_eq!(LibRrdErrorKind::FileNotFound, error.kind(), "{error}");
+        }
+        Err(other) => panic!("Expected librrd error about {expected}, got {other:?}"),
+        Ok(_) => panic!("Expected librrd error about {expected}, got success"),
+    }
+}
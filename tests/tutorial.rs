@@ -1,3 +1,5 @@
+#![cfg(feature = "graph")]
+
 use itertools::Itertools;
 use rrd::{
     ops::{
@@ -149,6 +151,8 @@ fn tutorial() -> anyhow::Result<()> {
         image_height: 141,
         value_min: 0.0,
         value_max: 0.04,
+        prints: vec![],
+        legends: vec![],
         extra_info: Default::default(),
     };
 
@@ -311,7 +315,7 @@ fn tutorial() -> anyhow::Result<()> {
                         blue: 0xFF,
                         alpha: None,
                     },
-                    legend: Some("Maximum allowed".into()),
+                    legend: Some(elements::Legend::new("Maximum allowed")),
                     dashes: None,
                 }
                 .into(),
@@ -324,7 +328,7 @@ fn tutorial() -> anyhow::Result<()> {
                             blue: 0,
                             alpha: None,
                         }),
-                        legend: Some("Good speed".into()),
+                        legend: Some(elements::Legend::new("Good speed")),
                     }),
                     stack: false,
                     skip_scale: false,
@@ -339,7 +343,7 @@ fn tutorial() -> anyhow::Result<()> {
                             blue: 0,
                             alpha: None,
                         }),
-                        legend: Some("Too fast".into()),
+                        legend: Some(elements::Legend::new("Too fast")),
                     }),
                     stack: false,
                     skip_scale: false,
@@ -354,16 +358,22 @@ fn tutorial() -> anyhow::Result<()> {
             image_width: 497,
             image_height: 155,
             value_max: 200.0,
-            extra_info: [
-                ("legend[0]", "  Maximum allowed".into()),
-                ("legend[1]", "  Good speed".into()),
-                ("legend[2]", "  Too fast".into()),
-                ("coords[0]", "16,134,135,148".into()),
-                ("coords[1]", "231,134,315,148".into()),
-                ("coords[2]", "411,134,481,148".into()),
+            legends: [
+                (4, "Maximum allowed", (16, 134, 135, 148)),
+                (5, "Good speed", (231, 134, 315, 148)),
+                (6, "Too fast", (411, 134, 481, 148)),
             ]
             .into_iter()
-            .map(|(k, v)| (k.to_string(), v))
+            .map(
+                |(element_index, text, (x0, y0, x1, y1))| graph::LegendEntry {
+                    element_index,
+                    text: text.to_string(),
+                    x: x0,
+                    y: y0,
+                    width: x1 - x0,
+                    height: y1 - y0,
+                },
+            )
             .collect(),
             ..initial_expected_metadata
         };
@@ -6,3 +6,18 @@ fn version_seems_reasonable() {
     assert!(!vers.is_empty());
     assert!(vers.starts_with("1."), "{}", vers);
 }
+
+#[test]
+fn linked_version_parses() {
+    let parsed = version::linked_version().unwrap();
+    assert_eq!(1, parsed.major);
+    assert!(version::librrd_version().starts_with(&format!("{}.{}", parsed.major, parsed.minor)));
+
+    // Bindings require at least 1.5.0
+    let capabilities = version::Capabilities::detect().unwrap();
+    assert!(capabilities.create_r2);
+    assert_eq!(
+        capabilities.locking_mode,
+        parsed >= version::LibrrdVersion::new(1, 9, 0)
+    );
+}